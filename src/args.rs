@@ -0,0 +1,338 @@
+//! Command line argument parsing.
+
+use std::{path::PathBuf, process, str::FromStr, time::Duration};
+
+use anyhow::{anyhow, bail, Context};
+use lexopt::{Arg, Parser, ValueExt};
+
+use crate::{
+    color::Color, info::InfoFormat, source::Source, FilterMode, TransparencyMode,
+    DEFAULT_CORNER_RADIUS,
+};
+
+const USAGE: &str = "\
+Usage: showimg [OPTIONS] <PATH>...
+       showimg [OPTIONS] --fd N
+
+Shows the images at PATH (files, directories or http(s) URLs) in a borderless window.
+
+Options:
+      --fd N               Read an image from file descriptor N (like a pipe), in addition to PATHs
+      --region X,Y,W,H     Open zoomed into this region of the first image (in image pixels)
+      --zoom PERCENT       Open at this zoom level (100 = one image pixel per screen pixel)
+      --snap-zoom          Snap to 100%, 200%, 300%, ... when zooming close to them
+  -o, --output PATH        Save the image (cropped to --region) to PATH instead of showing it
+      --extract-frames DIR Save every animation frame as a PNG file in DIR instead of showing it
+      --print-info[=json]  Print the properties of each image instead of showing it
+      --print-selection    Print the selected region as `X Y W H` when a window is closed
+      --no-always-on-top   Open a normal window instead of one that stays on top of others
+      --monitor N          Open on the Nth monitor (0 = first) instead of the cursor's
+      --crop-ratio W:H     Aspect ratio that Shift constrains the selection to (default 1:1)
+      --selection-color C  Tint the selection with the color C (`#RRGGBB` or `#RRGGBBAA`)
+      --selection-outline  Outline the selection with black and white dashes
+      --filter MODE        Filter magnified images with `smart`, `linear` or `nearest` filtering
+      --transparency MODE  Show transparent areas as `true` transparency, or a `light` or `dark`
+                           checkerboard
+      --bg-image PATH      Show transparent areas over the image at PATH, scaled to fill the window
+      --loop N             Play animations N times (0 = forever)
+      --no-loop            Play animations only once
+      --premultiplied      Treat the image as having premultiplied alpha
+      --backend NAME       Use the `vulkan`, `gl`, `dx12` or `metal` graphics backend
+      --title TITLE        Window title, with {name}, {w}, {h} and {zoom} placeholders
+  -r, --recursive          Include images in subdirectories of directories
+      --windows            Open every image in its own window
+      --watch              Reload the image whenever its file changes
+      --no-autocrop        Show the whole image instead of cropping to its content
+      --no-toolbar         Don't show the toolbar when hovering over the top of the window
+      --free-resize        Let the window take any aspect ratio, letterboxing the image
+      --no-vsync           Present frames without waiting for vertical sync
+      --show-fps           Show a frame rate counter
+      --rounded[=RADIUS]   Round the window corners with a radius of RADIUS pixels (default 12)
+      --auto-close SECS    Close the window after SECS seconds, unless it is interacted with
+  -h, --help               Print this help and exit
+  -V, --version            Print the version and exit
+";
+
+#[derive(Debug, Default)]
+pub struct Args {
+    /// The images to show, in playlist order (never empty).
+    pub paths: Vec<Source>,
+    /// Region of the image to show initially (in source image pixels).
+    pub region: Option<Region>,
+    /// Initial zoom level in percent (100 = one image pixel per screen pixel).
+    pub zoom: Option<f32>,
+    /// Snap to whole multiples of 100% when zooming close to one.
+    pub snap_zoom: bool,
+    /// Print the selected region to stdout on exit.
+    pub print_selection: bool,
+    /// Save every frame of the image as a PNG file in this directory instead of opening a window.
+    pub extract_frames: Option<PathBuf>,
+    /// Print the properties of the images to stdout instead of opening a window.
+    pub print_info: Option<InfoFormat>,
+    /// Save the (cropped) image to this path instead of opening a window.
+    pub output: Option<PathBuf>,
+    /// Create a normal window instead of one that stays on top of all others.
+    pub no_always_on_top: bool,
+    /// Show the whole image initially instead of cropping to its content bounds.
+    pub no_autocrop: bool,
+    /// Don't show the toolbar when hovering over the top of the window.
+    pub no_toolbar: bool,
+    /// Let the window be resized to any aspect ratio instead of the image's.
+    pub free_resize: bool,
+    /// Present frames as soon as possible instead of waiting for vertical sync.
+    pub no_vsync: bool,
+    /// Index of the monitor to open the window on, instead of the one containing the cursor.
+    pub monitor: Option<usize>,
+    /// Show the frame rate and frame time in the corner of the window.
+    pub show_fps: bool,
+    /// Round the window corners with this radius (in logical pixels).
+    pub rounded: Option<f32>,
+    /// Close the window after this long, unless the user interacts with it first.
+    pub auto_close: Option<Duration>,
+    /// Color to tint the selection with, instead of the configured one.
+    pub selection_color: Option<Color>,
+    /// Outline the selection with black and white dashes.
+    pub selection_outline: bool,
+    /// Background for transparent images, instead of the one selected last.
+    pub transparency: Option<TransparencyMode>,
+    /// Image to show behind transparent areas instead of the transparency mode's background.
+    pub bg_image: Option<PathBuf>,
+    /// How to filter the image when magnified, instead of the mode selected last.
+    pub filter: Option<FilterMode>,
+    /// Aspect ratio (width / height) that Shift constrains the selection to.
+    pub crop_ratio: Option<f32>,
+    /// Number of times to play animations (0 = forever), overriding the count stored in the file.
+    pub play_count: Option<u32>,
+    /// Treat the image's color channels as already premultiplied with its alpha channel.
+    pub premultiplied: bool,
+    /// Graphics backend to use instead of the automatically selected one.
+    pub backend: Option<wgpu::Backends>,
+    /// Open every image in its own window instead of navigating between them in one window.
+    pub windows: bool,
+    /// Also search the subdirectories of directories passed on the command line.
+    pub recursive: bool,
+    /// Reload the image whenever its file changes.
+    pub watch: bool,
+    /// Window title to use instead of the file name, with `{name}`, `{w}`, `{h}` and `{zoom}`
+    /// placeholders.
+    pub title: Option<String>,
+}
+
+/// A rectangle in source image pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl FromStr for Region {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s
+            .split(',')
+            .map(|part| part.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("invalid region '{s}'"))?;
+        let [x, y, w, h] = parts[..] else {
+            bail!("invalid region '{s}': expected 4 comma-separated values (x,y,w,h)");
+        };
+        if w == 0 || h == 0 {
+            bail!("invalid region '{s}': width and height must be non-zero");
+        }
+        Ok(Region { x, y, w, h })
+    }
+}
+
+fn parse_backend(s: &str) -> anyhow::Result<wgpu::Backends> {
+    Ok(match &*s.to_ascii_lowercase() {
+        "vulkan" | "vk" => wgpu::Backends::VULKAN,
+        "gl" | "opengl" | "gles" => wgpu::Backends::GL,
+        "dx12" | "d3d12" => wgpu::Backends::DX12,
+        "metal" | "mtl" => wgpu::Backends::METAL,
+        _ => bail!("unknown backend '{s}' (expected one of 'vulkan', 'gl', 'dx12', 'metal')"),
+    })
+}
+
+fn parse_transparency(s: &str) -> anyhow::Result<TransparencyMode> {
+    Ok(match &*s.to_ascii_lowercase() {
+        "true" | "transparent" => TransparencyMode::TrueTransparency,
+        "light" => TransparencyMode::LightCheckerboard,
+        "dark" => TransparencyMode::DarkCheckerboard,
+        _ => bail!("unknown transparency mode '{s}' (expected one of 'true', 'light', 'dark')"),
+    })
+}
+
+fn parse_filter(s: &str) -> anyhow::Result<FilterMode> {
+    Ok(match &*s.to_ascii_lowercase() {
+        "smart" => FilterMode::Smart,
+        "linear" => FilterMode::Linear,
+        "nearest" => FilterMode::Nearest,
+        _ => bail!("unknown filter mode '{s}' (expected one of 'smart', 'linear', 'nearest')"),
+    })
+}
+
+/// Parses an aspect ratio given as `W:H` or as a single number.
+fn parse_ratio(s: &str) -> anyhow::Result<f32> {
+    let ratio = match s.split_once(':') {
+        Some((w, h)) => match (w.trim().parse::<f32>(), h.trim().parse::<f32>()) {
+            (Ok(w), Ok(h)) => w / h,
+            _ => bail!("invalid aspect ratio '{s}'"),
+        },
+        None => s
+            .trim()
+            .parse::<f32>()
+            .with_context(|| format!("invalid aspect ratio '{s}'"))?,
+    };
+    if !ratio.is_finite() || ratio <= 0.0 {
+        bail!("invalid aspect ratio '{s}': must be positive");
+    }
+    Ok(ratio)
+}
+
+impl Args {
+    pub fn parse() -> anyhow::Result<Self> {
+        let mut args = Args::default();
+
+        let mut parser = Parser::from_env();
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Arg::Long("region") => {
+                    args.region = Some(parser.value()?.string()?.parse()?);
+                }
+                Arg::Long("zoom") => {
+                    let value = parser.value()?.string()?;
+                    let zoom = value
+                        .trim_end_matches('%')
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|zoom| zoom.is_finite() && *zoom > 0.0)
+                        .ok_or_else(|| anyhow!("invalid zoom level '{value}'"))?;
+                    args.zoom = Some(zoom);
+                }
+                Arg::Long("snap-zoom") => args.snap_zoom = true,
+                Arg::Long("loop") => {
+                    let value = parser.value()?.string()?;
+                    let count = value
+                        .parse()
+                        .with_context(|| format!("invalid loop count '{value}'"))?;
+                    args.play_count = Some(count);
+                }
+                Arg::Long("no-loop") => args.play_count = Some(1),
+                Arg::Long("crop-ratio") => {
+                    args.crop_ratio = Some(parse_ratio(&parser.value()?.string()?)?);
+                }
+                Arg::Long("print-selection") => args.print_selection = true,
+                Arg::Long("selection-color") => {
+                    args.selection_color = Some(parser.value()?.string()?.parse()?);
+                }
+                Arg::Long("selection-outline") => args.selection_outline = true,
+                Arg::Long("filter") => {
+                    args.filter = Some(parse_filter(&parser.value()?.string()?)?);
+                }
+                Arg::Long("transparency") => {
+                    args.transparency = Some(parse_transparency(&parser.value()?.string()?)?);
+                }
+                Arg::Long("bg-image") => args.bg_image = Some(parser.value()?.into()),
+                Arg::Long("print-info") => {
+                    args.print_info = Some(match parser.optional_value() {
+                        None => InfoFormat::Text,
+                        Some(value) => match &*value.string()? {
+                            "text" => InfoFormat::Text,
+                            "json" => InfoFormat::Json,
+                            value => {
+                                bail!("unknown info format '{value}' (expected 'text' or 'json')")
+                            }
+                        },
+                    });
+                }
+                Arg::Long("no-always-on-top") => args.no_always_on_top = true,
+                Arg::Long("no-vsync") => args.no_vsync = true,
+                Arg::Long("show-fps") => args.show_fps = true,
+                Arg::Long("monitor") => {
+                    let value = parser.value()?.string()?;
+                    let index = value
+                        .parse()
+                        .with_context(|| format!("invalid monitor index '{value}'"))?;
+                    args.monitor = Some(index);
+                }
+                Arg::Long("rounded") => {
+                    args.rounded = Some(match parser.optional_value() {
+                        None => DEFAULT_CORNER_RADIUS,
+                        Some(value) => {
+                            let value = value.string()?;
+                            value
+                                .parse::<f32>()
+                                .ok()
+                                .filter(|radius| radius.is_finite() && *radius >= 0.0)
+                                .ok_or_else(|| anyhow!("invalid corner radius '{value}'"))?
+                        }
+                    });
+                }
+                Arg::Long("auto-close") => {
+                    let value = parser.value()?.string()?;
+                    let timeout = value
+                        .trim_end_matches('s')
+                        .parse::<f32>()
+                        .ok()
+                        .and_then(|secs| Duration::try_from_secs_f32(secs).ok())
+                        .ok_or_else(|| anyhow!("invalid auto-close timeout '{value}'"))?;
+                    args.auto_close = Some(timeout);
+                }
+                Arg::Long("no-toolbar") => args.no_toolbar = true,
+                Arg::Long("free-resize") => args.free_resize = true,
+                Arg::Long("no-autocrop") => args.no_autocrop = true,
+                Arg::Long("premultiplied") => args.premultiplied = true,
+                Arg::Long("watch") => args.watch = true,
+                Arg::Long("windows") => args.windows = true,
+                Arg::Long("recursive") | Arg::Short('r') => args.recursive = true,
+                Arg::Long("title") => args.title = Some(parser.value()?.string()?),
+                Arg::Long("backend") => {
+                    args.backend = Some(parse_backend(&parser.value()?.string()?)?);
+                }
+                Arg::Long("output") | Arg::Short('o') => args.output = Some(parser.value()?.into()),
+                Arg::Long("extract-frames") => args.extract_frames = Some(parser.value()?.into()),
+                Arg::Long("help") | Arg::Short('h') => {
+                    print!("{USAGE}");
+                    process::exit(0);
+                }
+                Arg::Long("version") | Arg::Short('V') => {
+                    println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+                    process::exit(0);
+                }
+                Arg::Long("fd") => {
+                    let value = parser.value()?.string()?;
+                    let fd = value
+                        .parse::<i32>()
+                        .ok()
+                        .filter(|fd| *fd >= 0)
+                        .ok_or_else(|| anyhow!("invalid file descriptor '{value}'"))?;
+                    if cfg!(not(unix)) {
+                        bail!("`--fd` is only supported on Unix");
+                    }
+                    args.paths.push(Source::Fd(fd));
+                }
+                Arg::Value(value) => args.paths.push(Source::from_path(value.into())),
+                _ => return Err(arg.unexpected().into()),
+            }
+        }
+
+        if args.paths.is_empty() {
+            bail!(
+                "Missing argument. Either drag an image file onto the application, register it as \
+                an image file handler in your file manager, or invoke `{0}` with a path on the \
+                command line. Run `{0} --help` for all options.",
+                env!("CARGO_PKG_NAME"),
+            );
+        }
+        if args.output.is_some() && args.paths.len() > 1 {
+            bail!("`--output` only works with a single image");
+        }
+        if args.extract_frames.is_some() && args.paths.len() > 1 {
+            bail!("`--extract-frames` only works with a single image");
+        }
+        Ok(args)
+    }
+}
@@ -0,0 +1,57 @@
+//! Colors given on the command line or in the configuration file.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::bail;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::math::{self, vec4, Vec4f};
+
+/// An sRGB color with straight alpha, written as `#RRGGBB` or `#RRGGBBAA`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color(pub [u8; 4]);
+
+impl Color {
+    /// Converts the color to the linear, premultiplied representation used by the shaders.
+    pub fn to_linear_premultiplied(self) -> Vec4f {
+        let [r, g, b, a] = self.0.map(|c| f32::from(c) / 255.0);
+        let premultiply = |c: f32| math::srgb_to_linear(c) * a;
+        vec4(premultiply(r), premultiply(g), premultiply(b), a)
+    }
+}
+
+impl FromStr for Color {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.trim().trim_start_matches('#');
+        if !(hex.len() == 6 || hex.len() == 8) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            bail!("invalid color '{s}': expected `#RRGGBB` or `#RRGGBBAA`");
+        }
+        let mut rgba = [255; 4];
+        for (i, c) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+            *c = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)?;
+        }
+        Ok(Color(rgba))
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b, a] = self.0;
+        write!(f, "#{r:02x}{g:02x}{b:02x}{a:02x}")
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(|e| de::Error::custom(format!("{e:#}")))
+    }
+}
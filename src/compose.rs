@@ -0,0 +1,53 @@
+//! Assembly of animation frames into full-size images.
+//!
+//! The rest of `showimg` expects every frame to cover the whole image. `image`'s GIF, APNG and
+//! WebP decoders already composite their frames onto the full canvas (honoring disposal methods and
+//! blend ops, which `Frame` has no way of representing), but `Frame` can carry pixel offsets and
+//! differing sizes, so any such frames are drawn onto a canvas here.
+
+use image::{imageops, Frame, RgbaImage};
+
+/// Turns `frames` into frames that all have the same size and no pixel offset.
+///
+/// Partial frames are alpha-blended over the previous result, which is kept in place (the "do not
+/// dispose" behavior that frame-size optimizations rely on). Frames that are already complete are
+/// passed through unchanged.
+pub fn composite(frames: Vec<Frame>) -> Vec<Frame> {
+    let is_complete = |frame: &Frame, width, height| {
+        frame.left() == 0 && frame.top() == 0 && frame.buffer().dimensions() == (width, height)
+    };
+
+    let width = frames
+        .iter()
+        .map(|f| f.left() + f.buffer().width())
+        .max()
+        .unwrap_or(0);
+    let height = frames
+        .iter()
+        .map(|f| f.top() + f.buffer().height())
+        .max()
+        .unwrap_or(0);
+    if frames.iter().all(|f| is_complete(f, width, height)) {
+        return frames;
+    }
+    log::debug!(
+        "compositing {} frames onto a {width}x{height} canvas",
+        frames.len(),
+    );
+
+    let mut canvas = RgbaImage::new(width, height);
+    frames
+        .into_iter()
+        .map(|frame| {
+            let complete = is_complete(&frame, width, height);
+            let (left, top, delay) = (frame.left(), frame.top(), frame.delay());
+            let buffer = frame.into_buffer();
+            if complete {
+                canvas.clone_from(&buffer);
+            } else {
+                imageops::overlay(&mut canvas, &buffer, left.into(), top.into());
+            }
+            Frame::from_parts(canvas.clone(), 0, 0, delay)
+        })
+        .collect()
+}
@@ -0,0 +1,98 @@
+//! Persistent user preferences.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{color::Color, keymap::Keymap, FilterMode, TransparencyMode};
+
+const CONFIG_NAME: &str = "config";
+
+/// Number of recently opened files that are remembered.
+const RECENT_FILES_MAX: usize = 10;
+
+/// Settings that are remembered across launches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub transparency: TransparencyMode,
+    pub filter: FilterMode,
+    pub outline: bool,
+    /// Whether to dither the output on surfaces with 8 bits per channel.
+    pub dither: bool,
+    /// Whether to downscale with a Lanczos filter instead of sampling the mipmaps.
+    pub lanczos: bool,
+    /// Whether the checkerboard behind transparent areas zooms along with the image.
+    pub checkerboard_zoom: bool,
+    /// Color the selection is tinted with (can be overridden with `--selection-color`).
+    pub selection_color: Option<Color>,
+    /// Whether to outline the selection with black and white dashes (can be enabled with
+    /// `--selection-outline`).
+    pub selection_outline: bool,
+    /// Whether zooming snaps to whole multiples of 100% (can be enabled with `--snap-zoom`).
+    pub snap_zoom: bool,
+    /// Number of samples per pixel used for anti-aliasing (1, 2 or 4). The shaders already smooth
+    /// the image and its edges, so this is off (1) by default.
+    pub msaa_samples: u32,
+    /// Whether new windows are kept above all other windows (can be overridden with
+    /// `--no-always-on-top`).
+    pub always_on_top: bool,
+    /// Whether the default view is cropped to the content bounds of the image (can be overridden
+    /// with `--no-autocrop`).
+    pub autocrop: bool,
+    /// Keys bound to each action, overriding the defaults. Actions that are left out keep their
+    /// default keys, except for those taken by an action listed here.
+    pub keys: Keymap,
+    /// Absolute paths (or URLs) of the most recently opened images, most recent first.
+    pub recent_files: Vec<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            transparency: TransparencyMode::default(),
+            filter: FilterMode::default(),
+            outline: false,
+            dither: true,
+            lanczos: false,
+            checkerboard_zoom: false,
+            selection_color: None,
+            selection_outline: false,
+            snap_zoom: false,
+            msaa_samples: 1,
+            always_on_top: true,
+            autocrop: true,
+            keys: Keymap::new(),
+            recent_files: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration file, falling back to the defaults if it can't be read.
+    pub fn load() -> Self {
+        match confy::load(env!("CARGO_PKG_NAME"), CONFIG_NAME) {
+            Ok(config) => {
+                log::debug!("loaded config: {config:?}");
+                config
+            }
+            Err(e) => {
+                log::warn!("failed to load config, using defaults: {e}");
+                Self::default()
+            }
+        }
+    }
+
+    /// Moves `path` to the front of the recently opened files.
+    pub fn add_recent_file(&mut self, path: PathBuf) {
+        self.recent_files.retain(|p| *p != path);
+        self.recent_files.insert(0, path);
+        self.recent_files.truncate(RECENT_FILES_MAX);
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = confy::store(env!("CARGO_PKG_NAME"), CONFIG_NAME, self) {
+            log::error!("failed to save config: {e}");
+        }
+    }
+}
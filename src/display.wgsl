@@ -1,9 +1,14 @@
 @group(0) @binding(0)
 var in_sampler: sampler;
 @group(0) @binding(1)
-var in_texture: texture_2d<f32>;
+var in_texture: texture_2d_array<f32>;
 @group(0) @binding(2)
 var<uniform> u: DisplaySettings;
+@group(0) @binding(3)
+var background_texture: texture_2d<f32>;
+// The current frame downscaled to the size it is displayed at (see downscale.wgsl).
+@group(0) @binding(4)
+var downscaled_texture: texture_2d<f32>;
 
 struct DisplaySettings {
     // min/max frame buffer coordinates to render within; everything else is checkerboard
@@ -20,13 +25,106 @@ struct DisplaySettings {
     // checkerboard colors
     checkerboard_a: vec4f,
     checkerboard_b: vec4f,
-    // width/height of each checkerboard square in output pixels
+    // width/height of each checkerboard square in output pixels (or image pixels, if
+    // `checkerboard_zoom` is set)
     checkerboard_res: u32,
-    force_linear: u32, // 0 = smart filtering, 1 = always use linear filtering
+    filter_mode: u32, // 0 = smart filtering, 1 = linear filtering, 2 = nearest-neighbor
+    // magnifier centered on the cursor (radius is 0 when disabled)
+    loupe_center_fb: vec2f,
+    loupe_center_uv: vec2f,
+    loupe_radius: f32,
+    loupe_zoom: f32, // magnification relative to the main view
+    outline: u32, // 1 = draw an outline along the edge of the image
+    frame: u32, // array layer of the current animation frame
+    grid: u32, // 0 = no grid, 1 = rule of thirds, 2 = golden ratio, 3 = fine grid
+    dither: u32, // 1 = dither the output for an 8-bit surface
+    // UV coordinates of the region divided by the grid
+    min_grid: vec2f,
+    max_grid: vec2f,
+    alpha_view: u32, // 1 = show the alpha channel as grayscale instead of the image
+    lanczos: u32, // 1 = sample `downscaled_texture` instead of the mipmaps outside of the loupe
+    selection_outline: u32, // 1 = outline the selection with black and white dashes
+    checkerboard_zoom: u32, // 1 = anchor the checkerboard to the image, so that it zooms along
+    pixel_grid: u32, // 1 = draw lines between texels when zoomed in far enough
+    corner_radius: f32, // radius of the rounded window corners in pixels (0 = square corners)
+    window_size: vec2f,
+    background_image: u32, // 1 = show `background_texture` instead of the checkerboard
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+
 }
 
 const MIN_SMOOTHNESS: f32 = 0.25;
 
+// Whether the render target is a non-sRGB format, so the output has to be encoded manually.
+override SRGB_OUTPUT: bool = false;
+
+fn linear_to_srgb(c: vec3f) -> vec3f {
+    return select(1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3(0.0031308));
+}
+
+fn srgb_to_linear(c: vec3f) -> vec3f {
+    return select(pow((c + 0.055) / 1.055, vec3(2.4)), c / 12.92, c <= vec3(0.04045));
+}
+
+// Adds noise of up to half an 8-bit quantization step to `color`, so that smooth gradients turn
+// into fine noise instead of visible bands. The noise is interleaved gradient noise, which is
+// cheap to compute and has no visible pattern.
+fn dither(color: vec4f, fb: vec2f) -> vec4f {
+    let noise = fract(52.9829189 * fract(dot(floor(fb), vec2(0.06711056, 0.00583715)))) - 0.5;
+    // The color is premultiplied, so the noise is too (keeping transparent areas transparent).
+    let step = noise / 255.0 * color.a;
+    if SRGB_OUTPUT {
+        // Already encoded.
+        return vec4(color.rgb + step, color.a);
+    }
+    // The surface encodes to sRGB, so quantization happens in sRGB space; the noise has to be
+    // added there too.
+    return vec4(srgb_to_linear(max(linear_to_srgb(color.rgb) + step, vec3(0.0))), color.a);
+}
+
+// Length of each dash of the selection outline, in window pixels.
+const SELECTION_DASH_LENGTH: f32 = 4.0;
+
+const LOUPE_RING_WIDTH: f32 = 2.0;
+const LOUPE_RING_COLOR: vec4f = vec4(0.8, 0.8, 0.8, 1.0);
+
+// The outline consists of a dark and a light line (from the outside in), so that it's visible on
+// any background. Colors are premultiplied.
+const OUTLINE_DARK: vec4f = vec4(0.0, 0.0, 0.0, 0.6);
+const OUTLINE_LIGHT: vec4f = vec4(0.6, 0.6, 0.6, 0.6);
+
+// Premultiplied.
+const GRID_COLOR: vec4f = vec4(0.4, 0.4, 0.4, 0.4);
+const GRID_FINE_DIVISIONS: f32 = 8.0;
+const GOLDEN_SECTION: f32 = 0.381966;
+
+// Premultiplied.
+const PIXEL_GRID_COLOR: vec4f = vec4(0.25, 0.25, 0.25, 0.5);
+// The pixel grid is only drawn once every texel is at least this many window pixels wide, so that
+// the lines don't cover most of the image.
+const PIXEL_GRID_MIN_TEXEL_SIZE: f32 = 8.0;
+
+// Returns the distance from `t` (0-1 across the grid region) to the closest grid line.
+fn grid_line_distance(t: f32) -> f32 {
+    switch u.grid {
+        case 1u: {
+            return min(abs(t - 1.0 / 3.0), abs(t - 2.0 / 3.0));
+        }
+        case 2u: {
+            return min(abs(t - GOLDEN_SECTION), abs(t - (1.0 - GOLDEN_SECTION)));
+        }
+        case 3u: {
+            let f = t * GRID_FINE_DIVISIONS;
+            return abs(f - round(f)) / GRID_FINE_DIVISIONS;
+        }
+        default: {
+            return 1.0;
+        }
+    }
+}
+
 struct VertexOutput {
     @builtin(position)
     position: vec4f,
@@ -49,31 +147,60 @@ fn vertex(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
     return out;
 }
 
+// Returns how much of the pixel at `fb` is covered by the window's rounded rectangle.
+fn corner_coverage(fb: vec2f) -> f32 {
+    let half_size = u.window_size / 2.0;
+    // Signed distance to the rounded rectangle (negative inside of it).
+    let q = abs(fb - half_size) - (half_size - vec2(u.corner_radius));
+    let dist = length(max(q, vec2(0.0))) + min(max(q.x, q.y), 0.0) - u.corner_radius;
+    return clamp(0.5 - dist, 0.0, 1.0);
+}
+
 @fragment
 fn fragment(in: VertexOutput) -> @location(0) vec4f {
     // FB coords of this fragment.
     let fb = in.position.xy;
-    let border = any(fb < u.min_fb || fb >= u.max_fb);
+    var border = any(fb < u.min_fb) || any(fb >= u.max_fb);
 
     var uv = (fb - u.min_fb) / (u.max_fb - u.min_fb);
 
     // Map the UV coords (which are now in range 0 to 1) to the range indicated in the display settings.
     uv = (u.max_uv - u.min_uv) * uv + u.min_uv;
 
-    if u.force_linear == 0 {
+    // Inside the loupe, show a magnified view of the area around the cursor instead.
+    let loupe_offset = fb - u.loupe_center_fb;
+    let loupe_dist = length(loupe_offset);
+    if loupe_dist < u.loupe_radius {
+        let uv_per_fb = (u.max_uv - u.min_uv) / (u.max_fb - u.min_fb);
+        uv = u.loupe_center_uv + loupe_offset * uv_per_fb / u.loupe_zoom;
+        border = any(uv < vec2(0.0)) || any(uv >= vec2(1.0));
+    }
+
+    // The adjustments below make `uv` jump at texel boundaries, so mip level selection has to use
+    // the derivatives of the unmodified coordinates.
+    let uv_dx = dpdx(uv);
+    let uv_dy = dpdy(uv);
+    let unfiltered_uv = uv;
+
+    // The size of each texel (in window pixels) can be found out via derivatives.
+    let dim = vec2f(textureDimensions(in_texture));
+    let px = uv * dim; // sampled texture pixel
+    let dxdy = abs(vec2(dpdxFine(px.x), dpdyFine(px.y)));
+    let tex_per_px = max(dxdy.x, dxdy.y);
+
+    if u.filter_mode != 1 {
         // We want to render zoomed-in pixel art without making it all blurry, and without pixels getting
         // jittery when the window is enlarged. To do that, we use the approach detailed here:
         // https://csantosbh.wordpress.com/2014/01/25/manual-texture-filtering-for-pixelated-games-in-webgl/
         // We want the "smoothness" to be 1 when each texel occupies one or fewer window pixels, and
         // scale down to some minimum when each texel occupies more than one window pixel.
-        // The size of each texel can be found out via derivatives.
-        let dim = vec2f(textureDimensions(in_texture));
-        let px = uv * dim; // sampled texture pixel
-        let dxdy = abs(vec2(dpdxFine(px.x), dpdyFine(px.y)));
-        let tex_per_px = max(dxdy.x, dxdy.y);
         // 1 or more texels per screen pixel? Full linear interpolation.
         // Less than 1? Gradually transition to nearest neighbor.
-        let smoothness = clamp(tex_per_px, MIN_SMOOTHNESS, 1.0);
+        var smoothness = clamp(tex_per_px, MIN_SMOOTHNESS, 1.0);
+        if u.filter_mode == 2 && tex_per_px < 1.0 {
+            // Hard pixel edges at any magnification (minified images still use the mipmaps).
+            smoothness = 0.0;
+        }
 
         var fract = fract(px);
         if smoothness == 0.0 {
@@ -88,20 +215,119 @@ fn fragment(in: VertexOutput) -> @location(0) vec4f {
         uv = (floor(px) + fract) / dim;
     }
 
-    let tex_color = select(textureSample(in_texture, in_sampler, uv), vec4(0.0), border);
+    var tex_color = textureSampleGrad(in_texture, in_sampler, uv, u.frame, uv_dx, uv_dy);
+    if u.lanczos != 0 && loupe_dist >= u.loupe_radius {
+        // Each texel covers about one window pixel, so the sampler doesn't blur it much.
+        tex_color = textureSampleLevel(downscaled_texture, in_sampler, unfiltered_uv, 0.0);
+    }
+    tex_color = select(tex_color, vec4(0.0), border);
 
     // do a pre-multiplied alpha blend with the checkerboard colors
-    let checkervec = vec2u(in.position.xy) / u.checkerboard_res % 2; // even/odd in x/y dir
-    let check = checkervec.x != checkervec.y;  // parity
+    var check: bool;
+    if u.checkerboard_zoom != 0 {
+        // `floor` keeps the cells the same size to the left of and above the image, where the
+        // coordinates are negative.
+        let cell = vec2i(floor(unfiltered_uv * dim / f32(u.checkerboard_res)));
+        check = ((cell.x + cell.y) & 1) != 0;
+    } else {
+        let checkervec = vec2u(in.position.xy) / u.checkerboard_res % 2; // even/odd in x/y dir
+        check = checkervec.x != checkervec.y;  // parity
+    }
     var dest = select(u.checkerboard_a, u.checkerboard_b, check);
+    if u.background_image != 0 {
+        // Scaled to cover the whole window, keeping its aspect ratio.
+        let bg_size = vec2f(textureDimensions(background_texture));
+        let scale = max(u.window_size.x / bg_size.x, u.window_size.y / bg_size.y);
+        let bg_uv = (fb - u.window_size / 2.0) / (bg_size * scale) + 0.5;
+        let bg = textureSampleLevel(background_texture, in_sampler, bg_uv, 0.0);
+        dest = vec4(bg.rgb * bg.a, bg.a);
+    }
 
     dest = tex_color + (1 - tex_color.a) * dest;
 
+    if u.alpha_view != 0 && !border {
+        // Alpha values are shown as sRGB gray levels, so that 50% alpha looks like 50% gray.
+        dest = vec4(srgb_to_linear(vec3(tex_color.a)), 1.0);
+    }
+
     let in_selection = all(uv >= u.min_selection) && all(uv < u.max_selection);
     if in_selection {
         // blend the selection color on top
         let col = u.selection_color;
         dest = col + (1 - col.a) * dest;
+
+        if u.selection_outline != 0 {
+            // Alternating black and white dashes are visible on any image.
+            let fb_per_uv = (u.max_fb - u.min_fb) / (u.max_uv - u.min_uv);
+            let min_fb = (u.min_selection - u.min_uv) * fb_per_uv + u.min_fb;
+            let max_fb = (u.max_selection - u.min_uv) * fb_per_uv + u.min_fb;
+            if any(fb - min_fb < vec2(1.0)) || any(max_fb - fb <= vec2(1.0)) {
+                let dash = floor((fb.x + fb.y) / SELECTION_DASH_LENGTH) % 2.0;
+                dest = vec4(vec3(dash), 1.0);
+            }
+        }
+    }
+
+    if u.grid != 0 && !border && loupe_dist >= u.loupe_radius {
+        // Grid lines are placed in UV space, so that they move along with the image; their width
+        // is converted to window pixels so that they stay thin at every zoom level.
+        let t = (unfiltered_uv - u.min_grid) / (u.max_grid - u.min_grid);
+        let grid_fb = (u.max_grid - u.min_grid) / (u.max_uv - u.min_uv) * (u.max_fb - u.min_fb);
+        let dist = vec2(grid_line_distance(t.x), grid_line_distance(t.y)) * grid_fb;
+        let inside = all(t >= vec2(0.0)) && all(t <= vec2(1.0));
+        if inside && any(dist < vec2(0.5)) {
+            dest = GRID_COLOR + (1 - GRID_COLOR.a) * dest;
+        }
+    }
+
+    if u.pixel_grid != 0 && !border {
+        // Size of a texel in window pixels (larger inside of the loupe).
+        var texel_fb = (u.max_fb - u.min_fb) / ((u.max_uv - u.min_uv) * dim);
+        if loupe_dist < u.loupe_radius {
+            texel_fb *= u.loupe_zoom;
+        }
+        if all(texel_fb >= vec2(PIXEL_GRID_MIN_TEXEL_SIZE)) {
+            // Distance to the closest texel boundary, in window pixels.
+            let f = fract(unfiltered_uv * dim);
+            let dist = min(f, vec2(1.0) - f) * texel_fb;
+            if any(dist < vec2(0.5)) {
+                dest = PIXEL_GRID_COLOR + (1 - PIXEL_GRID_COLOR.a) * dest;
+            }
+        }
+    }
+
+    if u.outline != 0 && loupe_dist >= u.loupe_radius {
+        // distance to the closest edge of the image, in pixels (negative outside of it)
+        let edge_dist = min(min(fb.x - u.min_fb.x, fb.y - u.min_fb.y), min(u.max_fb.x - fb.x, u.max_fb.y - fb.y));
+        if edge_dist >= 0.0 && edge_dist < 2.0 {
+            let col = select(OUTLINE_LIGHT, OUTLINE_DARK, edge_dist < 1.0);
+            dest = col + (1 - col.a) * dest;
+        }
+    }
+
+    if u.loupe_radius > 0.0 && loupe_dist >= u.loupe_radius && loupe_dist < u.loupe_radius + LOUPE_RING_WIDTH {
+        dest = LOUPE_RING_COLOR;
+    }
+
+    if u.corner_radius > 0.0 {
+        dest *= corner_coverage(fb);
+    }
+
+    // Compositors blend windows with the sRGB-encoded values of the surface, so the output has to
+    // be premultiplied in sRGB space. Keeping the linear premultiplication would make translucent
+    // colors (like the hover checkerboard, or the image's own partial transparency) look much
+    // brighter than they do at full opacity.
+    let straight = select(dest.rgb / dest.a, vec3(0.0), dest.a == 0.0);
+    let encoded = linear_to_srgb(straight) * dest.a;
+    if SRGB_OUTPUT {
+        dest = vec4(encoded, dest.a);
+    } else {
+        // The surface encodes the output itself; decode it so that it ends up as `encoded`.
+        dest = vec4(srgb_to_linear(encoded), dest.a);
+    }
+
+    if u.dither != 0 {
+        dest = dither(dest, fb);
     }
 
     return dest;
@@ -0,0 +1,163 @@
+//! High quality downscaling of the displayed frame with a separable Lanczos filter, for
+//! [`Action::ToggleLanczos`].
+//!
+//! [`Action::ToggleLanczos`]: crate::keymap::Action::ToggleLanczos
+
+use crate::storage_shader;
+
+const WORKGROUP_SIZE: u32 = 16;
+
+pub struct Downscaler {
+    horizontal: wgpu::ComputePipeline,
+    vertical: wgpu::ComputePipeline,
+    bgl: wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+}
+
+impl Downscaler {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+        let module = storage_shader(
+            device,
+            "downscale.wgsl",
+            include_str!("downscale.wgsl"),
+            format,
+        );
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: None,
+                layout: Some(&layout),
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &[("WORKGROUP_SIZE".to_string(), WORKGROUP_SIZE as f64)].into(),
+                    zero_initialize_workgroup_memory: false,
+                },
+                cache: None,
+            })
+        };
+
+        Self {
+            horizontal: pipeline("horizontal"),
+            vertical: pipeline("vertical"),
+            bgl,
+            format,
+        }
+    }
+
+    /// Returns layer `layer` of `texture` downscaled to `width`x`height`.
+    ///
+    /// `texture` needs mipmaps, so that the filter never has to cover more than 2 texels per
+    /// destination texel.
+    pub fn downscale(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        layer: u32,
+        width: u32,
+        height: u32,
+    ) -> wgpu::Texture {
+        let scale = f32::min(
+            texture.width() as f32 / width as f32,
+            texture.height() as f32 / height as f32,
+        );
+        let level = (scale.log2().floor().max(0.0) as u32).min(texture.mip_level_count() - 1);
+        let source = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_mip_level: level,
+            mip_level_count: Some(1),
+            base_array_layer: layer,
+            array_layer_count: Some(1),
+            ..Default::default()
+        });
+        let source_height = (texture.height() >> level).max(1);
+
+        let create_texture = |label, width, height| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::STORAGE_BINDING
+                    | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            })
+        };
+        let intermediate = create_texture("downscale_intermediate", width, source_height);
+        let output = create_texture("downscaled", width, height);
+        let intermediate_view = intermediate.create_view(&Default::default());
+
+        let mut enc = device.create_command_encoder(&Default::default());
+        let mut pass = enc.begin_compute_pass(&Default::default());
+        let passes = [
+            (&self.horizontal, &source, &intermediate),
+            (&self.vertical, &intermediate_view, &output),
+        ];
+        for (pipeline, src, dst) in passes {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &self.bgl,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(src),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(
+                            &dst.create_view(&Default::default()),
+                        ),
+                    },
+                ],
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(
+                dst.width().div_ceil(WORKGROUP_SIZE),
+                dst.height().div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+        drop(pass);
+        queue.submit([enc.finish()]);
+
+        output
+    }
+}
@@ -0,0 +1,72 @@
+// High quality downscaling of a frame for display (see `lanczos` in display.wgsl).
+// The Lanczos kernel is separable, so the frame is filtered horizontally into an intermediate
+// texture first, and then vertically. Each pass only needs a few taps per texel, since the source
+// is the mip level with between 1 and 2 texels per destination texel.
+
+@group(0) @binding(0)
+var src: texture_2d<f32>;
+
+@group(0) @binding(1)
+var dst: texture_storage_2d<rgba16float, write>;
+
+override WORKGROUP_SIZE: u32 = 16;
+
+const PI: f32 = 3.14159265;
+
+// Lobes of the Lanczos kernel on each side of its center.
+const LANCZOS_RADIUS: f32 = 2.0;
+
+fn lanczos(x: f32) -> f32 {
+    if x == 0.0 {
+        return 1.0;
+    }
+    if abs(x) >= LANCZOS_RADIUS {
+        return 0.0;
+    }
+    let px = PI * x;
+    return LANCZOS_RADIUS * sin(px) * sin(px / LANCZOS_RADIUS) / (px * px);
+}
+
+// Filters `src` along `axis` (0 = horizontally, 1 = vertically) for the texel `id` of `dst`, which
+// is smaller than `src` along that axis.
+fn filter_axis(id: vec2u, axis: u32) -> vec4f {
+    let src_size = textureDimensions(src);
+    let scale = f32(src_size[axis]) / f32(textureDimensions(dst)[axis]);
+    // Rounding the mip level sizes down can leave slightly fewer source texels than destination
+    // texels, in which case the kernel must not get narrower than a source texel.
+    let width = max(scale, 1.0);
+    let center = (f32(id[axis]) + 0.5) * scale - 0.5; // in source texel centers
+
+    var coord = vec2i(id);
+    var sum = vec4(0.0);
+    var weight_sum = 0.0;
+    let first = i32(ceil(center - LANCZOS_RADIUS * width));
+    let last = i32(floor(center + LANCZOS_RADIUS * width));
+    for (var i = first; i <= last; i++) {
+        let w = lanczos((f32(i) - center) / width);
+        coord[axis] = clamp(i, 0, i32(src_size[axis]) - 1);
+        sum += w * textureLoad(src, coord, 0);
+        weight_sum += w;
+    }
+    return sum / weight_sum;
+}
+
+@compute
+@workgroup_size(WORKGROUP_SIZE, WORKGROUP_SIZE)
+fn horizontal(@builtin(global_invocation_id) id: vec3u) {
+    if any(id.xy >= textureDimensions(dst)) {
+        return;
+    }
+    textureStore(dst, id.xy, filter_axis(id.xy, 0u));
+}
+
+@compute
+@workgroup_size(WORKGROUP_SIZE, WORKGROUP_SIZE)
+fn vertical(@builtin(global_invocation_id) id: vec3u) {
+    if any(id.xy >= textureDimensions(dst)) {
+        return;
+    }
+    // The negative lobes overshoot at hard edges. Keep the result a valid premultiplied color.
+    let color = clamp(filter_axis(id.xy, 1u), vec4(0.0), vec4(1.0));
+    textureStore(dst, id.xy, vec4(min(color.rgb, vec3(color.a)), color.a));
+}
@@ -0,0 +1,28 @@
+//! Reading images from file descriptors inherited from the parent process (`--fd`).
+
+/// Reads everything from `fd` until the writing end is closed.
+#[cfg(unix)]
+pub fn read(fd: i32) -> anyhow::Result<Vec<u8>> {
+    use std::{fs::File, io::Read, mem::ManuallyDrop, os::fd::FromRawFd};
+
+    use anyhow::{bail, Context};
+
+    log::info!("reading file descriptor {fd}");
+    // SAFETY: the descriptor was handed to us by the parent process, and nothing else in here
+    // uses it. The `File` doesn't close it when dropped, so that reloading just reads nothing
+    // instead of hitting a closed (or reused) descriptor.
+    let mut file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+    let mut data = Vec::new();
+    // `read_to_end` retries on short reads and interruptions, and only stops at EOF.
+    file.read_to_end(&mut data)
+        .with_context(|| format!("Failed to read from file descriptor {fd}"))?;
+    if data.is_empty() {
+        bail!("File descriptor {fd} didn't contain any data");
+    }
+    Ok(data)
+}
+
+#[cfg(not(unix))]
+pub fn read(fd: i32) -> anyhow::Result<Vec<u8>> {
+    anyhow::bail!("Reading from file descriptor {fd} is only supported on Unix")
+}
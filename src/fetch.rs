@@ -0,0 +1,46 @@
+//! Downloading images from `http://` and `https://` URLs.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+
+/// Largest image that will be downloaded (`ureq`'s default limit is too small for big images).
+const MAX_DOWNLOAD_SIZE: u64 = 1 << 30;
+/// How long to wait for the server to accept the connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a whole download may take, including the connection.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Returns the last path segment of `url`, without the query string and fragment.
+pub fn file_name(url: &str) -> &str {
+    let end = url.find(['?', '#']).unwrap_or(url.len());
+    let path = url[..end].trim_end_matches('/');
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Downloads the resource at `url` into memory.
+pub fn fetch(url: &str) -> anyhow::Result<Vec<u8>> {
+    log::info!("downloading '{url}'");
+    let start = Instant::now();
+    let agent = ureq::Agent::config_builder()
+        .timeout_connect(Some(CONNECT_TIMEOUT))
+        .timeout_global(Some(DOWNLOAD_TIMEOUT))
+        .build()
+        .new_agent();
+    let mut response = agent
+        .get(url)
+        .call()
+        .with_context(|| format!("Failed to download '{url}'"))?;
+    let data = response
+        .body_mut()
+        .with_config()
+        .limit(MAX_DOWNLOAD_SIZE)
+        .read_to_vec()
+        .with_context(|| format!("Failed to download '{url}'"))?;
+    log::debug!(
+        "downloaded {} KiB in {:.02?}",
+        data.len() / 1024,
+        start.elapsed()
+    );
+    Ok(data)
+}
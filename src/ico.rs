@@ -0,0 +1,56 @@
+//! Support for ICO and CUR files containing multiple images.
+//!
+//! `image`'s ICO decoder only ever decodes the "best" entry of the file. To get at the others, we
+//! parse the icon directory ourselves and feed each entry to the decoder as a single-entry file.
+
+use std::{cmp, io::Cursor};
+
+use anyhow::{bail, Context};
+use image::{codecs::ico::IcoDecoder, DynamicImage, RgbaImage};
+
+const HEADER_SIZE: usize = 6;
+const ENTRY_SIZE: usize = 16;
+
+/// Decodes every image stored in an ICO or CUR file, ordered from largest to smallest.
+pub fn decode_entries(data: &[u8]) -> anyhow::Result<Vec<RgbaImage>> {
+    let read_u16 = |pos: usize| u16::from_le_bytes([data[pos], data[pos + 1]]);
+
+    if data.len() < HEADER_SIZE {
+        bail!("ICO file is truncated");
+    }
+    let ty = read_u16(2);
+    let count = usize::from(read_u16(4));
+    if count == 0 {
+        bail!("ICO file contains no images");
+    }
+    if data.len() < HEADER_SIZE + count * ENTRY_SIZE {
+        bail!("ICO directory is truncated");
+    }
+
+    let mut images = Vec::with_capacity(count);
+    for i in 0..count {
+        let entry = &data[HEADER_SIZE + i * ENTRY_SIZE..][..ENTRY_SIZE];
+        let length = u32::from_le_bytes(entry[8..12].try_into().unwrap()) as usize;
+        let offset = u32::from_le_bytes(entry[12..16].try_into().unwrap()) as usize;
+        let Some(payload) = data.get(offset..offset.saturating_add(length)) else {
+            bail!("ICO entry {i} lies outside of the file");
+        };
+
+        // Build a file containing only this entry, with the payload right after the directory.
+        let mut file = Vec::with_capacity(HEADER_SIZE + ENTRY_SIZE + length);
+        file.extend_from_slice(&[0, 0]);
+        file.extend_from_slice(&ty.to_le_bytes());
+        file.extend_from_slice(&1u16.to_le_bytes());
+        file.extend_from_slice(&entry[..12]);
+        file.extend_from_slice(&((HEADER_SIZE + ENTRY_SIZE) as u32).to_le_bytes());
+        file.extend_from_slice(payload);
+
+        let decoder = IcoDecoder::new(Cursor::new(file))?;
+        let image = DynamicImage::from_decoder(decoder)
+            .with_context(|| format!("failed to decode ICO entry {i}"))?;
+        images.push(image.into_rgba8());
+    }
+
+    images.sort_by_key(|image| cmp::Reverse(u64::from(image.width()) * u64::from(image.height())));
+    Ok(images)
+}
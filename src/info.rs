@@ -0,0 +1,102 @@
+//! `--print-info`: reporting the properties of an image without opening a window.
+
+use image::DynamicImage;
+use serde::Serialize;
+
+use crate::{load::LoadedImage, source::Source};
+
+/// Output format of `--print-info`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InfoFormat {
+    /// One `key=value` pair per line.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+#[derive(Serialize)]
+struct ImageProperties<'a> {
+    path: &'a str,
+    width: u32,
+    height: u32,
+    format: String,
+    frames: usize,
+    animated: bool,
+    /// `none`, `binary` or `partial`.
+    alpha: &'static str,
+    bit_depth: Option<u16>,
+    color_type: Option<String>,
+    size_kib: u64,
+}
+
+/// Prints the properties of `loaded` (loaded from `source`) to stdout.
+pub fn print(source: &Source, loaded: &LoadedImage, format: InfoFormat) -> anyhow::Result<()> {
+    let path = source.to_string();
+    let props = ImageProperties {
+        path: &path,
+        width: loaded.width(),
+        height: loaded.height(),
+        format: loaded.format_name().to_lowercase(),
+        frames: loaded.images.len(),
+        animated: loaded.animated,
+        alpha: alpha_usage(&loaded.images),
+        bit_depth: loaded
+            .color_type
+            .map(|ty| ty.bits_per_pixel() / u16::from(ty.channel_count())),
+        color_type: loaded.color_type.map(|ty| format!("{ty:?}")),
+        size_kib: loaded.kb,
+    };
+
+    match format {
+        InfoFormat::Json => println!("{}", serde_json::to_string(&props)?),
+        InfoFormat::Text => {
+            let or_unknown = |value: Option<String>| value.unwrap_or_else(|| "unknown".into());
+            println!("path={}", props.path);
+            println!("width={}", props.width);
+            println!("height={}", props.height);
+            println!("format={}", props.format);
+            println!("frames={}", props.frames);
+            println!("animated={}", props.animated);
+            println!("alpha={}", props.alpha);
+            println!(
+                "bit_depth={}",
+                or_unknown(props.bit_depth.map(|d| d.to_string()))
+            );
+            println!("color_type={}", or_unknown(props.color_type));
+            println!("size_kib={}", props.size_kib);
+        }
+    }
+    Ok(())
+}
+
+/// Classifies how the frames use their alpha channel, like the GPU preprocessing pass does.
+fn alpha_usage(images: &[DynamicImage]) -> &'static str {
+    let (mut used, mut partial) = (false, false);
+    for image in images {
+        match image {
+            DynamicImage::ImageRgba8(image) => {
+                for &a in image.pixels().map(|p| &p.0[3]) {
+                    used |= a < u8::MAX;
+                    partial |= a > 0 && a < u8::MAX;
+                }
+            }
+            DynamicImage::ImageRgba32F(image) => {
+                for &a in image.pixels().map(|p| &p.0[3]) {
+                    used |= a < 1.0;
+                    partial |= a > 0.0 && a < 1.0;
+                }
+            }
+            _ => unreachable!("frames are converted to Rgba8 or Rgba32F"),
+        }
+        if partial {
+            break;
+        }
+    }
+    if partial {
+        "partial"
+    } else if used {
+        "binary"
+    } else {
+        "none"
+    }
+}
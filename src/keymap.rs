@@ -0,0 +1,469 @@
+//! Keyboard shortcuts.
+//!
+//! Every shortcut triggers an [`Action`]. The keys bound to each action can be changed in the
+//! `keys` table of the configuration file.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use winit::keyboard::{KeyCode, ModifiersState};
+
+/// The shortcuts bound to each action, as stored in the configuration file.
+pub type Keymap = BTreeMap<Action, Vec<Shortcut>>;
+
+/// A key, together with the modifier keys that have to be held while pressing it.
+///
+/// In the configuration file, shortcuts are written as a key code with optional modifier prefixes,
+/// like `"KeyT"` or `"Ctrl+Shift+KeyS"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Shortcut {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+impl Shortcut {
+    /// `key` pressed while holding Ctrl.
+    pub const fn ctrl(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: true,
+            alt: false,
+            shift: false,
+        }
+    }
+
+    /// `key` pressed while holding Shift.
+    pub const fn shift(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            alt: false,
+            shift: true,
+        }
+    }
+
+    /// `key` pressed while holding Ctrl and Shift.
+    pub const fn ctrl_shift(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: true,
+            alt: false,
+            shift: true,
+        }
+    }
+}
+
+impl From<KeyCode> for Shortcut {
+    fn from(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            alt: false,
+            shift: false,
+        }
+    }
+}
+
+/// Formats the shortcut like it's written in the configuration file.
+impl fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "Ctrl"),
+            (self.alt, "Alt"),
+            (self.shift, "Shift"),
+        ] {
+            if held {
+                write!(f, "{name}+")?;
+            }
+        }
+        write!(f, "{:?}", self.key)
+    }
+}
+
+impl Serialize for Shortcut {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Shortcut {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let mut parts = s.split('+').collect::<Vec<_>>();
+        let key = parts.pop().unwrap_or_default();
+        let mut shortcut = Shortcut::from(KeyCode::deserialize(de::value::StrDeserializer::<
+            D::Error,
+        >::new(key))?);
+        for modifier in parts {
+            match &*modifier.to_ascii_lowercase() {
+                "ctrl" | "control" => shortcut.ctrl = true,
+                "alt" => shortcut.alt = true,
+                "shift" => shortcut.shift = true,
+                _ => {
+                    return Err(de::Error::custom(format!(
+                        "unknown modifier '{modifier}' in shortcut '{s}'"
+                    )))
+                }
+            }
+        }
+        Ok(shortcut)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Closes the window (or clears the measurement, or discards selection adjustments).
+    Exit,
+    /// Resets the zoom region (or clears the measurement).
+    ResetZoom,
+    /// Starts adjusting the selection, or applies the adjustments.
+    AdjustSelection,
+    /// Applies the selection adjustments.
+    ConfirmSelection,
+    CropToFrame,
+    /// Opens the displayed region in a new window.
+    OpenViewInNewWindow,
+    ZoomIn,
+    ZoomOut,
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    /// Resizes the window to show the image at 100% zoom.
+    ActualSize,
+    /// Shows the whole width of the image, at the window's current aspect ratio.
+    FitWidth,
+    /// Shows the whole height of the image, at the window's current aspect ratio.
+    FitHeight,
+    CycleTransparency,
+    /// Switches between a checkerboard that stays in place and one that zooms with the image.
+    ToggleCheckerboardZoom,
+    CycleGrid,
+    /// Draws lines between the pixels of the image when zoomed in far enough.
+    TogglePixelGrid,
+    CycleCropRatio,
+    ToggleFilter,
+    ToggleMeasuring,
+    ToggleOutline,
+    ToggleDither,
+    /// Downscales with a Lanczos filter instead of the (blurrier) mipmaps.
+    ToggleLanczos,
+    PreviousFrame,
+    NextFrame,
+    ToggleLoupe,
+    ToggleFrameCounter,
+    ToggleAlphaInfo,
+    /// Shows the average and dominant colors of the current frame.
+    TogglePalette,
+    /// Shows only the alpha channel, as grayscale.
+    ToggleAlphaView,
+    /// Outlines the content bounds detected during preprocessing.
+    ToggleContentBounds,
+    /// Lets the window be resized to any aspect ratio, letterboxing the image.
+    ToggleFreeResize,
+    ToggleFileInfo,
+    PreviousImage,
+    NextImage,
+    ToggleThumbnails,
+    Trash,
+    Reload,
+    ToggleHelp,
+    /// Saves the current frame in another format.
+    SaveAs,
+    CopyPath,
+    /// Renders the window contents to a PNG file in the temporary directory and prints its path.
+    ExportView,
+    /// Opens the recently opened files, one after another.
+    OpenRecent,
+    /// Restores the default display settings and zoom region.
+    ResetAll,
+    /// Goes back to the zoom region that was shown before the last zoom.
+    UndoZoom,
+    /// Copies the coordinate of the image pixel under the cursor.
+    CopyPixelCoordinate,
+}
+
+impl Action {
+    /// Describes the action for the help overlay.
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Exit => "Close window",
+            Action::ResetZoom => "Reset zoom region",
+            Action::AdjustSelection => "Adjust the selected region",
+            Action::ConfirmSelection => "Apply selection adjustments",
+            Action::CropToFrame => "Zoom to the content of the frame",
+            Action::OpenViewInNewWindow => "Open the view in a new window",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::PanLeft => "Pan left",
+            Action::PanRight => "Pan right",
+            Action::PanUp => "Pan up",
+            Action::PanDown => "Pan down",
+            Action::ActualSize => "Resize window to the image size",
+            Action::FitWidth => "Fit the image width",
+            Action::FitHeight => "Fit the image height",
+            Action::CycleTransparency => "Cycle transparency background",
+            Action::ToggleCheckerboardZoom => "Toggle zooming the checkerboard",
+            Action::CycleGrid => "Cycle composition grid",
+            Action::TogglePixelGrid => "Toggle pixel grid",
+            Action::CycleCropRatio => "Cycle selection aspect ratio",
+            Action::ToggleFilter => "Cycle filter mode",
+            Action::ToggleMeasuring => "Toggle measuring mode",
+            Action::ToggleOutline => "Toggle image outline",
+            Action::ToggleDither => "Toggle dithering",
+            Action::ToggleLanczos => "Toggle high quality downscaling",
+            Action::PreviousFrame => "Previous frame",
+            Action::NextFrame => "Next frame",
+            Action::ToggleLoupe => "Toggle magnifier",
+            Action::ToggleFrameCounter => "Toggle frame counter",
+            Action::ToggleAlphaInfo => "Show alpha channel info",
+            Action::TogglePalette => "Show average and dominant colors",
+            Action::ToggleAlphaView => "Toggle alpha channel view",
+            Action::ToggleContentBounds => "Show detected content bounds",
+            Action::ToggleFreeResize => "Unlock the window's aspect ratio",
+            Action::ToggleFileInfo => "Show file info",
+            Action::PreviousImage => "Previous image",
+            Action::NextImage => "Next image",
+            Action::ToggleThumbnails => "Toggle thumbnail strip",
+            Action::Trash => "Move file to the trash",
+            Action::Reload => "Reload image",
+            Action::ToggleHelp => "Show this help",
+            Action::SaveAs => "Save as another format",
+            Action::CopyPath => "Copy the file path",
+            Action::ExportView => "Export the view to a temporary PNG",
+            Action::OpenRecent => "Open a recent file",
+            Action::UndoZoom => "Undo zoom",
+            Action::ResetAll => "Reset all view settings",
+            Action::CopyPixelCoordinate => "Copy the pixel coordinate under the cursor",
+        }
+    }
+}
+
+pub fn default_keymap() -> Keymap {
+    use Action::*;
+    use KeyCode::*;
+
+    [
+        (Exit, &[Escape][..]),
+        (AdjustSelection, &[KeyE]),
+        (ConfirmSelection, &[Enter, NumpadEnter]),
+        (CropToFrame, &[KeyC]),
+        (OpenViewInNewWindow, &[KeyW]),
+        (ZoomIn, &[Equal, NumpadAdd]),
+        (ZoomOut, &[Minus, NumpadSubtract]),
+        (PanLeft, &[ArrowLeft]),
+        (PanRight, &[ArrowRight]),
+        (PanUp, &[ArrowUp]),
+        (PanDown, &[ArrowDown]),
+        (ActualSize, &[Digit1]),
+        (FitWidth, &[KeyX]),
+        (FitHeight, &[KeyY]),
+        (CycleTransparency, &[KeyT]),
+        (CycleGrid, &[KeyG]),
+        (CycleCropRatio, &[KeyK]),
+        (ToggleFilter, &[KeyL]),
+        (ToggleMeasuring, &[KeyR]),
+        (ToggleOutline, &[KeyO]),
+        (ToggleDither, &[KeyD]),
+        (ToggleLanczos, &[KeyQ]),
+        (PreviousFrame, &[Comma]),
+        (NextFrame, &[Period]),
+        (ToggleLoupe, &[KeyM]),
+        (ToggleFrameCounter, &[KeyN]),
+        (ToggleAlphaInfo, &[KeyA]),
+        (TogglePalette, &[KeyU]),
+        (ToggleAlphaView, &[KeyV]),
+        (ToggleContentBounds, &[KeyB]),
+        (ToggleFreeResize, &[KeyF]),
+        (ToggleFileInfo, &[KeyI]),
+        (PreviousImage, &[PageUp]),
+        (NextImage, &[PageDown]),
+        (ToggleThumbnails, &[KeyS]),
+        (Trash, &[Delete]),
+        (Reload, &[F5]),
+        (ToggleHelp, &[F1, Slash]),
+        (CopyPixelCoordinate, &[KeyP]),
+        (OpenRecent, &[KeyH]),
+    ]
+    .into_iter()
+    .map(|(action, keys)| (action, keys.iter().copied().map(Shortcut::from).collect()))
+    .chain([
+        // Ctrl+0 resets the zoom in web browsers.
+        (
+            ResetZoom,
+            vec![
+                Backspace.into(),
+                Shortcut::ctrl(Digit0),
+                Shortcut::ctrl(Numpad0),
+            ],
+        ),
+        (UndoZoom, vec![KeyZ.into(), Shortcut::ctrl(KeyZ)]),
+        (ResetAll, vec![Shortcut::shift(Backspace)]),
+        (SaveAs, vec![Shortcut::ctrl_shift(KeyS)]),
+        (CopyPath, vec![Shortcut::ctrl_shift(KeyC)]),
+        (ExportView, vec![Shortcut::ctrl_shift(KeyE)]),
+        (ToggleCheckerboardZoom, vec![Shortcut::shift(KeyT)]),
+        (TogglePixelGrid, vec![Shortcut::shift(KeyG)]),
+    ])
+    .collect()
+}
+
+/// Builds the lookup table from keys to actions.
+///
+/// Configured bindings take precedence: actions missing from `configured` keep those of their
+/// default keys that no configured action uses. If a key is bound to several actions otherwise, a
+/// warning is logged and the first of them (in declaration order) wins.
+pub fn build(configured: &Keymap) -> HashMap<Shortcut, Action> {
+    let mut lookup = HashMap::new();
+    for (action, keys) in configured {
+        for &key in keys {
+            if let Some(existing) = lookup.get(&key) {
+                log::warn!(
+                    "{key} is bound to both {existing:?} and {action:?}, using {existing:?}"
+                );
+                continue;
+            }
+            lookup.insert(key, *action);
+        }
+    }
+
+    // Default bindings of actions that weren't configured fill in the rest, unless the shortcut
+    // was taken by a configured action.
+    for (action, keys) in default_keymap() {
+        if configured.contains_key(&action) {
+            continue;
+        }
+        for key in keys {
+            match lookup.get(&key) {
+                Some(existing) if configured.contains_key(existing) => {
+                    log::debug!("{key} is bound to {existing:?}, unbinding it from {action:?}");
+                }
+                Some(existing) => {
+                    log::warn!(
+                        "{key} is bound to both {existing:?} and {action:?}, using {existing:?}"
+                    );
+                }
+                None => {
+                    lookup.insert(key, action);
+                }
+            }
+        }
+    }
+    lookup
+}
+
+/// Returns the action bound to pressing `key` while holding `modifiers`.
+///
+/// Shortcuts without modifiers also trigger while only Shift is held, since several keys (like `?`
+/// or `+`) need Shift on common keyboard layouts.
+pub fn action(
+    lookup: &HashMap<Shortcut, Action>,
+    key: KeyCode,
+    modifiers: ModifiersState,
+) -> Option<Action> {
+    let shortcut = Shortcut {
+        key,
+        ctrl: modifiers.control_key(),
+        alt: modifiers.alt_key(),
+        shift: modifiers.shift_key(),
+    };
+    let only_shift = shortcut.shift && !shortcut.ctrl && !shortcut.alt;
+    lookup
+        .get(&shortcut)
+        .or_else(|| lookup.get(&Shortcut::from(key)).filter(|_| only_shift))
+        .copied()
+}
+
+/// Returns a short name for `shortcut` to show to the user.
+pub fn key_name(shortcut: Shortcut) -> String {
+    let mut name = String::new();
+    for (held, modifier) in [
+        (shortcut.ctrl, "Ctrl+"),
+        (shortcut.alt, "Alt+"),
+        (shortcut.shift, "Shift+"),
+    ] {
+        if held {
+            name.push_str(modifier);
+        }
+    }
+    name + &plain_key_name(shortcut.key)
+}
+
+fn plain_key_name(key: KeyCode) -> String {
+    let name = match key {
+        KeyCode::Equal => "=",
+        KeyCode::Minus => "-",
+        KeyCode::Comma => ",",
+        KeyCode::Period => ".",
+        KeyCode::Slash => "/",
+        KeyCode::NumpadAdd => "Num+",
+        KeyCode::NumpadSubtract => "Num-",
+        KeyCode::NumpadEnter => "NumEnter",
+        _ => {
+            let name = format!("{key:?}");
+            // `KeyA` -> `A`, `Digit1` -> `1`, `ArrowUp` -> `Up`
+            return ["Key", "Digit", "Arrow"]
+                .iter()
+                .find_map(|prefix| name.strip_prefix(prefix))
+                .unwrap_or(&name)
+                .to_string();
+        }
+    };
+    name.to_string()
+}
+
+/// Lists the keys bound to each action, in declaration order of the actions.
+pub fn help_text(lookup: &HashMap<Shortcut, Action>) -> String {
+    let mut keys = BTreeMap::<Action, Vec<Shortcut>>::new();
+    for (key, action) in lookup {
+        keys.entry(*action).or_default().push(*key);
+    }
+    let rows = keys
+        .into_iter()
+        .map(|(action, mut keys)| {
+            keys.sort();
+            let keys = keys.into_iter().map(key_name).collect::<Vec<_>>();
+            (keys.join(", "), action.description())
+        })
+        .collect::<Vec<_>>();
+    let width = rows.iter().map(|(keys, _)| keys.len()).max().unwrap_or(0);
+    rows.iter()
+        .map(|(keys, description)| format!("{keys:width$}  {description}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_key_overrides_default() {
+        let configured = Keymap::from([(Action::Reload, vec![Shortcut::from(KeyCode::KeyR)])]);
+        let lookup = build(&configured);
+        assert_eq!(
+            lookup.get(&Shortcut::from(KeyCode::KeyR)),
+            Some(&Action::Reload)
+        );
+        // Configured keys replace the action's default keys.
+        assert_eq!(lookup.get(&Shortcut::from(KeyCode::F5)), None);
+        // Unrelated defaults are kept.
+        assert_eq!(
+            lookup.get(&Shortcut::from(KeyCode::Escape)),
+            Some(&Action::Exit)
+        );
+    }
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        let lookup = build(&Keymap::new());
+        let bindings = default_keymap().values().map(Vec::len).sum::<usize>();
+        assert_eq!(lookup.len(), bindings);
+    }
+}
@@ -0,0 +1,354 @@
+//! Image file loading.
+
+use std::{fs, io::Cursor, path::Path, time::Instant};
+
+use anyhow::{bail, Context};
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder, qoi::QoiDecoder, webp::WebPDecoder},
+    error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind},
+    imageops::{self, FilterType},
+    AnimationDecoder, ColorType, Delay, DynamicImage, Frame, ImageDecoder, ImageError, ImageFormat,
+    ImageReader, Rgba, RgbaImage,
+};
+
+use crate::{
+    compose, fd, fetch, ico, loops, math::linear_to_srgb, pages, raw, source::Source, svg,
+    SVG_SUPERSAMPLING, WIN_HEIGHT, WIN_WIDTH,
+};
+
+/// The decoded contents of an image file.
+#[derive(Debug, Default)]
+pub struct LoadedImage {
+    /// Every frame is either `ImageRgba8` (sRGB) or `ImageRgba32F` (linear), and all frames have
+    /// the same size.
+    pub images: Vec<DynamicImage>,
+    pub delays: Vec<Delay>,
+    /// Original size of each entry of an icon file (before scaling them to a common size).
+    pub entry_sizes: Vec<(u32, u32)>,
+    /// `None` for SVGs and camera RAW files, which `image` doesn't know about.
+    pub format: Option<ImageFormat>,
+    /// Whether this is a camera RAW file (see [`raw`]).
+    pub raw: bool,
+    /// Pixel format of the file, before it got converted for display.
+    pub color_type: Option<ColorType>,
+    /// Whether the frames are played back as an animation.
+    pub animated: bool,
+    /// Number of times the file says the animation should be played (`None` = forever).
+    pub play_count: Option<u32>,
+    pub kb: u64,
+}
+
+impl LoadedImage {
+    /// Returns the name of the file format, like `Png`.
+    pub fn format_name(&self) -> String {
+        match self.format {
+            Some(format) => format!("{format:?}"),
+            None if self.raw => "RAW".to_string(),
+            None => "SVG".to_string(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.images[0].width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.images[0].height()
+    }
+}
+
+/// Converts a frame of a [`LoadedImage`] to 8-bit sRGB.
+pub fn to_srgb8(image: &DynamicImage) -> RgbaImage {
+    match image {
+        // `to_rgba8` would keep the linear values.
+        DynamicImage::ImageRgba32F(image) => {
+            RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+                let [r, g, b, a] = image.get_pixel(x, y).0;
+                let unorm = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+                Rgba([
+                    unorm(linear_to_srgb(r)),
+                    unorm(linear_to_srgb(g)),
+                    unorm(linear_to_srgb(b)),
+                    unorm(a),
+                ])
+            })
+        }
+        image => image.to_rgba8(),
+    }
+}
+
+/// Determines the format of an image file from its extension (`None` for SVGs and RAW files).
+fn format_from_path(path: &Path) -> anyhow::Result<Option<ImageFormat>> {
+    Ok(match path.extension() {
+        _ if svg::is_svg(path) || raw::is_raw(path) => None,
+        // Cursors use the same container format as icons.
+        Some(ext) if ext.eq_ignore_ascii_case("cur") => Some(ImageFormat::Ico),
+        _ => Some(ImageFormat::from_path(path)?),
+    })
+}
+
+/// Determines the format of an image from its contents, for when there is no file extension.
+///
+/// Returns `Some(None)` for SVGs (see [`LoadedImage::format`]), and `None` if the format is unknown.
+fn guess_format(data: &[u8]) -> Option<Option<ImageFormat>> {
+    match image::guess_format(data) {
+        Ok(format) => Some(Some(format)),
+        Err(_) if data.trim_ascii_start().starts_with(b"<") => Some(None),
+        Err(_) => None,
+    }
+}
+
+/// Returns whether `path` has the extension of a format that can be loaded.
+pub fn is_supported(path: &Path) -> bool {
+    if raw::is_raw(path) {
+        return cfg!(feature = "raw");
+    }
+    format_from_path(path).is_ok_and(|format| format.is_none_or(|f| f.reading_enabled()))
+}
+
+/// Loads the image from `source`.
+pub fn load(source: &Source) -> anyhow::Result<LoadedImage> {
+    log::info!("opening '{source}'");
+    let start = Instant::now();
+    let name = match source {
+        Source::Path(path) => path.as_path(),
+        Source::Url(url) => Path::new(fetch::file_name(url)),
+        Source::Fd(_) => Path::new(""),
+    };
+    // Many RAW formats look like TIFF files, so they are only detected by their extension.
+    let raw = raw::is_raw(name);
+    let (data, format) = match source {
+        Source::Url(url) => {
+            let data = fetch::fetch(url)?;
+            // URLs often don't end in a file extension, so look at the data first.
+            let format = match guess_format(&data) {
+                Some(format) if !raw => format,
+                _ => format_from_path(name)?,
+            };
+            (data, format)
+        }
+        Source::Fd(fd) => {
+            let data = fd::read(*fd)?;
+            let Some(format) = guess_format(&data) else {
+                let unknown = ImageFormatHint::Unknown;
+                let e = UnsupportedError::from_format_and_kind(
+                    unknown.clone(),
+                    UnsupportedErrorKind::Format(unknown),
+                );
+                return Err(ImageError::Unsupported(e))
+                    .context(format!("Unrecognized image format in file descriptor {fd}"));
+            };
+            (data, format)
+        }
+        Source::Path(path) => {
+            let data = fs::read(path)
+                .context(format!("Failed to open image file '{}'", path.display()))?;
+            (data, format_from_path(path)?)
+        }
+    };
+    let kb = data.len() as u64 / 1024;
+
+    let reader = Cursor::new(&data[..]);
+    let mut entry_sizes = Vec::new();
+    let mut float_image = None;
+    // NB: the animation decoders apply each frame's disposal method (background/previous) and blend
+    // op themselves, and yield complete canvases, so the frames don't need any further assembly.
+    let frames = match format {
+        Some(ImageFormat::Png) => {
+            let dec = PngDecoder::new(reader)?;
+            if dec.is_apng()? {
+                dec.apng()?.into_frames().collect_frames()?
+            } else {
+                // It's awkward to get a normal fucking image from a `PngDecoder` for some reason,
+                // so just use the `image::load` API.
+                vec![Frame::new(
+                    image::load_from_memory_with_format(&data, ImageFormat::Png)?.into_rgba8(),
+                )]
+            }
+        }
+        Some(ImageFormat::Gif) => GifDecoder::new(reader)?.into_frames().collect_frames()?,
+        Some(ImageFormat::WebP) => WebPDecoder::new(reader)?.into_frames().collect_frames()?,
+        Some(ImageFormat::Qoi) => vec![Frame::new(
+            DynamicImage::from_decoder(QoiDecoder::new(reader)?)?.into_rgba8(),
+        )],
+        Some(ImageFormat::OpenExr) => {
+            // EXR files contain linear floating-point data that can exceed 1.0. Converting it to
+            // 8 bits would throw away the highlights, so it bypasses the 8-bit `Frame`s and is
+            // uploaded as-is.
+            let image =
+                image::load_from_memory_with_format(&data, ImageFormat::OpenExr)?.into_rgba32f();
+            let frame = Frame::new(image::RgbaImage::new(image.width(), image.height()));
+            float_image = Some(image);
+            vec![frame]
+        }
+        Some(ImageFormat::Ico) => {
+            let entries = ico::decode_entries(&data)?;
+            entry_sizes = entries.iter().map(|e| e.dimensions()).collect();
+
+            // Every frame needs to have the same size, so scale the smaller icons up to match the
+            // largest one. The actual size of the current entry is displayed in the title.
+            let (width, height) = entry_sizes[0];
+            entries
+                .into_iter()
+                .map(|entry| {
+                    if entry.dimensions() == (width, height) {
+                        Frame::new(entry)
+                    } else {
+                        Frame::new(imageops::resize(&entry, width, height, FilterType::Nearest))
+                    }
+                })
+                .collect()
+        }
+        Some(ImageFormat::Tiff) => {
+            // The pages of a multi-page TIFF are displayed like the entries of an icon file.
+            let pages = pages::decode_pages(&data)?;
+            if pages.len() > 1 {
+                entry_sizes = pages.iter().map(|page| page.dimensions()).collect();
+            }
+
+            // Every frame needs to have the same size, so smaller pages are put in the top left
+            // corner of a transparent canvas the size of the largest one.
+            let width = pages.iter().map(|page| page.width()).max().unwrap();
+            let height = pages.iter().map(|page| page.height()).max().unwrap();
+            pages
+                .into_iter()
+                .map(|page| {
+                    if page.dimensions() == (width, height) {
+                        Frame::new(page)
+                    } else {
+                        let mut canvas = image::RgbaImage::new(width, height);
+                        imageops::overlay(&mut canvas, &page, 0, 0);
+                        Frame::new(canvas)
+                    }
+                })
+                .collect()
+        }
+        None if raw => vec![Frame::new(raw::decode(&data)?)],
+        None => vec![Frame::new(svg::rasterize(
+            &data,
+            WIN_WIDTH * SVG_SUPERSAMPLING,
+            WIN_HEIGHT * SVG_SUPERSAMPLING,
+        )?)],
+        Some(format) => vec![Frame::new(
+            image::load_from_memory_with_format(&data, format)?.into_rgba8(),
+        )],
+    };
+    assert!(!frames.is_empty());
+    let (width, height) = frames[0].buffer().dimensions();
+    if width == 0 || height == 0 {
+        bail!("image has no pixels ({width}x{height})");
+    }
+    let frames = compose::composite(frames);
+
+    // The entries of an icon file and the pages of a TIFF file are displayed like animation frames,
+    // but aren't played back.
+    let animated =
+        frames.len() > 1 && !matches!(format, Some(ImageFormat::Ico | ImageFormat::Tiff));
+    let play_count = if animated {
+        loops::play_count(&data, format).unwrap_or_else(|e| {
+            log::warn!("failed to read loop count: {e:#}");
+            None
+        })
+    } else {
+        None
+    };
+    let what = if animated { "animation" } else { "image" };
+    let image = frames[0].buffer();
+    log::debug!(
+        "loaded {}x{} {what} from {} KiB file in {:.02?} (aspect ratio {}; memsize {} KiB per frame; {} frames)",
+        image.width(),
+        image.height(),
+        kb,
+        start.elapsed(),
+        image.width() as f32 / image.height() as f32,
+        u64::from(image.width()) * u64::from(image.height()) * 4 / 1024,
+        frames.len(),
+    );
+
+    let mut images = Vec::new();
+    let mut delays = Vec::new();
+    for frame in frames {
+        delays.push(frame.delay());
+        images.push(DynamicImage::ImageRgba8(frame.into_buffer()));
+    }
+    if let Some(image) = float_image {
+        images = vec![DynamicImage::ImageRgba32F(image)];
+    }
+
+    let mut reader = ImageReader::new(Cursor::new(&data[..]));
+    if let Some(format) = format {
+        reader.set_format(format);
+    }
+    let color_type = reader
+        .with_guessed_format()
+        .ok()
+        .filter(|_| !raw)
+        .and_then(|r| r.into_decoder().ok())
+        .map(|d| d.color_type());
+
+    Ok(LoadedImage {
+        images,
+        delays,
+        entry_sizes,
+        format,
+        raw,
+        color_type,
+        animated,
+        play_count,
+        kb,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    /// `testdata/disposal.gif` is 8x8 and has four frames:
+    ///
+    /// 1. Solid red, kept.
+    /// 2. A blue 4x4 square at (2, 2), disposed to the background (transparent).
+    /// 3. A green 2x2 square at (0, 0), disposed to the previous canvas.
+    /// 4. A white pixel at (7, 7).
+    #[test]
+    fn gif_disposal() {
+        let path = Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testdata/disposal.gif"
+        ));
+        let loaded = load(&Source::Path(path.into())).unwrap();
+        assert!(loaded.animated);
+        assert_eq!(loaded.images.len(), 4);
+        let delays = loaded
+            .delays
+            .iter()
+            .map(|&delay| Duration::from(delay))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            [100, 200, 30, 50].map(Duration::from_millis).to_vec()
+        );
+
+        let pixel = |frame: usize, x, y| loaded.images[frame].to_rgba8().get_pixel(x, y).0;
+        let center = |x, y| (2..6).contains(&x) && (2..6).contains(&y);
+
+        for (x, y) in (0..8).flat_map(|y| (0..8).map(move |x| (x, y))) {
+            assert_eq!(pixel(0, x, y), RED);
+            assert_eq!(pixel(1, x, y), if center(x, y) { BLUE } else { RED });
+
+            let disposed = if center(x, y) { CLEAR } else { RED };
+            let expected = if x < 2 && y < 2 { GREEN } else { disposed };
+            assert_eq!(pixel(2, x, y), expected, "frame 2 at ({x}, {y})");
+            // The green square is gone again, but the cleared center stays clear.
+            let expected = if (x, y) == (7, 7) { WHITE } else { disposed };
+            assert_eq!(pixel(3, x, y), expected, "frame 3 at ({x}, {y})");
+        }
+    }
+}
@@ -0,0 +1,34 @@
+//! Reading the number of times an animation should be played.
+//!
+//! `image`'s animation decoders don't expose this, so the containers are parsed again with the
+//! underlying codec crates.
+
+use std::io::Cursor;
+
+use image::ImageFormat;
+
+/// Returns how many times the animation in the file `data` should be played, or `None` if it
+/// should loop forever.
+pub fn play_count(data: &[u8], format: Option<ImageFormat>) -> anyhow::Result<Option<u32>> {
+    let reader = Cursor::new(data);
+    match format {
+        Some(ImageFormat::Gif) => {
+            let mut decoder = gif::DecodeOptions::new().read_info(reader)?;
+            // The NETSCAPE2.0 extension that holds the loop count precedes the first frame.
+            decoder.next_frame_info()?;
+            Ok(match decoder.repeat() {
+                gif::Repeat::Infinite => None,
+                // This counts the repetitions *after* the first playthrough.
+                gif::Repeat::Finite(n) => Some(u32::from(n) + 1),
+            })
+        }
+        Some(ImageFormat::Png) => {
+            let reader = png::Decoder::new(reader).read_info()?;
+            Ok(match reader.info().animation_control {
+                Some(actl) if actl.num_plays != 0 => Some(actl.num_plays),
+                _ => None,
+            })
+        }
+        _ => Ok(None),
+    }
+}
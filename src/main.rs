@@ -1,45 +1,123 @@
+mod args;
+mod color;
+mod compose;
+mod config;
+mod downscale;
+mod fd;
+mod fetch;
+mod ico;
+mod info;
+mod keymap;
+mod load;
+mod loops;
 mod math;
+mod mipmap;
+mod overlay;
+mod pages;
+mod palette;
+mod placement;
 mod ratio;
+mod raw;
+mod scan;
+mod sizing;
+mod source;
+mod svg;
+mod thumbnails;
+mod watch;
+// cfg predicate copied from winit, keep in sync with Cargo.toml
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "redox",
+        target_family = "wasm",
+        target_os = "android",
+        target_os = "ios",
+        target_os = "macos"
+    ))
+))]
+mod xlib;
 
 use std::{
-    cmp, env,
-    fs::{self, File},
-    io::BufReader,
-    mem,
-    path::Path,
+    cell::RefCell,
+    cmp,
+    collections::{HashMap, VecDeque},
+    env,
+    f64::consts::FRAC_1_SQRT_2,
+    fmt::Write as _,
+    fs, io, mem,
+    num::NonZeroU64,
+    path::{Path, PathBuf},
     process,
-    sync::Arc,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, AtomicI32, Ordering},
+        mpsc::{self, Sender},
+        Arc,
+    },
     thread,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{bail, Context};
+use args::{Args, Region};
+use color::Color;
+use config::Config;
+use downscale::Downscaler;
 use image::{
-    codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
-    AnimationDecoder, Delay, Frame, ImageFormat,
+    imageops::{self, FilterType},
+    ColorType, DynamicImage, ImageError, ImageFormat, Rgb, RgbImage, RgbaImage,
 };
+use info::InfoFormat;
+use keymap::{Action, Shortcut};
+use load::LoadedImage;
 use math::{vec2, vec4, Vec2f, Vec4f};
+use mipmap::MipmapGenerator;
+use overlay::{
+    Overlay, OverlayRenderer, LABEL_PADDING, LINE_HEIGHT, PANEL_COLOR, TEXT_COLOR, THUMBNAIL_SIZE,
+    THUMBNAIL_SLOTS,
+};
 use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+use serde::{Deserialize, Serialize};
+use source::Source;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
     CompositeAlphaMode,
 };
 use winit::{
     application::ApplicationHandler,
-    dpi::{PhysicalPosition, PhysicalSize},
-    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent},
     event_loop::{ActiveEventLoop, EventLoop, EventLoopProxy},
-    keyboard::{KeyCode, PhysicalKey},
+    keyboard::{ModifiersState, PhysicalKey},
+    monitor::MonitorHandle,
     window::{CursorIcon, ResizeDirection, Window, WindowId, WindowLevel},
 };
 
+/// Aspect ratios that the selection can be constrained to (`None` = unconstrained).
+const CROP_RATIOS: &[Option<f32>] = &[Some(1.0), Some(4.0 / 3.0), Some(16.0 / 9.0), None];
+
+// Maximum initial window size, in logical pixels.
 const WIN_WIDTH: u32 = 1280;
 const WIN_HEIGHT: u32 = 720;
 
+/// SVGs are rasterized at this multiple of the default window size, so that they stay reasonably
+/// sharp when zooming in or enlarging the window.
+const SVG_SUPERSAMPLING: u32 = 2;
+
+/// Space to leave free around the window when clamping its initial size to the monitor (in logical
+/// pixels). Monitors don't report their work area, so this also accounts for panels and task bars.
+const MONITOR_MARGIN: f32 = 64.0;
+
 /// Width of the border around the window contents within which the window gets
 /// resized instead of moved.
 const RESIZE_BORDER_WIDTH: f64 = 15.0;
 
+/// Distance (in physical pixels) the cursor has to move with the left mouse button held before the
+/// window starts moving.
+const DRAG_THRESHOLD: f64 = 4.0;
+/// Maximum time between two left clicks for them to count as a double click.
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+
 /// Size of the checkerboard pattern cells (in screen pixels).
 const CHECKERBOARD_CELL_SIZE: u32 = 10;
 
@@ -58,6 +136,123 @@ const CHECKERBOARD_DARK_B: f32 = 0.06;
 
 const SELECTION_COLOR: Vec4f = vec4(0.2, 0.5, 0.5, 0.1);
 
+/// Outline of the content bounds of the whole image.
+const CONTENT_BOUNDS_COLOR: Vec4f = vec4(1.0, 0.2, 0.8, 1.0);
+/// Outline of the content bounds of the current animation frame.
+const FRAME_BOUNDS_COLOR: Vec4f = vec4(0.2, 0.8, 1.0, 1.0);
+const CONTENT_BOUNDS_WIDTH: f32 = 1.0;
+
+/// Radius of the magnifier (in screen pixels).
+const LOUPE_RADIUS: f32 = 100.0;
+/// Magnification of the loupe relative to the main view, when it is first opened.
+const LOUPE_DEFAULT_ZOOM: f32 = 4.0;
+const LOUPE_MIN_ZOOM: f32 = 1.0;
+const LOUPE_MAX_ZOOM: f32 = 64.0;
+/// Factor by which each scroll wheel step changes the loupe's magnification.
+const LOUPE_ZOOM_STEP: f32 = 1.25;
+/// Fraction of the visible region to move by when panning with the arrow keys.
+const PAN_STEP: f32 = 0.1;
+/// Factor by which the <kbd>+</kbd> and <kbd>-</kbd> keys zoom in and out.
+const ZOOM_STEP: f32 = 1.5;
+/// How close (relative to the scale) a zoom level has to be to a whole multiple of 100% to snap to
+/// it (with `--snap-zoom`).
+const ZOOM_SNAP_TOLERANCE: f32 = 0.1;
+/// Number of previous zoom regions that can be returned to with [`Action::UndoZoom`].
+const ZOOM_HISTORY_MAX: usize = 32;
+/// Factor by which one step of the mouse wheel zooms in or out.
+const WHEEL_ZOOM_STEP: f32 = 1.25;
+/// Factor by which one step of the mouse wheel speeds up or slows down animations (with Ctrl).
+const PLAYBACK_SPEED_STEP: f32 = 1.25;
+/// Maximum number of [`PLAYBACK_SPEED_STEP`]s animations can be sped up or slowed down by.
+const PLAYBACK_SPEED_MAX_STEPS: i32 = 10;
+
+/// Radius of the window corners with `--rounded` (in logical pixels), if none is given.
+const DEFAULT_CORNER_RADIUS: f32 = 12.0;
+
+/// Assumed display refresh interval when the monitor doesn't report its refresh rate.
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_micros(16_667);
+
+/// Duration of the transition when switching to a different zoom region.
+const ZOOM_ANIMATION_DURATION: Duration = Duration::from_millis(150);
+
+/// Number of pixels that count as one "line" for devices that scroll by pixels (touchpads).
+const PIXELS_PER_SCROLL_LINE: f64 = 40.0;
+
+/// Height of the animation progress bar (in screen pixels).
+const PROGRESS_BAR_HEIGHT: f32 = 3.0;
+const PROGRESS_BAR_COLOR: Vec4f = vec4(1.0, 1.0, 1.0, 0.8);
+
+/// Fraction of the window width covered by the indicator shown while the image is preprocessed
+/// or downloaded.
+const SCANNING_INDICATOR_WIDTH: f32 = 0.2;
+/// Time the indicator takes to sweep across the window and back, in seconds.
+const SCANNING_INDICATOR_PERIOD: f32 = 1.5;
+/// Size of the transparent image a window shows while its first image is downloaded.
+const DOWNLOAD_PLACEHOLDER_SIZE: (u32, u32) = (640, 360);
+
+/// Height of the animation timeline strip at the bottom of the window (in screen pixels).
+const TIMELINE_HEIGHT: f32 = 12.0;
+const TIMELINE_COLOR: Vec4f = vec4(0.0, 0.0, 0.0, 0.5);
+const TIMELINE_PLAYED_COLOR: Vec4f = vec4(1.0, 1.0, 1.0, 0.6);
+
+/// Space around the thumbnails in the thumbnail strip (in screen pixels).
+const THUMBNAIL_PADDING: f32 = 8.0;
+const THUMBNAIL_STRIP_COLOR: Vec4f = vec4(0.0, 0.0, 0.0, 0.6);
+/// Shown in place of thumbnails that aren't available (yet).
+const THUMBNAIL_PLACEHOLDER_COLOR: Vec4f = vec4(0.5, 0.5, 0.5, 0.3);
+/// Border around the thumbnail of the displayed image.
+const THUMBNAIL_HIGHLIGHT_COLOR: Vec4f = vec4(1.0, 1.0, 1.0, 0.9);
+const THUMBNAIL_HIGHLIGHT_WIDTH: f32 = 2.0;
+
+/// Number of recent frames the `--show-fps` counter averages over.
+const FPS_SAMPLES: usize = 60;
+
+/// Commands offered by the context menu, in order.
+const CONTEXT_MENU: &[Action] = &[
+    Action::PreviousImage,
+    Action::NextImage,
+    Action::OpenRecent,
+    Action::Reload,
+    Action::CopyPath,
+    Action::SaveAs,
+    Action::ExportView,
+    Action::CycleTransparency,
+    Action::ToggleAlphaView,
+    Action::ToggleThumbnails,
+    Action::ActualSize,
+    Action::ResetZoom,
+    Action::OpenViewInNewWindow,
+    Action::ToggleHelp,
+    Action::Exit,
+];
+const CONTEXT_MENU_ROW_HEIGHT: f32 = 24.0;
+
+/// Buttons of the toolbar, with their labels.
+const TOOLBAR: &[(Action, &str)] = &[
+    (Action::PreviousImage, "<"),
+    (Action::NextImage, ">"),
+    (Action::ZoomOut, "-"),
+    (Action::ZoomIn, "+"),
+    (Action::ResetZoom, "Fit"),
+    (Action::Exit, "X"),
+];
+/// The toolbar is shown while the cursor is this close to the top of the window.
+const TOOLBAR_HOVER_HEIGHT: f64 = 64.0;
+const TOOLBAR_BUTTON_HEIGHT: f32 = 28.0;
+const TOOLBAR_FADE_DURATION: Duration = Duration::from_millis(300);
+const CONTEXT_MENU_HOVER_COLOR: Vec4f = vec4(1.0, 1.0, 1.0, 0.25);
+
+/// Distance between overlay panels and the edge of the window.
+const OVERLAY_MARGIN: f32 = 8.0;
+
+/// Tint shown over the window while a file is dragged over it.
+const DROP_HINT_COLOR: Vec4f = vec4(0.2, 0.5, 1.0, 0.3);
+
+const MEASURE_COLOR: Vec4f = vec4(1.0, 0.8, 0.0, 1.0);
+const MEASURE_LINE_WIDTH: f32 = 2.0;
+/// Size of the squares marking the ends of a measurement.
+const MEASURE_MARKER_SIZE: f32 = 6.0;
+
 const SUPPORTED_ALPHA_MODES: &[CompositeAlphaMode] = if cfg!(windows) {
     // On Windows, wgpu only seems to support pre-multiplied alpha with the `Inherit` mode.
     // FIXME: remove this when wgpu fixes this https://github.com/gfx-rs/wgpu/issues/3486
@@ -69,26 +264,165 @@ const SUPPORTED_ALPHA_MODES: &[CompositeAlphaMode] = if cfg!(windows) {
     &[CompositeAlphaMode::PreMultiplied]
 };
 
-/// Texture format used during rendering. Must match the format in `preprocess.wgsl`.
+/// Texture formats that can hold the preprocessed image, in order of preference.
+///
+/// Since these need to be storage-compatible formats, they can't be any of the `-srgb` formats.
+/// `Rgba8Unorm` loses precision in dark and translucent areas, but can be written to from compute
+/// shaders on hardware that doesn't support `Rgba16Float` storage textures.
+const TEXTURE_FORMATS: &[wgpu::TextureFormat] = &[
+    wgpu::TextureFormat::Rgba16Float,
+    wgpu::TextureFormat::Rgba8Unorm,
+];
+
+/// Surface formats with more than 8 bits per color channel, in order of preference.
+///
+/// The display pipeline works with `Rgba16Float` images, so these reduce banding in gradients.
+/// None of them are `-srgb` formats, so the shaders encode their output themselves when rendering
+/// to them.
+const HIGH_BIT_DEPTH_SURFACE_FORMATS: &[wgpu::TextureFormat] = &[wgpu::TextureFormat::Rgb10a2Unorm];
+
+/// Picks the format to render to from the formats supported by the surface.
+///
+/// `Rgb10a2Unorm` only has 2 bits of alpha, which would make partially transparent areas of the
+/// window look posterized, so high bit depth formats are only used if the window is opaque anyway.
+fn choose_surface_format(
+    formats: &[wgpu::TextureFormat],
+    supports_alpha: bool,
+) -> Option<wgpu::TextureFormat> {
+    let high_bit_depth = HIGH_BIT_DEPTH_SURFACE_FORMATS
+        .iter()
+        .find(|format| formats.contains(format));
+    match high_bit_depth {
+        Some(format) if !supports_alpha => Some(*format),
+        Some(format) => {
+            log::debug!("not using {format:?}, since the compositor supports transparency");
+            formats.first().copied()
+        }
+        None => formats.first().copied(),
+    }
+}
+
+/// Pipeline constants of the shaders that render to a surface of format `format`.
+fn output_constants(format: wgpu::TextureFormat) -> HashMap<String, f64> {
+    [("SRGB_OUTPUT".to_string(), f64::from(!format.is_srgb()))].into()
+}
+
+/// Returns the WGSL name of the storage texel format `format`.
+///
+/// The shaders are written for `rgba16float`, and get this substituted in when another format from
+/// [`TEXTURE_FORMATS`] is used.
+fn wgsl_texel_format(format: wgpu::TextureFormat) -> &'static str {
+    match format {
+        wgpu::TextureFormat::Rgba16Float => "rgba16float",
+        wgpu::TextureFormat::Rgba8Unorm => "rgba8unorm",
+        _ => unreachable!("unsupported storage format {format:?}"),
+    }
+}
+
+/// Loads the WGSL shader `source` with its storage textures using `format`.
+fn storage_shader(
+    device: &wgpu::Device,
+    label: &str,
+    source: &str,
+    format: wgpu::TextureFormat,
+) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(
+            source
+                .replace("rgba16float", wgsl_texel_format(format))
+                .into(),
+        ),
+    })
+}
+
+/// Backends to use unless overridden with `--backend`.
 ///
-/// Since this needs to be a storage-compatible format, it can't be any of the `-srgb` formats.
-const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// This excludes the OpenGL backend. It causes crashes even when not used, so it is only loaded when
+/// no other backend works (see `Viewer::open_adapter`).
+const DEFAULT_BACKENDS: wgpu::Backends = wgpu::Backends::PRIMARY;
+
+/// Exit code for errors that don't have a more specific one.
+const EXIT_ERROR: i32 = 1;
+/// Exit code for when an image file (or URL) doesn't exist.
+const EXIT_NOT_FOUND: i32 = 2;
+/// Exit code for when an image is in a format that can't be loaded.
+const EXIT_UNSUPPORTED_FORMAT: i32 = 3;
 
 fn main() {
     match run() {
         Ok(()) => {}
-        Err(e) => exit_with_error(format!("{e:#}")),
+        Err(e) => exit_with_error(format!("{e:#}"), exit_code(&e)),
     }
 }
 
-fn exit_with_error(error: String) -> ! {
+fn exit_with_error(error: String, code: i32) -> ! {
     eprintln!("Error: {error:#}");
+    show_error(&error);
+    process::exit(code);
+}
+
+/// Picks the exit code for `error` by looking for a known cause in its chain.
+fn exit_code(error: &anyhow::Error) -> i32 {
+    for cause in error.chain() {
+        if let Some(e) = cause.downcast_ref::<io::Error>() {
+            if e.kind() == io::ErrorKind::NotFound {
+                return EXIT_NOT_FOUND;
+            }
+        }
+        if let Some(ureq::Error::StatusCode(404 | 410)) = cause.downcast_ref() {
+            return EXIT_NOT_FOUND;
+        }
+        if let Some(ImageError::Unsupported(_)) = cause.downcast_ref() {
+            return EXIT_UNSUPPORTED_FORMAT;
+        }
+    }
+    EXIT_ERROR
+}
+
+/// Returns what `source` can be opened from again later: the absolute path of a file, or the URL.
+///
+/// File descriptors can't be opened again, so they have none.
+fn absolute_path(source: &Source) -> Option<PathBuf> {
+    match source {
+        Source::Path(path) => Some(std::path::absolute(path).unwrap_or_else(|_| path.clone())),
+        Source::Url(url) => Some(url.into()),
+        Source::Fd(_) => None,
+    }
+}
+
+/// Converts `region` to UV coordinates of an image of the given size, or fails if it doesn't fit.
+fn region_uv(
+    region: Region,
+    image_width: u32,
+    image_height: u32,
+) -> anyhow::Result<(Vec2f, Vec2f)> {
+    let fits =
+        |start: u32, len: u32, max: u32| start.checked_add(len).is_some_and(|end| end <= max);
+    if !fits(region.x, region.w, image_width) || !fits(region.y, region.h, image_height) {
+        bail!(
+            "Region {},{},{},{} exceeds the image bounds ({}x{})",
+            region.x,
+            region.y,
+            region.w,
+            region.h,
+            image_width,
+            image_height,
+        );
+    }
+    let size = vec2(image_width as f32, image_height as f32);
+    let min = vec2(region.x as f32, region.y as f32);
+    let max = min + vec2(region.w as f32, region.h as f32);
+    Ok((min / size, max / size))
+}
+
+/// Shows `error` in a message box.
+fn show_error(error: &str) {
     rfd::MessageDialog::new()
         .set_level(rfd::MessageLevel::Error)
         .set_title(concat!(env!("CARGO_PKG_NAME"), " – error"))
-        .set_description(format!("{error:#}"))
+        .set_description(error)
         .show();
-    process::exit(1);
 }
 
 fn run() -> anyhow::Result<()> {
@@ -97,628 +431,4448 @@ fn run() -> anyhow::Result<()> {
         .parse_default_env()
         .init();
 
-    let args = env::args_os().skip(1).collect::<Vec<_>>();
-    let path = match &*args {
-        [path] if path != "--help" => Path::new(path),
-        _ => bail!(
-            "Missing argument. Either drag an image file onto the application, register it as an \
-            image file handler in your file manager, or invoke `{}` with a path on the command \
-            line.",
-            env!("CARGO_PKG_NAME"),
-        ),
-    };
+    let mut args = Args::parse()?;
+    args.paths = scan::expand_dirs(args.paths, args.recursive)?;
+    let source = &args.paths[0];
 
-    log::info!("opening '{}'", path.display());
-    let metadata =
-        fs::metadata(path).context(format!("Failed to open image file '{}'", path.display()))?;
-    let kb = metadata.len() / 1024;
-
-    let start = Instant::now();
-    let reader = BufReader::new(File::open(path)?);
-    let format = ImageFormat::from_path(path)?;
-    let frames = match format {
-        ImageFormat::Png => {
-            let dec = PngDecoder::new(reader)?;
-            if dec.is_apng()? {
-                dec.apng()?.into_frames().collect_frames()?
-            } else {
-                // It's awkward to get a normal fucking image from a `PngDecoder` for some reason,
-                // so just use the `image::load` API.
-                vec![Frame::new(image::open(path)?.into_rgba8())]
+    if let Some(format) = args.print_info {
+        for (i, source) in args.paths.iter().enumerate() {
+            if i > 0 && format == InfoFormat::Text {
+                println!();
             }
+            info::print(source, &load::load(source)?, format)?;
         }
-        ImageFormat::Gif => GifDecoder::new(reader)?.into_frames().collect_frames()?,
-        ImageFormat::WebP => WebPDecoder::new(reader)?.into_frames().collect_frames()?,
-        _ => vec![Frame::new(image::open(path)?.into_rgba8())],
+        return Ok(());
+    }
+
+    let mut loaded = match source {
+        // Downloads happen once the window is open, unless the image is needed right away.
+        Source::Url(_) if args.output.is_none() && args.extract_frames.is_none() => None,
+        _ => Some(load::load(source)?),
+    };
+    let initial_region = match (args.region, &loaded) {
+        (Some(region), Some(loaded)) => Some(region_uv(region, loaded.width(), loaded.height())?),
+        _ => None,
     };
-    assert!(!frames.is_empty());
 
-    for frame in &frames {
-        if frame.top() != 0 || frame.left() != 0 {
-            bail!("`showimg` does not support animations with per-frame pixel offsets");
-        }
-    }
-    for win in frames.windows(2) {
-        let (a, b) = (&win[0], &win[1]);
-        if a.buffer().width() != b.buffer().width() || a.buffer().height() != b.buffer().height() {
-            bail!("`showimg` does not support animations with dynamic frame sizes");
-        }
+    if let (Some(output), Some(loaded)) = (&args.output, &mut loaded) {
+        // Headless mode: save the (cropped) first frame without ever opening a window.
+        let image = match args.region {
+            Some(r) => loaded.images[0].crop_imm(r.x, r.y, r.w, r.h),
+            None => loaded.images.swap_remove(0),
+        };
+        save_image(&image, output)?;
+        log::info!(
+            "saved {}x{} image to '{}'",
+            image.width(),
+            image.height(),
+            output.display(),
+        );
+        return Ok(());
     }
 
-    let what = if frames.len() == 1 {
-        "image"
-    } else {
-        "animation"
-    };
-    let image = frames[0].buffer();
-    let image_width = image.width();
-    let image_height = image.height();
-    let image_aspect_ratio = image_width as f32 / image_height as f32;
-    log::debug!(
-        "loaded {}x{} {what} from {} KiB file in {:.02?} (aspect ratio {}; memsize {} KiB per frame; {} frames)",
-        image.width(),
-        image.height(),
-        kb,
-        start.elapsed(),
-        image_aspect_ratio,
-        (image.width() * image.height() * 4) / 1024,
-        frames.len(),
-    );
-    let mut images = Vec::new();
-    let mut delays = Vec::new();
-    for frame in frames {
-        delays.push(frame.delay());
-        images.push(frame.into_buffer());
+    if let (Some(dir), Some(loaded)) = (&args.extract_frames, &loaded) {
+        // Headless mode: dump the frames without ever opening a window.
+        extract_frames(loaded, dir)?;
+        return Ok(());
     }
 
-    let title = match path.file_name() {
-        Some(name) => name.to_string_lossy(),
-        None => path.to_string_lossy(),
+    let background_image = match &args.bg_image {
+        Some(path) => {
+            let loaded = load::load(&Source::from_path(path.clone()))
+                .context("Failed to load the background image")?;
+            Some(Rc::new(load::to_srgb8(&loaded.images[0])))
+        }
+        None => None,
     };
 
-    let event_loop = EventLoop::builder().build()?;
+    let mut config = Config::load();
+    // A file descriptor can't be opened again later.
+    if let Some(path) = absolute_path(source) {
+        config.add_recent_file(path);
+        config.save();
+    }
+    let backends = args.backend.unwrap_or(DEFAULT_BACKENDS);
+
+    let event_loop = EventLoop::with_user_event().build()?;
     let proxy = event_loop.create_proxy();
 
-    event_loop.run_app(&mut App {
-        frame_count: images.len(),
-        image_aspect_ratio,
-        image_width,
-        image_height,
-        images,
-        delays: Some((proxy, delays)),
-        title: title.into(),
+    let mut viewer = Viewer {
+        transparency: args.transparency.unwrap_or(config.transparency),
+        filter: args.filter.unwrap_or(config.filter),
+        outline: config.outline,
+        dither: config.dither,
+        lanczos: config.lanczos,
+        checkerboard_zoom: config.checkerboard_zoom,
+        selection_color: args
+            .selection_color
+            .or(config.selection_color)
+            .map_or(SELECTION_COLOR, Color::to_linear_premultiplied),
+        selection_outline: args.selection_outline || config.selection_outline,
+        snap_zoom: args.snap_zoom || config.snap_zoom,
+        always_on_top: config.always_on_top && !args.no_always_on_top,
+        autocrop: config.autocrop && !args.no_autocrop,
+        vsync: !args.no_vsync,
+        crop_ratio: Some(args.crop_ratio.unwrap_or(1.0)),
+        assume_premultiplied: args.premultiplied,
+        play_count_override: args.play_count,
+        keymap: Rc::new(keymap::build(&config.keys)),
+        config: Rc::new(RefCell::new(config)),
+        pending_region: args.region.filter(|_| loaded.is_none()),
+        initial_region,
+        initial_zoom: args.zoom,
+        title_template: args.title,
+        toolbar: !args.no_toolbar,
+        free_resize: args.free_resize,
+        loupe_zoom: LOUPE_DEFAULT_ZOOM,
+        proxy: Some(proxy),
+        backends,
         instance: wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            // Disable the OpenGL backend. It causes crashes even when not used.
-            backends: wgpu::Backends::PRIMARY,
+            backends,
             ..Default::default()
         }),
-        ..App::default()
-    })?;
+        watch: args.watch,
+        show_fps: args.show_fps,
+        monitor: args.monitor,
+        auto_close: args.auto_close,
+        corner_radius: args.rounded.unwrap_or(0.0),
+        show_background_image: background_image.is_some(),
+        background_image,
+        ..Viewer::default()
+    };
+    viewer.startup = StartupSettings {
+        transparency: viewer.transparency,
+        filter: viewer.filter,
+        outline: viewer.outline,
+        dither: viewer.dither,
+        lanczos: viewer.lanczos,
+        checkerboard_zoom: viewer.checkerboard_zoom,
+        crop_ratio: viewer.crop_ratio,
+    };
+    viewer.set_first_image(source, loaded);
+    viewer.playlist = args.paths;
 
-    Ok(())
-}
+    let mut pending = Vec::new();
+    if args.windows {
+        // The other images get their own windows instead of waiting in the playlist.
+        for (index, source) in viewer.playlist.iter().enumerate().skip(1) {
+            let mut sibling = viewer.sibling();
+            let loaded = match source {
+                Source::Url(_) => None,
+                _ => Some(load::load(source)?),
+            };
+            sibling.set_first_image(source, loaded);
+            sibling.playlist_index = index;
+            sibling.initial_zoom = args.zoom;
+            pending.push(sibling);
+        }
+    }
+    pending.insert(0, viewer);
 
-struct Win {
-    supports_alpha: bool,
-    image_info: ImageInfo,
-    window: Arc<Window>,
-    surface: wgpu::Surface<'static>,
-    adapter: wgpu::Adapter,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
+    let mut app = App {
+        viewers: HashMap::new(),
+        pending,
+        print_selection: args.print_selection,
+    };
+    event_loop.run_app(&mut app)?;
 
-    /// The main render pipeline that displays the viewed image.
-    display_pipeline: wgpu::RenderPipeline,
-    /// Uniform buffer containing the [`DisplaySettings`].
-    display_settings: wgpu::Buffer,
-    /// One `BindGroup` per animation frame.
-    display_bind_groups: Vec<wgpu::BindGroup>,
+    Ok(())
 }
 
-#[derive(Default)]
-struct App {
-    image_aspect_ratio: f32, // full image aspect ratio; never changes
-    aspect_ratio: f32,       // selection aspect ratio
-    /// Frame data; cleared during startup.
-    images: Vec<image::RgbaImage>,
-    delays: Option<(EventLoopProxy<()>, Vec<Delay>)>,
-    image_width: u32,
-    image_height: u32,
-    frame_index: usize,
-    frame_count: usize,
-    title: String,
-    instance: wgpu::Instance,
-    window: Option<Win>,
-    min_uv: Vec2f,
-    max_uv: Vec2f,
-    cursor_pos: Option<PhysicalPosition<f64>>, // None = cursor left
-    cursor_mode: CursorMode,
-    transparency: TransparencyMode,
-    filter: FilterMode,
-}
+impl Uploader {
+    fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_format: wgpu::TextureFormat,
+        background: Option<&RgbaImage>,
+    ) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
 
-#[derive(Default, Clone, Copy)]
-enum CursorMode {
-    #[default]
-    Move,
-    Resize(ResizeDirection),
-    Select(PhysicalPosition<f64>),
-}
+        let display_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let no_downscaled = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("no_downscaled"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: texture_format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&Default::default());
 
-#[derive(Debug, Default, Clone, Copy, PartialEq)]
-enum TransparencyMode {
-    #[default]
-    TrueTransparency,
-    LightCheckerboard,
-    DarkCheckerboard,
+        Self {
+            texture_format,
+            sampler,
+            display_bgl,
+            background: upload_background(device, queue, background),
+            no_downscaled,
+            mipmap_generator: MipmapGenerator::new(device, texture_format),
+            downscaler: Downscaler::new(device, texture_format),
+        }
+    }
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq)]
-enum FilterMode {
-    #[default]
-    Smart,
-    Linear,
+/// Creates the pipeline that renders the image (see `display.wgsl`) to a target of format
+/// `surface_format`.
+fn create_display_pipeline(
+    device: &wgpu::Device,
+    uploader: &Uploader,
+    surface_format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("display.wgsl"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("display.wgsl").into()),
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(
+            &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&uploader.display_bgl],
+                push_constant_ranges: &[],
+            }),
+        ),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vertex"),
+            compilation_options: Default::default(),
+            buffers: &[],
+        },
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleStrip,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fragment"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &output_constants(surface_format),
+                ..Default::default()
+            },
+            targets: &[Some(wgpu::ColorTargetState::from(surface_format))],
+        }),
+        multiview: None,
+        cache: None,
+    })
 }
 
-impl ApplicationHandler for App {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        if self.window.is_none() {
-            let images = mem::take(&mut self.images);
-            let win = self.create_window(event_loop, images);
-            if !win.supports_alpha {
-                self.transparency = TransparencyMode::LightCheckerboard;
-            }
-            let window = win.window.clone();
-            self.window = Some(win);
-
-            self.reset_region();
+/// Creates the bind group of the display pipeline for showing the frames in `texture`, or the
+/// `downscaled` frame when zoomed out.
+fn display_bind_group(
+    device: &wgpu::Device,
+    uploader: &Uploader,
+    texture: &wgpu::Texture,
+    downscaled: Option<&wgpu::Texture>,
+    display_settings: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    let downscaled = downscaled.map(|texture| texture.create_view(&Default::default()));
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &uploader.display_bgl,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Sampler(&uploader.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&texture.create_view(
+                    &wgpu::TextureViewDescriptor {
+                        dimension: Some(wgpu::TextureViewDimension::D2Array),
+                        ..Default::default()
+                    },
+                )),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Buffer(
+                    display_settings.as_entire_buffer_binding(),
+                ),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&uploader.background),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(
+                    downscaled.as_ref().unwrap_or(&uploader.no_downscaled),
+                ),
+            },
+        ],
+    })
+}
 
-            if let Some((proxy, delays)) = mem::take(&mut self.delays) {
-                if delays.len() <= 1 {
-                    return;
-                }
+/// Uploads and preprocesses the frames of an image.
+///
+/// The preprocessing results are read back from the GPU afterwards, see
+/// [`ImageTextures::readback`]. Fails if the device can't hold the frames.
+fn upload_images(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    uploader: &Uploader,
+    display_settings: &wgpu::Buffer,
+    images: &[DynamicImage],
+    premultiplied: bool,
+) -> anyhow::Result<ImageTextures> {
+    let (image_width, image_height) = (images[0].width(), images[0].height());
+    let limits = device.limits();
 
-                thread::spawn(move || {
-                    log::debug!("starting animation thread");
-                    for delay in delays.iter().cycle() {
-                        thread::sleep(Duration::from(*delay));
-                        let Ok(()) = proxy.send_event(()) else { break };
-                        window.request_redraw();
-                    }
-                });
-            }
-        }
+    // Images that don't fit in a texture are shown downscaled. Everything outside of this function
+    // keeps working in the original image's pixels, since the texture is sampled with UVs.
+    let max = limits.max_texture_dimension_2d;
+    let scaled;
+    let images = if image_width > max || image_height > max {
+        let scale = f64::from(max) / f64::from(cmp::max(image_width, image_height));
+        let width = ((f64::from(image_width) * scale).floor() as u32).clamp(1, max);
+        let height = ((f64::from(image_height) * scale).floor() as u32).clamp(1, max);
+        log::warn!(
+            "image size {image_width}x{image_height} exceeds the maximum texture size supported \
+            by the GPU ({max}x{max}); showing it downscaled to {width}x{height}"
+        );
+        scaled = images
+            .iter()
+            .map(|image| image.resize_exact(width, height, FilterType::Triangle))
+            .collect::<Vec<_>>();
+        &scaled[..]
+    } else {
+        images
+    };
+    let (width, height) = (images[0].width(), images[0].height());
+    if limits.max_texture_array_layers < images.len() as u32 {
+        bail!(
+            "Animation has {} frames, but at most {} are supported",
+            images.len(),
+            limits.max_texture_array_layers,
+        );
     }
 
-    fn user_event(&mut self, _event_loop: &ActiveEventLoop, _event: ()) {
-        // The animation thread sends a user event every time the current frame's delay expires.
-        self.frame_index = (self.frame_index + 1) % self.frame_count;
+    // Every frame gets its own `ImageInfo`, placed at offsets that can be bound individually.
+    let info_size = mem::size_of::<ImageInfo>() as u64;
+    let info_stride = info_size.next_multiple_of(u64::from(
+        device.limits().min_storage_buffer_offset_alignment,
+    ));
+    let mut contents = vec![0; (info_stride * images.len() as u64) as usize];
+    for chunk in contents.chunks_mut(info_stride as usize) {
+        chunk[..info_size as usize].copy_from_slice(bytemuck::bytes_of(&ImageInfo::default()));
     }
+    let image_info = device.create_buffer_init(&BufferInitDescriptor {
+        label: None,
+        contents: &contents,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+    });
+    let preprocess_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    // The input is only loaded from, never sampled, so this also accepts
+                    // `Rgba32Float` textures, which aren't filterable.
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::StorageTexture {
+                    access: wgpu::StorageTextureAccess::WriteOnly,
+                    format: uploader.texture_format,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    });
 
-    fn window_event(
-        &mut self,
-        event_loop: &ActiveEventLoop,
-        window_id: WindowId,
-        event: WindowEvent,
-    ) {
-        let Some(win) = &self.window else { return };
-        if window_id != win.window.id() {
-            return;
-        }
-
-        match event {
-            WindowEvent::Resized(size) => {
-                // When the window is resized, we force it to have the same aspect ratio as the
-                // image it is displaying.
-                log::trace!("resized to {}x{}", size.width, size.height);
+    const PREPROCESS_WORKGROUP_SIZE: u32 = 16;
+    let preprocess_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: None,
+        layout: Some(
+            &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&preprocess_bgl],
+                push_constant_ranges: &[],
+            }),
+        ),
+        module: &storage_shader(
+            device,
+            "preprocess.wgsl",
+            include_str!("preprocess.wgsl"),
+            uploader.texture_format,
+        ),
+        entry_point: Some("preprocess"),
+        compilation_options: wgpu::PipelineCompilationOptions {
+            constants: &[
+                (
+                    "WORKGROUP_SIZE".to_string(),
+                    PREPROCESS_WORKGROUP_SIZE as f64,
+                ),
+                (
+                    "PREMULTIPLIED".to_string(),
+                    f64::from(u8::from(premultiplied)),
+                ),
+            ]
+            .into(),
+            zero_initialize_workgroup_memory: false,
+        },
+        cache: None,
+    });
+
+    // Upload and preprocess frames. All frames are stored in the layers of a single array
+    // texture.
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: images.len() as u32,
+    };
+    let input_format = match images[0] {
+        DynamicImage::ImageRgba32F(_) => wgpu::TextureFormat::Rgba32Float,
+        _ => wgpu::TextureFormat::Rgba8UnormSrgb,
+    };
+    // Computed in 64 bits: a gigapixel image doesn't fit a `u32` byte count.
+    let bytes_per_pixel = u32::from(images[0].color().bytes_per_pixel());
+    let bytes_per_row = bytes_per_pixel
+        .checked_mul(width)
+        .with_context(|| format!("Image width {width} is too large to upload"))?;
+    let frame_bytes = u64::from(bytes_per_row) * u64::from(height);
+    log::debug!(
+        "uploading {} frame(s) of {} KiB each",
+        images.len(),
+        frame_bytes / 1024,
+    );
+    let input_texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: input_format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    for (layer, image) in images.iter().enumerate() {
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &input_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: layer as u32,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            image.as_bytes(),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                depth_or_array_layers: 1,
+                ..size
+            },
+        );
+    }
+
+    // The preprocessed texture gets a full mip chain for smooth minification.
+    let output_texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size,
+        mip_level_count: size.max_mips(wgpu::TextureDimension::D2),
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: uploader.texture_format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+        view_formats: &[],
+    });
+
+    let mut enc = device.create_command_encoder(&Default::default());
+    let mut pass = enc.begin_compute_pass(&Default::default());
+    pass.set_pipeline(&preprocess_pipeline);
+    for layer in 0..size.depth_or_array_layers {
+        // The preprocessing shader works on one frame at a time.
+        let layer_view = |texture: &wgpu::Texture| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: layer,
+                array_layer_count: Some(1),
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        };
+        let preprocess_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &preprocess_bgl,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&layer_view(&input_texture)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&layer_view(&output_texture)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &image_info,
+                        offset: u64::from(layer) * info_stride,
+                        size: NonZeroU64::new(info_size),
+                    }),
+                },
+            ],
+        });
+        let workgroups_x = size.width.div_ceil(PREPROCESS_WORKGROUP_SIZE);
+        let workgroups_y = size.height.div_ceil(PREPROCESS_WORKGROUP_SIZE);
+        pass.set_bind_group(0, &preprocess_bind_group, &[]);
+        pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+    }
+    uploader
+        .mipmap_generator
+        .generate(device, &mut pass, &output_texture);
+    drop(pass);
+
+    let display_bind_group =
+        display_bind_group(device, uploader, &output_texture, None, display_settings);
+
+    // Copy the computed image information to a staging buffer.
+    let image_info_dl = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: image_info.size(),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    enc.copy_buffer_to_buffer(&image_info, 0, &image_info_dl, 0, image_info.size());
+
+    let submission = queue.submit([enc.finish()]);
+
+    Ok(ImageTextures {
+        image_info: ImageInfo::default(),
+        frame_infos: vec![ImageInfo::default(); images.len()],
+        texture: output_texture,
+        downscaled: None,
+        display_bind_group,
+        readback: Some(InfoReadback {
+            buffer: image_info_dl,
+            submission,
+            stride: info_stride,
+            texture_size: (width, height),
+            image_size: (image_width, image_height),
+            premultiplied,
+            start: Instant::now(),
+        }),
+    })
+}
+
+impl ImageTextures {
+    /// Blocks until the preprocessing results are available, and stores them.
+    fn wait_for_preprocessing(&mut self, device: &wgpu::Device) {
+        let Some(readback) = &self.readback else {
+            return;
+        };
+        let (sender, receiver) = mpsc::channel();
+        readback
+            .buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                sender.send(result).ok();
+            });
+        device.poll(wgpu::Maintain::wait_for(readback.submission.clone()));
+        // The callback should have been called by now, but some drivers never get there.
+        let result = receiver.try_recv().unwrap_or(Err(wgpu::BufferAsyncError));
+        self.finish_preprocessing(result);
+    }
+
+    /// Stores the preprocessing results once the readback buffer has been mapped (or failed to
+    /// be).
+    ///
+    /// If the results can't be read back, the image is treated as having no transparent border
+    /// to crop and as using its alpha channel, which only loses the autocrop and some warnings.
+    fn finish_preprocessing(&mut self, mapped: Result<(), wgpu::BufferAsyncError>) {
+        let Some(readback) = self.readback.take() else {
+            return;
+        };
+        if let Err(e) = mapped {
+            log::warn!(
+                "failed to read back the preprocessing results ({e}); showing the whole image"
+            );
+            let (width, height) = readback.image_size;
+            let info = ImageInfo {
+                uses_alpha: 1,
+                uses_partial_alpha: 0,
+                known_straight: 0,
+                top: 0,
+                right: width - 1,
+                bottom: height - 1,
+                left: 0,
+            };
+            self.frame_infos.fill(info);
+            self.image_info = info;
+            return;
+        }
+        let info_size = mem::size_of::<ImageInfo>();
+        self.frame_infos = readback
+            .buffer
+            .slice(..)
+            .get_mapped_range()
+            .chunks(readback.stride as usize)
+            .map(|chunk| *bytemuck::from_bytes::<ImageInfo>(&chunk[..info_size]))
+            .map(|info| info.rescaled(readback.texture_size, readback.image_size))
+            .collect();
+        readback.buffer.unmap();
+        log::debug!("preprocessing took {:.02?}", readback.start.elapsed());
+
+        // Content bounds and alpha usage of the animation as a whole.
+        let image_info = self
+            .frame_infos
+            .iter()
+            .fold(ImageInfo::default(), |acc, info| acc.union(info));
+        self.image_info = image_info;
+
+        log::debug!(
+            "left={} top={} right={} bottom={}",
+            image_info.left,
+            image_info.top,
+            image_info.right,
+            image_info.bottom,
+        );
+        log::debug!(
+            "uses_alpha={} known_straight={}",
+            image_info.uses_alpha(),
+            image_info.known_straight(),
+        );
+        if readback.premultiplied {
+            if image_info.known_straight() {
+                log::warn!(
+                    "image was declared premultiplied, but uses straight alpha; artifacts are \
+                    likely"
+                );
+            }
+        } else if image_info.uses_partial_alpha() && !image_info.known_straight() {
+            log::warn!(
+                "image uses alpha channel, but may already be premultiplied; artifacts are \
+                possible (use `--premultiplied` if it is)"
+            );
+        }
+    }
+}
+
+/// Saves `image` to `path`, choosing the format based on the file extension.
+fn save_image(image: &DynamicImage, path: &Path) -> anyhow::Result<()> {
+    let format = ImageFormat::from_path(path)?;
+    let is_float = matches!(
+        image,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+    );
+    // OpenEXR can only store floating-point data, while most other formats can't store it at all.
+    let mut converted = match (format == ImageFormat::OpenExr, is_float) {
+        (true, false) => Some(DynamicImage::ImageRgba32F(image.to_rgba32f())),
+        (false, true) => Some(DynamicImage::ImageRgba8(image.to_rgba8())),
+        _ => None,
+    };
+    // JPEG and PNM can't store an alpha channel at all.
+    if matches!(format, ImageFormat::Jpeg | ImageFormat::Pnm) && image.color().has_alpha() {
+        let rgba = converted.as_ref().unwrap_or(image).to_rgba8();
+        if rgba.pixels().any(|p| p[3] < u8::MAX) {
+            log::warn!("{format:?} can't store transparency, compositing the image onto white");
+        }
+        converted = Some(DynamicImage::ImageRgb8(flatten_onto_white(&rgba)));
+    }
+    converted
+        .as_ref()
+        .unwrap_or(image)
+        .save_with_format(path, format)
+        .with_context(|| format!("Failed to save image to '{}'", path.display()))
+}
+
+/// Uploads the image shown behind transparent areas with `--bg-image`.
+///
+/// Without one, a single transparent pixel is uploaded, since the display pass always needs a
+/// texture to bind.
+fn upload_background(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    image: Option<&RgbaImage>,
+) -> wgpu::TextureView {
+    let placeholder;
+    let image = match image {
+        Some(image) => image,
+        None => {
+            placeholder = RgbaImage::new(1, 1);
+            &placeholder
+        }
+    };
+    // It only ever covers the window, so it can be downscaled to fit the GPU's limits.
+    let max = device.limits().max_texture_dimension_2d;
+    let scaled;
+    let image = if image.width() > max || image.height() > max {
+        let scale = max as f32 / image.width().max(image.height()) as f32;
+        let width = ((image.width() as f32 * scale) as u32).clamp(1, max);
+        let height = ((image.height() as f32 * scale) as u32).clamp(1, max);
+        log::warn!("background image exceeds the maximum texture size, downscaling it");
+        scaled = imageops::resize(image, width, height, FilterType::Triangle);
+        &scaled
+    } else {
+        image
+    };
+    device
+        .create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("background"),
+                size: wgpu::Extent3d {
+                    width: image.width(),
+                    height: image.height(),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            image.as_raw(),
+        )
+        .create_view(&Default::default())
+}
+
+/// Renders the image with `settings` into an offscreen texture the size of the window, and reads
+/// it back.
+fn render_view(win: &Win, settings: &DisplaySettings) -> anyhow::Result<RgbaImage> {
+    let size = win.window.inner_size();
+    let format = win.surface_format;
+    let bytes_per_pixel = match format.block_copy_size(None) {
+        Some(4) => 4,
+        _ => bail!("reading back {format:?} textures is not supported"),
+    };
+    let extent = wgpu::Extent3d {
+        width: size.width.max(1),
+        height: size.height.max(1),
+        depth_or_array_layers: 1,
+    };
+    let create_target = |label, sample_count, usage| {
+        win.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: extent,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        })
+    };
+    let texture = create_target(
+        "export_target",
+        1,
+        wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+    );
+    let view = texture.create_view(&Default::default());
+    // The display pipeline is created for the window's sample count, so the target has to match.
+    let msaa = (win.sample_count > 1).then(|| {
+        create_target(
+            "export_msaa_target",
+            win.sample_count,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        )
+        .create_view(&Default::default())
+    });
+    let (target, resolve_target) = match &msaa {
+        Some(msaa) => (msaa, Some(&view)),
+        None => (&view, None),
+    };
+
+    let stride =
+        (extent.width * bytes_per_pixel).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let buffer = win.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("export_readback"),
+        size: u64::from(stride * extent.height),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    win.queue
+        .write_buffer(&win.display_settings, 0, bytemuck::bytes_of(settings));
+    let mut enc = win.device.create_command_encoder(&Default::default());
+    let mut pass = enc.begin_render_pass(&wgpu::RenderPassDescriptor {
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        ..Default::default()
+    });
+    pass.set_pipeline(&win.display_pipeline);
+    pass.set_bind_group(0, &win.textures.display_bind_group, &[]);
+    pass.draw(0..4, 0..1);
+    drop(pass);
+    enc.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(stride),
+                rows_per_image: None,
+            },
+        },
+        extent,
+    );
+    let submission = win.queue.submit([enc.finish()]);
+
+    let (sender, receiver) = mpsc::channel();
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).ok();
+        });
+    win.device.poll(wgpu::Maintain::wait_for(submission));
+    receiver
+        .try_recv()
+        .context("readback buffer was never mapped")?
+        .context("failed to map readback buffer")?;
+
+    let data = buffer.slice(..).get_mapped_range();
+    let mut image = RgbaImage::new(extent.width, extent.height);
+    for (row, out) in data
+        .chunks(stride as usize)
+        .zip(image.chunks_mut((extent.width * 4) as usize))
+    {
+        for (texel, out) in row.chunks(4).zip(out.chunks_mut(4)) {
+            let texel: [u8; 4] = texel.try_into().unwrap();
+            out.copy_from_slice(&texel_to_rgba8(format, texel, win.supports_alpha)?);
+        }
+    }
+    drop(data);
+    buffer.unmap();
+    Ok(image)
+}
+
+/// Converts a texel of the surface format `format` to straight-alpha RGBA8.
+///
+/// The surface holds sRGB-encoded values premultiplied in sRGB space (see `display.wgsl`), or
+/// ignores alpha entirely if the compositor doesn't support transparency.
+fn texel_to_rgba8(
+    format: wgpu::TextureFormat,
+    texel: [u8; 4],
+    supports_alpha: bool,
+) -> anyhow::Result<[u8; 4]> {
+    use wgpu::TextureFormat::*;
+
+    let [r, g, b, a] = match format {
+        Rgba8Unorm | Rgba8UnormSrgb => texel,
+        Bgra8Unorm | Bgra8UnormSrgb => [texel[2], texel[1], texel[0], texel[3]],
+        Rgb10a2Unorm => {
+            let bits = u32::from_le_bytes(texel);
+            let channel = |shift: u32| (((bits >> shift) & 0x3ff) >> 2) as u8;
+            [
+                channel(0),
+                channel(10),
+                channel(20),
+                ((bits >> 30) * 85) as u8,
+            ]
+        }
+        _ => bail!("reading back {format:?} textures is not supported"),
+    };
+    if !supports_alpha {
+        return Ok([r, g, b, u8::MAX]);
+    }
+    let unpremultiply = |c: u8| match a {
+        0 => 0,
+        a => (u32::from(c) * 255 / u32::from(a)).min(255) as u8,
+    };
+    Ok([unpremultiply(r), unpremultiply(g), unpremultiply(b), a])
+}
+
+/// Composites `image` onto a white background.
+fn flatten_onto_white(image: &RgbaImage) -> RgbImage {
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        let alpha = f32::from(a) / 255.0;
+        let blend = |c: u8| (f32::from(c) * alpha + 255.0 * (1.0 - alpha)).round() as u8;
+        Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+/// Saves every frame of `loaded` as a PNG file in `dir`, along with a `delays.txt` file that lists
+/// how long each frame is shown (in milliseconds).
+fn extract_frames(loaded: &LoadedImage, dir: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create directory '{}'", dir.display()))?;
+    let count = loaded.images.len();
+    let digits = count.to_string().len().max(4);
+    let mut delays = String::new();
+    for (index, image) in loaded.images.iter().enumerate() {
+        let name = format!("frame_{index:0digits$}.png");
+        save_image(image, &dir.join(&name))?;
+        let delay = loaded
+            .delays
+            .get(index)
+            .map_or(Duration::ZERO, |&delay| Duration::from(delay));
+        writeln!(delays, "{name} {}", delay.as_millis()).unwrap();
+        if (index + 1) % 10 == 0 || index + 1 == count {
+            log::info!("extracted {}/{count} frames", index + 1);
+        }
+    }
+    let path = dir.join("delays.txt");
+    fs::write(&path, delays).with_context(|| format!("Failed to write '{}'", path.display()))?;
+    log::info!("saved {count} frames to '{}'", dir.display());
+    Ok(())
+}
+
+struct Win {
+    supports_alpha: bool,
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+
+    /// The main render pipeline that displays the viewed image.
+    display_pipeline: wgpu::RenderPipeline,
+    /// Uniform buffer containing the [`DisplaySettings`].
+    display_settings: wgpu::Buffer,
+    uploader: Uploader,
+    /// The frames of the image that is being displayed.
+    textures: ImageTextures,
+    overlay: OverlayRenderer,
+    present_mode: wgpu::PresentMode,
+    /// Format of the surface textures (see [`choose_surface_format`]).
+    surface_format: wgpu::TextureFormat,
+    /// Number of samples per pixel of the render target.
+    sample_count: u32,
+    /// Multisampled render target that gets resolved to the surface texture (`None` if
+    /// `sample_count` is 1). Recreated with the swapchain.
+    msaa_target: RefCell<Option<wgpu::TextureView>>,
+    /// The state that was rendered last.
+    last_frame: RefCell<Option<FrameState>>,
+    /// When the last [`FPS_SAMPLES`] frames were presented, oldest first.
+    frame_times: RefCell<VecDeque<Instant>>,
+    /// The title that was set last. [`Window::title`] isn't implemented on every platform.
+    title: RefCell<String>,
+}
+
+impl Win {
+    /// Sets the window title, unless it already is `title`.
+    fn set_title(&self, title: String) {
+        let mut current = self.title.borrow_mut();
+        if *current != title {
+            self.window.set_title(&title);
+            *current = title;
+        }
+    }
+}
+
+/// GPU objects used for uploading images, which are kept around for opening other images.
+struct Uploader {
+    /// Format of the preprocessed frames (one of [`TEXTURE_FORMATS`]).
+    texture_format: wgpu::TextureFormat,
+    sampler: wgpu::Sampler,
+    display_bgl: wgpu::BindGroupLayout,
+    /// The `--bg-image` (or a transparent placeholder pixel without one).
+    background: wgpu::TextureView,
+    /// Bound in place of a downscaled frame before there is one.
+    no_downscaled: wgpu::TextureView,
+    mipmap_generator: MipmapGenerator,
+    downscaler: Downscaler,
+}
+
+/// The preprocessed frames of an image, and what was learned about them during preprocessing.
+struct ImageTextures {
+    /// Combined `ImageInfo` of all frames.
+    image_info: ImageInfo,
+    frame_infos: Vec<ImageInfo>,
+    /// Array texture containing all animation frames, with mipmaps.
+    texture: wgpu::Texture,
+    /// The current frame downscaled for [`Action::ToggleLanczos`], once it has been needed.
+    downscaled: Option<DownscaledFrame>,
+    /// Binds `texture` and `downscaled`.
+    display_bind_group: wgpu::BindGroup,
+    /// Set until the preprocessing results have been read back from the GPU. Until then,
+    /// `image_info` and `frame_infos` are placeholders.
+    readback: Option<InfoReadback>,
+}
+
+/// A frame downscaled to (roughly) the size it is displayed at, which is only computed again once
+/// the zoom level, the window size, or the frame changes. The texture itself is bound by
+/// [`ImageTextures::display_bind_group`].
+struct DownscaledFrame {
+    frame: u32,
+    width: u32,
+    height: u32,
+}
+
+/// The `ImageInfo`s written by the preprocessing pass, on their way back from the GPU.
+struct InfoReadback {
+    /// Staging buffer with one `ImageInfo` per frame, `stride` bytes apart.
+    buffer: wgpu::Buffer,
+    submission: wgpu::SubmissionIndex,
+    stride: u64,
+    /// Size of the uploaded frames, which are smaller than the image if it had to be downscaled.
+    texture_size: (u32, u32),
+    image_size: (u32, u32),
+    premultiplied: bool,
+    start: Instant,
+}
+
+/// Details about the image file that are only used for display.
+#[derive(Default)]
+struct FileInfo {
+    /// Name of the file format (see [`LoadedImage::format_name`]).
+    format: String,
+    color_type: Option<ColorType>,
+    kb: u64,
+}
+
+/// Events sent to the event loop from other threads, for the window with the given ID.
+#[derive(Debug)]
+enum UserEvent {
+    /// Sent by the animation thread with the number of frames to advance by.
+    Advance(WindowId, usize),
+    /// Sent by the animation thread instead of the last [`UserEvent::Advance`] once the last loop
+    /// of an animation with a finite play count has been played.
+    AnimationFinished(WindowId),
+    /// Sent by the file watcher when the displayed file has changed on disk.
+    FileChanged(WindowId),
+    /// Sent by the thumbnail generator (`None` if the file couldn't be loaded).
+    Thumbnail(WindowId, Source, Option<RgbaImage>),
+    /// Sent once the `--auto-close` timeout has expired.
+    AutoClose(WindowId),
+    /// Sent when the results of preprocessing the image can be read back from the GPU (or when
+    /// mapping the readback buffer failed).
+    Preprocessed(WindowId, Result<(), wgpu::BufferAsyncError>),
+    /// Sent by the download thread once the image from the URL has been loaded (or has failed to).
+    Downloaded(WindowId, Source, anyhow::Result<LoadedImage>),
+}
+
+impl UserEvent {
+    fn window_id(&self) -> WindowId {
+        match self {
+            UserEvent::Advance(id, _)
+            | UserEvent::AnimationFinished(id)
+            | UserEvent::FileChanged(id)
+            | UserEvent::Thumbnail(id, ..)
+            | UserEvent::AutoClose(id)
+            | UserEvent::Preprocessed(id, _)
+            | UserEvent::Downloaded(id, ..) => *id,
+        }
+    }
+}
+
+/// What to do with an image once it has been downloaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LoadPurpose {
+    /// Replace the placeholder the window opened with.
+    Startup,
+    /// Show it with a fresh view, like any other opened image.
+    Open,
+    /// Show it in place of the current image, keeping the view.
+    Reload,
+}
+
+/// An image that is being downloaded on another thread.
+struct Download {
+    source: Source,
+    purpose: LoadPurpose,
+    /// When the download started, for animating the indicator.
+    start: Instant,
+}
+
+enum Thumbnail {
+    /// Requested from the thumbnail generator.
+    Pending,
+    Ready {
+        /// Location in the thumbnail atlas.
+        uv: (Vec2f, Vec2f),
+        size: Vec2f,
+    },
+    Failed,
+}
+
+/// The open windows, each with its own [`Viewer`].
+struct App {
+    viewers: HashMap<WindowId, Viewer>,
+    /// Viewers whose windows will be created once the event loop is ready.
+    pending: Vec<Viewer>,
+    /// Print the selection of each window when it is closed (`--print-selection`).
+    print_selection: bool,
+}
+
+impl App {
+    /// Creates the windows of all pending viewers.
+    fn create_pending(&mut self, event_loop: &ActiveEventLoop) {
+        for mut viewer in mem::take(&mut self.pending) {
+            viewer.resumed(event_loop);
+            let Some(win) = &viewer.window else { continue };
+            self.viewers.insert(win.window.id(), viewer);
+        }
+    }
+
+    /// Handles the windows that a viewer closed or opened while processing an event.
+    fn after_event(&mut self, event_loop: &ActiveEventLoop, window_id: WindowId) {
+        let Some(viewer) = self.viewers.get_mut(&window_id) else {
+            return;
+        };
+        self.pending.append(&mut viewer.spawned);
+        if viewer.closed {
+            let mut viewer = self.viewers.remove(&window_id).unwrap();
+            viewer.stop_animation();
+            if self.print_selection {
+                let (x, y, w, h) = viewer.selection_pixels();
+                println!("{x} {y} {w} {h}");
+            }
+        }
+        self.create_pending(event_loop);
+        if self.viewers.is_empty() {
+            log::info!("last window closed -> exiting");
+            event_loop.exit();
+        }
+    }
+}
+
+impl ApplicationHandler<UserEvent> for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.create_pending(event_loop);
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        let window_id = event.window_id();
+        if let Some(viewer) = self.viewers.get_mut(&window_id) {
+            viewer.user_event(event);
+            self.after_event(event_loop, window_id);
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        if let Some(viewer) = self.viewers.get_mut(&window_id) {
+            viewer.window_event(event);
+            self.after_event(event_loop, window_id);
+        }
+    }
+}
+
+/// The display settings a window started out with (from the configuration file and the command
+/// line), restored by [`Action::ResetAll`].
+#[derive(Debug, Clone, Copy, Default)]
+struct StartupSettings {
+    transparency: TransparencyMode,
+    filter: FilterMode,
+    outline: bool,
+    dither: bool,
+    lanczos: bool,
+    checkerboard_zoom: bool,
+    crop_ratio: Option<f32>,
+}
+
+/// The state of a single window and the image it shows.
+#[derive(Default)]
+struct Viewer {
+    image_aspect_ratio: f32, // full image aspect ratio; never changes
+    aspect_ratio: f32,       // selection aspect ratio
+    /// The decoded image, which stays around after uploading it, and is shared with the windows
+    /// opened from this one.
+    loaded: Rc<LoadedImage>,
+    proxy: Option<EventLoopProxy<UserEvent>>,
+    /// Tells the animation thread of the current image to exit.
+    animation_stop: Option<Arc<AtomicBool>>,
+    /// Animation playback speed, as a number of [`PLAYBACK_SPEED_STEP`]s (0 = normal speed).
+    ///
+    /// Shared with the animation thread, so that changes take effect without restarting it.
+    playback_speed: Arc<AtomicI32>,
+    /// Display duration of each animation frame.
+    frame_delays: Vec<Duration>,
+    /// Number of times the animation is played before it stops on the last frame (`None` = forever).
+    play_count: Option<u32>,
+    /// Play count from `--loop`, which takes precedence over the one from the file (0 = forever).
+    play_count_override: Option<u32>,
+    image_width: u32,
+    image_height: u32,
+    frame_index: usize,
+    frame_count: usize,
+    /// Whether the frames are played back as an animation.
+    animated: bool,
+    /// Original size of each entry of an icon file (before scaling them to a common size).
+    entry_sizes: Vec<(u32, u32)>,
+    /// UV region to show on startup instead of the content bounds (from `--region`).
+    initial_region: Option<(Vec2f, Vec2f)>,
+    /// `--region`, if it can't be checked yet because the first image is still being downloaded.
+    pending_region: Option<Region>,
+    /// The image that is being downloaded in the background, if any.
+    download: Option<Download>,
+    /// Zoom level to open the window at, in percent (from `--zoom`).
+    initial_zoom: Option<f32>,
+    /// The last region selected with the middle mouse button (cleared when resetting the view).
+    committed_selection: Option<(Vec2f, Vec2f)>,
+    /// Previously displayed UV regions of the current image, oldest first.
+    zoom_history: VecDeque<(Vec2f, Vec2f)>,
+    /// The region that was displayed when `committed_selection` was made.
+    selection_view: (Vec2f, Vec2f),
+    /// The selection being edited (shown on top of `selection_view`), while in adjust mode.
+    adjusting_selection: Option<(Vec2f, Vec2f)>,
+    /// In-progress transition from a previous zoom region to `min_uv`-`max_uv`.
+    zoom_animation: Option<ZoomAnimation>,
+    /// Where the displayed image was read from.
+    source: Source,
+    /// The images that can be navigated between (from the command line).
+    playlist: Vec<Source>,
+    /// Index of the displayed image in `playlist`.
+    playlist_index: usize,
+    show_thumbnails: bool,
+    thumbnails: HashMap<Source, Thumbnail>,
+    /// Number of thumbnail atlas slots that are in use.
+    thumbnail_slots: u32,
+    /// Sends paths to the thumbnail generator thread, once it was started.
+    thumbnail_requests: Option<Sender<Source>>,
+    /// Whether to reload the image when its file changes (`--watch`).
+    watch: bool,
+    /// Whether to show the frame rate counter (`--show-fps`).
+    show_fps: bool,
+    /// Index of the monitor to open the window on (`--monitor`).
+    monitor: Option<usize>,
+    /// Close the window after this long (`--auto-close`); reset to `None` by user interaction.
+    auto_close: Option<Duration>,
+    /// Radius of the rounded window corners in logical pixels (`--rounded`), or 0.
+    corner_radius: f32,
+    /// Image to show behind transparent areas (`--bg-image`).
+    background_image: Option<Rc<RgbaImage>>,
+    /// Whether `background_image` is shown instead of the `transparency` mode's background.
+    show_background_image: bool,
+    watcher: Option<notify::RecommendedWatcher>,
+    /// Set while a `UserEvent::FileChanged` is queued, so that a burst of file system events
+    /// only causes a single reload.
+    reload_pending: Arc<AtomicBool>,
+    title: String,
+    /// Window title from `--title`, with placeholders not yet replaced.
+    title_template: Option<String>,
+    /// The backends `instance` was created with.
+    backends: wgpu::Backends,
+    instance: wgpu::Instance,
+    window: Option<Win>,
+    min_uv: Vec2f,
+    max_uv: Vec2f,
+    cursor_pos: Option<PhysicalPosition<f64>>, // None = cursor left
+    cursor_mode: CursorMode,
+    /// Time and position of the last left click, for detecting double clicks.
+    last_click: Option<(Instant, PhysicalPosition<f64>)>,
+    /// Settings to go back to on [`Action::ResetAll`].
+    startup: StartupSettings,
+    transparency: TransparencyMode,
+    filter: FilterMode,
+    /// Filter mode used for the current image instead of `filter` (for tiny images, which are
+    /// shown pixel-doubled). Cleared when another image is opened or the filter is changed.
+    filter_override: Option<FilterMode>,
+    /// Shared with the other windows.
+    config: Rc<RefCell<Config>>,
+    /// Action bound to each key.
+    keymap: Rc<HashMap<Shortcut, Action>>,
+    always_on_top: bool,
+    vsync: bool,
+    show_frame_counter: bool,
+    /// Whether the source image is treated as already premultiplied.
+    premultiplied: bool,
+    /// Whether `--premultiplied` was passed.
+    assume_premultiplied: bool,
+    /// Whether a file is being dragged over the window.
+    drop_hover: bool,
+    /// Whether to show what is known about the image's use of the alpha channel.
+    show_alpha_info: bool,
+    /// Whether to show the average and dominant colors of the image.
+    show_palette: bool,
+    /// The colors shown with `show_palette`, for each frame they have been computed for (`None` if
+    /// the frame is empty).
+    palettes: HashMap<usize, Option<palette::Palette>>,
+    file_info: FileInfo,
+    /// Whether to show information about the image file.
+    show_file_info: bool,
+    /// Whether to show the list of keyboard shortcuts.
+    show_help: bool,
+    /// Top left corner of the context menu, while it is open.
+    context_menu: Option<Vec2f>,
+    /// Whether the toolbar is shown when hovering over the top of the window.
+    toolbar: bool,
+    /// Whether the window may have a different aspect ratio than the displayed region, which is
+    /// then letterboxed (`--free-resize`).
+    free_resize: bool,
+    toolbar_hover: ToolbarHover,
+    /// Whether to draw an outline around the image.
+    outline: bool,
+    /// Whether to dither the output to hide banding (only done on 8-bit surfaces).
+    dither: bool,
+    /// Whether to downscale with a Lanczos filter when zoomed out.
+    lanczos: bool,
+    /// Whether the checkerboard cells are measured in image pixels instead of screen pixels.
+    checkerboard_zoom: bool,
+    /// Linear, premultiplied color the selection is tinted with.
+    selection_color: Vec4f,
+    /// Whether to outline the selection with black and white dashes.
+    selection_outline: bool,
+    /// Whether zooming in or out snaps to whole multiples of 100% when close to one.
+    snap_zoom: bool,
+    /// Set once a pinch gesture was received. From then on, scrolling with pixel precision (which
+    /// is what touchpads do) pans the view instead of zooming, since pinching zooms.
+    touchpad_gestures: bool,
+    /// Whether to show the alpha channel as grayscale instead of the image.
+    alpha_view: bool,
+    /// Whether to outline the content bounds detected during preprocessing.
+    show_content_bounds: bool,
+    /// Whether the default view is cropped to the content bounds instead of showing the whole
+    /// image.
+    autocrop: bool,
+    /// Position in the recently opened files that [`Action::OpenRecent`] opens next.
+    recent_index: usize,
+    grid: GridMode,
+    /// Whether to draw lines between the image pixels when zoomed in far enough.
+    pixel_grid: bool,
+    modifiers: ModifiersState,
+    /// Aspect ratio the selection is constrained to while Shift is held (`None` = unconstrained).
+    crop_ratio: Option<f32>,
+    /// Whether left clicks place measurement points instead of moving the window.
+    measuring: bool,
+    /// Endpoints of the current measurement in UV coordinates. Until the second point is placed,
+    /// the measurement ends at the cursor.
+    measure_start: Option<Vec2f>,
+    measure_end: Option<Vec2f>,
+    loupe: bool,
+    loupe_zoom: f32,
+    /// Created when something is first copied, and kept around since the copied text is lost
+    /// when it is dropped on some platforms.
+    clipboard: Option<arboard::Clipboard>,
+    /// Set when the window should be closed.
+    closed: bool,
+    /// Viewers for new windows that were opened from this one, which still need to be created.
+    spawned: Vec<Viewer>,
+}
+
+/// Tracks whether the cursor is in the area that shows the toolbar.
+#[derive(Default)]
+struct ToolbarHover {
+    hovered: bool,
+    /// When the cursor left the toolbar area, while the toolbar is fading out.
+    fade: Option<Instant>,
+}
+
+impl ToolbarHover {
+    fn set(&mut self, hovered: bool) {
+        if self.hovered && !hovered {
+            self.fade = Some(Instant::now());
+        }
+        self.hovered = hovered;
+    }
+
+    /// Returns the opacity of the toolbar, which fades out after the cursor leaves it.
+    fn opacity(&self) -> f32 {
+        if self.hovered {
+            return 1.0;
+        }
+        match self.fade {
+            Some(start) => {
+                1.0 - (start.elapsed().as_secs_f32() / TOOLBAR_FADE_DURATION.as_secs_f32()).min(1.0)
+            }
+            None => 0.0,
+        }
+    }
+}
+
+struct ZoomAnimation {
+    from_min: Vec2f,
+    from_max: Vec2f,
+    start: Instant,
+}
+
+#[derive(Default, Clone, Copy)]
+enum CursorMode {
+    #[default]
+    Move,
+    /// Left mouse button held down in `Move` mode. The window move is deferred until the cursor
+    /// moves by at least `DRAG_THRESHOLD`, so that double clicks don't start one.
+    Press(PhysicalPosition<f64>),
+    Resize(ResizeDirection),
+    /// Selecting a region with the middle mouse button. Holds the window position of the corner
+    /// opposite to the cursor, which moves along with it while Ctrl is held.
+    Select(PhysicalPosition<f64>),
+    /// Hovering over the animation timeline.
+    Timeline,
+    /// Dragging on the animation timeline. Playback is paused in this mode.
+    Scrub,
+    /// Hovering over an edge or corner of the selection that is being adjusted.
+    AdjustSelection(ResizeDirection),
+    /// Dragging an edge or corner of the selection that is being adjusted.
+    DragSelection(ResizeDirection),
+    /// Hovering over the thumbnail of the playlist entry with the given index.
+    Thumbnail(usize),
+    /// Hovering over an entry of the context menu.
+    MenuItem(Action),
+    /// Hovering over a toolbar button.
+    ToolbarButton(Action),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum TransparencyMode {
+    #[default]
+    TrueTransparency,
+    LightCheckerboard,
+    DarkCheckerboard,
+}
+
+/// Composition guide drawn over the image (values match `display.wgsl`).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum GridMode {
+    #[default]
+    Off = 0,
+    Thirds = 1,
+    GoldenRatio = 2,
+    Fine = 3,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum FilterMode {
+    #[default]
+    Smart,
+    Linear,
+    /// Nearest-neighbor sampling whenever the image is magnified.
+    Nearest,
+}
+
+impl Viewer {
+    /// Creates the window, unless it already exists.
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_none() {
+            let win = self.create_window(event_loop, &self.loaded.images);
+            if !win.supports_alpha && self.transparency == TransparencyMode::TrueTransparency {
+                // Don't restore a mode the compositor can't display.
+                self.transparency = TransparencyMode::LightCheckerboard;
+            }
+            self.window = Some(win);
+
+            self.filter_override = self.tiny_filter_override();
+
+            // The initial region depends on the content bounds found during preprocessing, so the
+            // rest happens once they are known (see `UserEvent::Preprocessed`).
+            self.start_readback();
+        }
+    }
+
+    /// Reads back the preprocessing results of the first image on a separate thread, so that the
+    /// window can show a progress indicator while waiting for the GPU.
+    fn start_readback(&mut self) {
+        let (Some(win), Some(proxy)) = (&self.window, &self.proxy) else {
+            return;
+        };
+        let Some(readback) = &win.textures.readback else {
+            return;
+        };
+        let window_id = win.window.id();
+        // Set by whichever of the callback and the polling thread reports the result first.
+        let reported = Arc::new(AtomicBool::new(false));
+        let (callback_proxy, callback_reported) = (proxy.clone(), reported.clone());
+        readback
+            .buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if !callback_reported.swap(true, Ordering::Relaxed) {
+                    let event = UserEvent::Preprocessed(window_id, result);
+                    callback_proxy.send_event(event).ok();
+                }
+            });
+        let device = win.device.clone();
+        let submission = readback.submission.clone();
+        let proxy = proxy.clone();
+        thread::spawn(move || {
+            device.poll(wgpu::Maintain::wait_for(submission));
+            // The callback should have been called by now, but some drivers never get there, which
+            // would leave the window waiting forever.
+            if !reported.swap(true, Ordering::Relaxed) {
+                let event = UserEvent::Preprocessed(window_id, Err(wgpu::BufferAsyncError));
+                proxy.send_event(event).ok();
+            }
+        });
+        win.window.request_redraw();
+    }
+
+    /// Finishes opening the window once the preprocessing results of the first image are known.
+    fn preprocessed(&mut self, mapped: Result<(), wgpu::BufferAsyncError>) {
+        let Some(win) = &mut self.window else { return };
+        if win.textures.readback.is_none() {
+            return;
+        }
+        win.textures.finish_preprocessing(mapped);
+        if win.textures.image_info.uses_alpha() && !win.supports_alpha {
+            log::warn!(
+                "compositor does not support premultiplied alpha; using checkerboard background"
+            );
+        }
+
+        self.show_initial_region();
+        self.start_animation();
+        self.watch_file();
+        self.start_auto_close();
+        // The result of downloading the first image can only be sent once there is a window.
+        if self
+            .download
+            .as_ref()
+            .is_some_and(|d| d.purpose == LoadPurpose::Startup)
+        {
+            self.start_download();
+        }
+    }
+
+    /// Shows the region given on the command line, or the whole image.
+    fn show_initial_region(&mut self) {
+        match self.initial_region {
+            Some((min, max)) => self.set_region(min, max),
+            // A zoom level refers to the whole image, not its content bounds.
+            None if self.initial_zoom.is_some() => self.set_region(vec2(0.0, 0.0), vec2(1.0, 1.0)),
+            None => self.reset_region(),
+        }
+        // Setting the region may not change the window size, which would otherwise redraw it.
+        if let Some(win) = &self.window {
+            win.window.request_redraw();
+        }
+    }
+
+    fn user_event(&mut self, event: UserEvent) {
+        match event {
+            UserEvent::Advance(_, frames) => {
+                // The animation thread sends this every time the current frame's delay expires.
+                // It also requests a redraw afterwards, which updates the frame counter overlay.
+                if let CursorMode::Scrub = self.cursor_mode {
+                    // Playback is paused while the user is scrubbing through the timeline.
+                    return;
+                }
+                self.frame_index = (self.frame_index + frames) % self.frame_count;
+            }
+            UserEvent::AnimationFinished(_) => {
+                if let CursorMode::Scrub = self.cursor_mode {
+                    return;
+                }
+                // Hold on the final frame, regardless of where scrubbing or stepping through the
+                // frames has left the frame index.
+                self.frame_index = self.frame_count - 1;
+            }
+            UserEvent::FileChanged(_) => {
+                self.reload_pending.store(false, Ordering::Relaxed);
+                if self.source.path().is_some_and(|path| !path.exists()) {
+                    // Probably in the middle of being replaced; the watcher will report it again
+                    // once it's back.
+                    log::debug!("'{}' does not exist, not reloading", self.source);
+                    return;
+                }
+                self.reload();
+            }
+            UserEvent::Thumbnail(_, source, thumbnail) => {
+                let Some(win) = &self.window else { return };
+                if !matches!(self.thumbnails.get(&source), Some(Thumbnail::Pending)) {
+                    // The file was deleted in the meantime, so it no longer counts as pending.
+                    return;
+                }
+                let thumbnail = match thumbnail {
+                    Some(image) => {
+                        let uv =
+                            win.overlay
+                                .write_thumbnail(&win.queue, self.thumbnail_slots, &image);
+                        self.thumbnail_slots += 1;
+                        Thumbnail::Ready {
+                            uv,
+                            size: vec2(image.width() as f32, image.height() as f32),
+                        }
+                    }
+                    None => Thumbnail::Failed,
+                };
+                self.thumbnails.insert(source, thumbnail);
+                win.window.request_redraw();
+            }
+            UserEvent::Preprocessed(_, mapped) => self.preprocessed(mapped),
+            UserEvent::Downloaded(_, source, result) => self.downloaded(source, result),
+            UserEvent::AutoClose(_) => {
+                if self.auto_close.is_some() {
+                    log::info!("auto-close timeout expired -> closing window");
+                    self.closed = true;
+                }
+            }
+        }
+    }
+
+    fn window_event(&mut self, event: WindowEvent) {
+        if let WindowEvent::RedrawRequested = event {
+            // Animations, scrubbing and frame navigation all change the frame before redrawing.
+            self.update_palette();
+            // Same for zooming and resizing, which change the size the frame is displayed at.
+            self.update_downscaled();
+        }
+        let Some(win) = &self.window else { return };
+
+        if matches!(
+            event,
+            WindowEvent::KeyboardInput { .. }
+                | WindowEvent::MouseInput { .. }
+                | WindowEvent::MouseWheel { .. }
+                | WindowEvent::PinchGesture { .. }
+                | WindowEvent::PanGesture { .. }
+        ) && self.auto_close.take().is_some()
+        {
+            // Don't close the window while the user is looking at it.
+            log::debug!("user interaction, cancelling auto-close");
+        }
+
+        match event {
+            WindowEvent::Resized(size) => {
+                // When the window is resized, we force it to have the same aspect ratio as the
+                // image it is displaying (unless `--free-resize` is in effect).
+                log::trace!("resized to {}x{}", size.width, size.height);
                 self.enforce_aspect_ratio(win, size);
+                // More or fewer thumbnails might fit now.
+                self.request_thumbnails();
+            }
+            WindowEvent::RedrawRequested => {
+                if self.title_template.is_some() {
+                    // The title might include the zoom level, which changes all the time.
+                    win.set_title(self.window_title());
+                }
+                self.redraw(win);
+
+                // Keep redrawing until the zoom transition is complete.
+                if let Some(anim) = &self.zoom_animation {
+                    if anim.start.elapsed() < ZOOM_ANIMATION_DURATION {
+                        win.window.request_redraw();
+                    } else {
+                        self.zoom_animation = None;
+                    }
+                }
+                // And for the preprocessing and download indicators, until the image is ready.
+                if win.textures.readback.is_some() || self.download.is_some() {
+                    win.window.request_redraw();
+                }
+                // Same for the toolbar fading out.
+                if let Some(start) = self.toolbar_hover.fade {
+                    if start.elapsed() < TOOLBAR_FADE_DURATION {
+                        win.window.request_redraw();
+                    } else {
+                        self.toolbar_hover.fade = None;
+                    }
+                }
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => match self.cursor_mode {
+                CursorMode::MenuItem(action) if self.context_menu.is_some() => {
+                    self.context_menu = None;
+                    self.cursor_mode = CursorMode::Move;
+                    self.update_cursor();
+                    win.window.request_redraw();
+                    self.perform_command(action);
+                }
+                CursorMode::ToolbarButton(action) => self.perform_command(action),
+                // Clicking anywhere else just closes the context menu.
+                _ if self.context_menu.is_some() => {
+                    self.context_menu = None;
+                    win.window.request_redraw();
+                }
+                CursorMode::Move if self.measuring => {
+                    let Some(pos) = self.cursor_pos else { return };
+                    let uv = self.window_to_uv(win, pos);
+                    match (self.measure_start, self.measure_end) {
+                        (Some(start), None) => {
+                            self.measure_end = Some(uv);
+                            let (distance, angle) = self.measurement(start, uv);
+                            log::info!("measured {distance:.2} px at {angle:.2} degrees");
+                        }
+                        _ => {
+                            self.measure_start = Some(uv);
+                            self.measure_end = None;
+                        }
+                    }
+                    win.window.request_redraw();
+                }
+                CursorMode::Move => {
+                    let Some(pos) = self.cursor_pos else {
+                        if let Err(e) = win.window.drag_window() {
+                            log::error!("failed to initiate window move: {e}");
+                        }
+                        return;
+                    };
+                    let now = Instant::now();
+                    let is_double_click = self.last_click.is_some_and(|(time, last)| {
+                        now - time <= DOUBLE_CLICK_TIME && distance(last, pos) <= DRAG_THRESHOLD
+                    });
+                    if is_double_click {
+                        self.last_click = None;
+                        self.toggle_zoom(pos);
+                    } else {
+                        self.cursor_mode = CursorMode::Press(pos);
+                    }
+                }
+                CursorMode::Resize(dir) => {
+                    if let Err(e) = win.window.drag_resize_window(dir) {
+                        log::error!("failed to initiate window resize: {e}");
+                    }
+                }
+                CursorMode::Timeline => {
+                    self.cursor_mode = CursorMode::Scrub;
+                    self.update_cursor();
+                    if let Some(index) = self
+                        .cursor_pos
+                        .and_then(|pos| self.timeline_frame_at(win, pos))
+                    {
+                        self.frame_index = index;
+                        win.window.request_redraw();
+                    }
+                }
+                CursorMode::AdjustSelection(dir) => {
+                    self.cursor_mode = CursorMode::DragSelection(dir);
+                }
+                CursorMode::Thumbnail(index) => self.open_playlist_entry(index),
+                CursorMode::Press(_)
+                | CursorMode::Select(_)
+                | CursorMode::Scrub
+                | CursorMode::DragSelection(_)
+                | CursorMode::MenuItem(_) => {}
+            },
+            WindowEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                ..
+            } => match self.cursor_mode {
+                CursorMode::Press(pos) => {
+                    // Only a press that didn't turn into a window drag counts as a click.
+                    self.last_click = Some((Instant::now(), pos));
+                    self.cursor_mode = CursorMode::Move;
+                }
+                CursorMode::Scrub => {
+                    self.cursor_mode = CursorMode::Timeline;
+                    self.update_cursor();
+                }
+                CursorMode::DragSelection(dir) => {
+                    self.cursor_mode = CursorMode::AdjustSelection(dir);
+                }
+                _ => {}
+            },
+            WindowEvent::MouseInput {
+                button: MouseButton::Middle,
+                state,
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    if let Some(pos) = self.cursor_pos {
+                        self.cursor_mode = CursorMode::Select(pos);
+                        self.update_cursor();
+                        win.window.request_redraw();
+                    }
+                }
+                ElementState::Released => {
+                    // Commit area selection, compute new aspect ratio, and enforce it.
+                    let (min, max) = self.selection_region(win);
+                    let range = max - min;
+                    if range[0] > 0.0 && range[1] > 0.0 {
+                        // Valid (ish?) range
+                        let size = self.uv_to_window(win, max) - self.uv_to_window(win, min);
+                        self.selection_view = (self.min_uv, self.max_uv);
+                        push_zoom_history(&mut self.zoom_history, self.min_uv, self.max_uv);
+                        self.adjusting_selection = None;
+                        self.zoom_animation = self.zoom_animation_to(min, max);
+                        self.min_uv = min;
+                        self.max_uv = max;
+                        self.committed_selection = Some((min, max));
+                        self.aspect_ratio = self.image_aspect_ratio * (range[0] / range[1]);
+
+                        // Also downsize the window, since this is largely intended to be a cropping tool.
+                        let _ = win
+                            .window
+                            .request_inner_size(PhysicalSize::new(size[0] as u32, size[1] as u32));
+                    }
+
+                    self.cursor_mode = CursorMode::Move;
+                    self.update_cursor();
+                    self.enforce_aspect_ratio(win, win.window.inner_size());
+                    win.window.request_redraw();
+                }
+            },
+            WindowEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Right,
+                ..
+            } => {
+                let Some(pos) = self.cursor_pos else { return };
+                if self.modifiers.shift_key() {
+                    // The OS menu is still needed for things like moving the window between
+                    // desktops.
+                    win.window.show_window_menu(pos);
+                } else {
+                    let size = win.window.inner_size();
+                    let available = vec2(size.width as f32, size.height as f32);
+                    let max_pos = available - self.context_menu_size();
+                    let pos = to_vec2f(pos);
+                    self.context_menu = Some(vec2(
+                        pos[0].min(max_pos[0]).max(0.0),
+                        pos[1].min(max_pos[1]).max(0.0),
+                    ));
+                    win.window.request_redraw();
+                }
+            }
+            WindowEvent::MouseWheel {
+                delta: MouseScrollDelta::PixelDelta(delta),
+                ..
+            } if self.touchpad_gestures && !self.loupe && !self.modifiers.control_key() => {
+                self.pan_by_pixels(vec2(delta.x as f32, delta.y as f32));
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(pos) => (pos.y / PIXELS_PER_SCROLL_LINE) as f32,
+                };
+                if self.loupe {
+                    self.loupe_zoom = (self.loupe_zoom * LOUPE_ZOOM_STEP.powf(lines))
+                        .clamp(LOUPE_MIN_ZOOM, LOUPE_MAX_ZOOM);
+                    log::trace!("loupe zoom: {}", self.loupe_zoom);
+                    win.window.request_redraw();
+                } else if self.modifiers.control_key() {
+                    if self.animated {
+                        self.change_playback_speed(lines.signum() as i32);
+                    }
+                } else {
+                    self.zoom_by(WHEEL_ZOOM_STEP.powf(lines));
+                }
+            }
+            WindowEvent::PinchGesture { delta, phase, .. } => {
+                self.touchpad_gestures = true;
+                if phase == TouchPhase::Started {
+                    // The whole gesture can be undone at once.
+                    push_zoom_history(&mut self.zoom_history, self.min_uv, self.max_uv);
+                }
+                if !delta.is_finite() {
+                    return;
+                }
+                // Zoom around the cursor, which is where the fingers are on the touchpad.
+                let anchor = match self.cursor_pos {
+                    Some(pos) => self.window_to_uv(win, pos),
+                    None => (self.min_uv + self.max_uv) * 0.5,
+                };
+                if let Some((min, max)) = self.zoomed_region(1.0 + delta as f32, anchor) {
+                    self.show_region(min, max);
+                }
+            }
+            WindowEvent::PanGesture { delta, .. } => {
+                self.pan_by_pixels(vec2(delta.x, delta.y));
+            }
+            WindowEvent::HoveredFile(_) => {
+                self.drop_hover = true;
+                win.window.request_redraw();
+            }
+            WindowEvent::HoveredFileCancelled => {
+                self.drop_hover = false;
+                win.window.request_redraw();
+            }
+            WindowEvent::DroppedFile(path) => {
+                self.drop_hover = false;
+                win.window.request_redraw();
+                self.open_in_playlist(Source::Path(path));
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+                if let CursorMode::Select(_) = self.cursor_mode {
+                    win.window.request_redraw();
+                }
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_pos = None;
+                self.toolbar_hover.set(false);
+                win.window.request_redraw();
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                if let (CursorMode::Select(start), Some(old), true) = (
+                    self.cursor_mode,
+                    self.cursor_pos,
+                    self.modifiers.control_key(),
+                ) {
+                    // Holding Ctrl moves the whole selection along with the cursor, instead of
+                    // just its far corner. This can't use Shift, which already constrains the
+                    // aspect ratio; holding both moves a constrained selection.
+                    let start = PhysicalPosition::new(
+                        start.x + position.x - old.x,
+                        start.y + position.y - old.y,
+                    );
+                    self.cursor_mode = CursorMode::Select(start);
+                }
+                self.cursor_pos = Some(position);
+                self.toolbar_hover.set(position.y < TOOLBAR_HOVER_HEIGHT);
+                // Most cursor movement doesn't change anything visible, so avoid redrawing then.
+                self.request_redraw_if_changed(win);
+
+                match self.cursor_mode {
+                    CursorMode::Select(_) => {
+                        // We're already doing something, don't change to move/resize mode.
+                        return;
+                    }
+                    CursorMode::Press(start) => {
+                        if distance(start, position) > DRAG_THRESHOLD {
+                            self.cursor_mode = CursorMode::Move;
+                            self.last_click = None;
+                            if let Err(e) = win.window.drag_window() {
+                                log::error!("failed to initiate window move: {e}");
+                            }
+                        }
+                        return;
+                    }
+                    CursorMode::Scrub => {
+                        if let Some(index) = self.timeline_frame_at(win, position) {
+                            self.frame_index = index;
+                            win.window.request_redraw();
+                        }
+                        return;
+                    }
+                    CursorMode::DragSelection(dir) => {
+                        if let Some(selection) = self.dragged_selection(win, dir, position) {
+                            self.adjusting_selection = Some(selection);
+                            win.window.request_redraw();
+                        }
+                        return;
+                    }
+                    _ => {}
+                }
+
+                if let Some(dir) = self.selection_handle_at(win, position) {
+                    self.cursor_mode = CursorMode::AdjustSelection(dir);
+                    self.update_cursor();
+                    return;
+                }
+
+                if let Some((min, max)) = self.timeline_rect(win) {
+                    let pos = to_vec2f(position);
+                    if pos[0] >= min[0] && pos[0] < max[0] && pos[1] >= min[1] && pos[1] < max[1] {
+                        self.cursor_mode = CursorMode::Timeline;
+                        self.update_cursor();
+                        return;
+                    }
+                }
+
+                if let Some(action) = self.context_menu_item_at(position) {
+                    self.cursor_mode = CursorMode::MenuItem(action);
+                    self.update_cursor();
+                    return;
+                }
+
+                if let Some(action) = self.toolbar_button_at(win, position) {
+                    self.cursor_mode = CursorMode::ToolbarButton(action);
+                    self.update_cursor();
+                    return;
+                }
+
+                if let Some(index) = self.thumbnail_at(win, position) {
+                    self.cursor_mode = CursorMode::Thumbnail(index);
+                    self.update_cursor();
+                    return;
+                }
+
+                let inner_size = win.window.inner_size().cast::<f64>();
+                let (left, top) = (position.x, position.y);
+                let right = inner_size.width - position.x;
+                let bottom = inner_size.height - position.y;
+                // Rounded corners are further inside the window than the edges, by up to this
+                // much along the diagonal, so the corners get a larger area to grab.
+                let radius = f64::from(self.corner_radius_px(win));
+                let corner_inset = radius * (1.0 - FRAC_1_SQRT_2);
+                let near = |dist: f64, along: f64| {
+                    dist <= RESIZE_BORDER_WIDTH
+                        || (along <= radius && dist <= RESIZE_BORDER_WIDTH + corner_inset)
+                };
+                let (n, e, s, w) = (
+                    near(top, left.min(right)),
+                    near(right, top.min(bottom)),
+                    near(bottom, left.min(right)),
+                    near(left, top.min(bottom)),
+                );
+
+                self.cursor_mode = match resize_direction(n, e, s, w) {
+                    Some(dir) => CursorMode::Resize(dir),
+                    None => CursorMode::Move,
+                };
+
+                self.update_cursor();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        physical_key: PhysicalKey::Code(code),
+                        ..
+                    },
+                ..
+            } => {
+                let Some(action) = keymap::action(&self.keymap, code, self.modifiers) else {
+                    return;
+                };
+                log::trace!("{code:?} pressed");
+                self.perform(action);
+            }
+            WindowEvent::CloseRequested => {
+                log::info!("external close request");
+                self.closed = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Carries out a command chosen with the mouse.
+    fn perform_command(&mut self, action: Action) {
+        if action == Action::Exit {
+            // Escape also cancels measurements and adjustments, but the command is labeled as
+            // closing the window.
+            log::info!("{action:?} -> closing window");
+            self.closed = true;
+        } else {
+            self.perform(action);
+        }
+    }
+
+    /// Carries out a keyboard shortcut or context menu command.
+    fn perform(&mut self, action: Action) {
+        let Some(win) = &self.window else { return };
+        match action {
+            Action::Exit if self.context_menu.is_some() => {
+                log::debug!("{action:?} -> closing context menu");
+                self.context_menu = None;
+                if let CursorMode::MenuItem(_) = self.cursor_mode {
+                    self.cursor_mode = CursorMode::Move;
+                    self.update_cursor();
+                }
+                win.window.request_redraw();
+            }
+            Action::Exit | Action::ResetZoom if self.measure_start.is_some() => {
+                log::debug!("{action:?} -> clearing measurement");
+                self.measure_start = None;
+                self.measure_end = None;
+                win.window.request_redraw();
+            }
+            Action::Exit if self.adjusting_selection.is_some() => {
+                log::debug!("{action:?} -> discarding selection adjustments");
+                self.end_adjusting_selection(false);
+            }
+            Action::AdjustSelection | Action::ConfirmSelection
+                if self.adjusting_selection.is_some() =>
+            {
+                log::debug!("{action:?} -> committing adjusted selection");
+                self.end_adjusting_selection(true);
+            }
+            Action::AdjustSelection if self.committed_selection.is_some() => {
+                log::debug!("{action:?} -> adjusting selection");
+                self.begin_adjusting_selection();
+            }
+            Action::Exit => {
+                log::info!("{action:?} -> closing window");
+                self.closed = true;
+            }
+            Action::OpenViewInNewWindow => self.open_view_in_new_window(),
+            Action::SaveAs => self.save_as(),
+            Action::CopyPath => {
+                let Some(path) = absolute_path(&self.source) else {
+                    log::warn!("{action:?} -> the image was read from a file descriptor");
+                    return;
+                };
+                self.copy_to_clipboard(path.display().to_string());
+            }
+            Action::ExportView => match self.export_view(win) {
+                // Printed rather than logged, so that scripts can pick it up.
+                Ok(path) => println!("{}", path.display()),
+                Err(e) => log::error!("failed to export the view: {e:#}"),
+            },
+            Action::OpenRecent => {
+                let current = absolute_path(&self.source);
+                let recent = self
+                    .config
+                    .borrow()
+                    .recent_files
+                    .iter()
+                    .filter(|path| current.as_ref() != Some(path))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if recent.is_empty() {
+                    log::info!("{action:?} -> no other recently opened files");
+                    return;
+                }
+                // Pressing the key repeatedly steps further back in the history.
+                let path = recent[self.recent_index % recent.len()].clone();
+                self.recent_index += 1;
+                log::info!("{action:?} -> opening '{}'", path.display());
+                self.open_in_playlist(Source::from_path(path));
+            }
+            Action::CopyPixelCoordinate => {
+                let pixel = self.cursor_pos.and_then(|pos| self.pixel_at(win, pos));
+                let Some((x, y)) = pixel else {
+                    log::warn!("{action:?} -> the cursor is not over the image");
+                    return;
+                };
+                self.copy_to_clipboard(format!("{x},{y}"));
+            }
+            Action::FitWidth | Action::FitHeight => {
+                let axis = if action == Action::FitWidth { 0 } else { 1 };
+                log::info!("{action:?} -> fitting axis {axis}");
+                self.fit_axis(axis);
+            }
+            Action::ResetZoom => {
+                log::info!("{action:?} -> resetting zoom region");
+                self.reset_or_toggle_region();
+            }
+            Action::ResetAll => {
+                log::info!("{action:?} -> resetting all view settings");
+                self.reset_all();
+            }
+            Action::UndoZoom => match self.zoom_history.pop_back() {
+                Some((min, max)) => {
+                    log::info!("{action:?} -> returning to {min:?}-{max:?}");
+                    self.committed_selection = None;
+                    self.adjusting_selection = None;
+                    self.show_region(min, max);
+                }
+                None => log::info!("{action:?} -> no previous zoom region"),
+            },
+            Action::PreviousImage | Action::NextImage => {
+                let index = if action == Action::NextImage {
+                    self.playlist_index + 1
+                } else {
+                    self.playlist_index.wrapping_sub(1)
+                };
+                if index < self.playlist.len() {
+                    self.open_playlist_entry(index);
+                }
+            }
+            Action::ToggleThumbnails => {
+                self.show_thumbnails = !self.show_thumbnails;
+                log::debug!("{action:?} -> show_thumbnails={}", self.show_thumbnails);
+                if let CursorMode::Thumbnail(_) = self.cursor_mode {
+                    self.cursor_mode = CursorMode::Move;
+                    self.update_cursor();
+                }
+                win.window.request_redraw();
+                self.request_thumbnails();
+            }
+            Action::Trash => {
+                log::info!("{action:?} -> trashing '{}'", self.source);
+                self.trash_current();
+            }
+            Action::Reload => {
+                log::info!("{action:?} -> reloading '{}'", self.source);
+                self.reload();
+            }
+            Action::CropToFrame if self.frame_count > 1 => {
+                log::info!(
+                    "{action:?} -> cropping to the content of frame {}",
+                    self.frame_index
+                );
+                self.crop_to_frame();
+            }
+            Action::ZoomIn => self.zoom_by(ZOOM_STEP),
+            Action::ZoomOut => self.zoom_by(1.0 / ZOOM_STEP),
+            Action::CycleTransparency => {
+                let first = if win.supports_alpha {
+                    TransparencyMode::TrueTransparency
+                } else {
+                    TransparencyMode::LightCheckerboard
+                };
+                if self.show_background_image {
+                    self.show_background_image = false;
+                    self.transparency = first;
+                } else if self.background_image.is_some()
+                    && self.transparency == TransparencyMode::DarkCheckerboard
+                {
+                    // The background image comes after the last mode, when there is one.
+                    self.show_background_image = true;
+                } else {
+                    self.transparency = match self.transparency {
+                        TransparencyMode::TrueTransparency => TransparencyMode::LightCheckerboard,
+                        TransparencyMode::LightCheckerboard => TransparencyMode::DarkCheckerboard,
+                        TransparencyMode::DarkCheckerboard => first,
+                    };
+                }
+                log::debug!(
+                    "{action:?} -> cycling transparency mode to {:?} (background image: {})",
+                    self.transparency,
+                    self.show_background_image,
+                );
+                let mut config = self.config.borrow_mut();
+                config.transparency = self.transparency;
+                config.save();
+                win.window.request_redraw();
+            }
+            Action::CycleGrid => {
+                self.grid = match self.grid {
+                    GridMode::Off => GridMode::Thirds,
+                    GridMode::Thirds => GridMode::GoldenRatio,
+                    GridMode::GoldenRatio => GridMode::Fine,
+                    GridMode::Fine => GridMode::Off,
+                };
+                log::debug!("{action:?} -> cycling grid to {:?}", self.grid);
+                win.window.request_redraw();
+            }
+            Action::TogglePixelGrid => {
+                self.pixel_grid = !self.pixel_grid;
+                log::debug!("{action:?} -> pixel_grid={}", self.pixel_grid);
+                win.window.request_redraw();
+            }
+            Action::CycleCropRatio => {
+                let index = CROP_RATIOS
+                    .iter()
+                    .position(|&r| r == self.crop_ratio)
+                    .map_or(0, |i| (i + 1) % CROP_RATIOS.len());
+                self.crop_ratio = CROP_RATIOS[index];
+                log::info!(
+                    "{action:?} -> selection aspect ratio: {:?}",
+                    self.crop_ratio
+                );
+                self.request_redraw_if_changed(win);
+            }
+            Action::ToggleFilter => {
+                self.filter = match self.filter_override.take().unwrap_or(self.filter) {
+                    FilterMode::Smart => FilterMode::Linear,
+                    FilterMode::Linear => FilterMode::Nearest,
+                    FilterMode::Nearest => FilterMode::Smart,
+                };
+                log::debug!("{action:?} -> cycling filter mode to {:?}", self.filter);
+                let mut config = self.config.borrow_mut();
+                config.filter = self.filter;
+                config.save();
+                win.window.request_redraw();
+            }
+            Action::ToggleMeasuring => {
+                self.measuring = !self.measuring;
+                self.measure_start = None;
+                self.measure_end = None;
+                log::debug!("{action:?} -> measuring={}", self.measuring);
+                self.update_cursor();
+                win.window.request_redraw();
+            }
+            Action::ToggleOutline => {
+                self.outline = !self.outline;
+                log::debug!("{action:?} -> outline={}", self.outline);
+                let mut config = self.config.borrow_mut();
+                config.outline = self.outline;
+                config.save();
+                win.window.request_redraw();
+            }
+            Action::ToggleAlphaView => {
+                self.alpha_view = !self.alpha_view;
+                log::debug!("{action:?} -> alpha_view={}", self.alpha_view);
+                win.window.request_redraw();
+            }
+            Action::ToggleContentBounds => {
+                self.show_content_bounds = !self.show_content_bounds;
+                log::debug!(
+                    "{action:?} -> show_content_bounds={}",
+                    self.show_content_bounds
+                );
+                win.window.request_redraw();
+            }
+            Action::ToggleFreeResize => {
+                self.free_resize = !self.free_resize;
+                log::debug!("{action:?} -> free_resize={}", self.free_resize);
+                if self.free_resize {
+                    ratio::release(&win.window);
+                } else {
+                    self.enforce_aspect_ratio(win, win.window.inner_size());
+                }
+            }
+            Action::ToggleDither => {
+                self.dither = !self.dither;
+                log::debug!("{action:?} -> dither={}", self.dither);
+                let mut config = self.config.borrow_mut();
+                config.dither = self.dither;
+                config.save();
+                win.window.request_redraw();
+            }
+            Action::ToggleCheckerboardZoom => {
+                self.checkerboard_zoom = !self.checkerboard_zoom;
+                log::debug!("{action:?} -> checkerboard_zoom={}", self.checkerboard_zoom);
+                let mut config = self.config.borrow_mut();
+                config.checkerboard_zoom = self.checkerboard_zoom;
+                config.save();
+                win.window.request_redraw();
+            }
+            Action::ToggleLanczos => {
+                self.lanczos = !self.lanczos;
+                log::debug!("{action:?} -> lanczos={}", self.lanczos);
+                let mut config = self.config.borrow_mut();
+                config.lanczos = self.lanczos;
+                config.save();
+                win.window.request_redraw();
+            }
+            Action::PreviousFrame | Action::NextFrame if self.frame_count > 1 => {
+                self.frame_index = if action == Action::NextFrame {
+                    (self.frame_index + 1) % self.frame_count
+                } else {
+                    (self.frame_index + self.frame_count - 1) % self.frame_count
+                };
+                log::debug!("{action:?} -> switching to frame {}", self.frame_index);
+                win.set_title(self.window_title());
+                win.window.request_redraw();
+            }
+            Action::PanLeft | Action::PanRight | Action::PanUp | Action::PanDown => {
+                let dir = match action {
+                    Action::PanLeft => vec2(-1.0, 0.0),
+                    Action::PanRight => vec2(1.0, 0.0),
+                    Action::PanUp => vec2(0.0, -1.0),
+                    _ => vec2(0.0, 1.0),
+                };
+                let range = self.max_uv - self.min_uv;
+                let (min, max) = pan_region(self.min_uv, self.max_uv, dir * range * PAN_STEP);
+                if (min, max) != (self.min_uv, self.max_uv) {
+                    log::trace!("{action:?} -> panning to {min:?}-{max:?}");
+                    self.min_uv = min;
+                    self.max_uv = max;
+                    win.window.request_redraw();
+                }
+            }
+            Action::ToggleLoupe => {
+                self.loupe = !self.loupe;
+                log::debug!("{action:?} -> loupe={}", self.loupe);
+                win.window.request_redraw();
+            }
+            Action::ToggleFrameCounter => {
+                self.show_frame_counter = !self.show_frame_counter;
+                log::debug!(
+                    "{action:?} -> show_frame_counter={}",
+                    self.show_frame_counter
+                );
+                win.window.request_redraw();
+            }
+            Action::ToggleAlphaInfo => {
+                self.show_alpha_info = !self.show_alpha_info;
+                log::debug!("{action:?} -> show_alpha_info={}", self.show_alpha_info);
+                win.window.request_redraw();
+            }
+            Action::TogglePalette => {
+                self.show_palette = !self.show_palette;
+                log::debug!("{action:?} -> show_palette={}", self.show_palette);
+                if self.show_palette {
+                    self.update_palette();
+                }
+                if let Some(win) = &self.window {
+                    win.window.request_redraw();
+                }
+            }
+            Action::ToggleHelp => {
+                self.show_help = !self.show_help;
+                log::debug!("{action:?} -> show_help={}", self.show_help);
+                win.window.request_redraw();
+            }
+            Action::ToggleFileInfo => {
+                self.show_file_info = !self.show_file_info;
+                log::debug!("{action:?} -> show_file_info={}", self.show_file_info);
+                win.window.request_redraw();
+            }
+            Action::ActualSize => {
+                // Set the window size to the exact size of the view (in logical pixels, so that
+                // HiDPI displays don't shrink the image).
+                let width = (self.max_uv[0] - self.min_uv[0]) * self.image_width as f32;
+                let height = width / self.aspect_ratio;
+                let _ = win.window.request_inner_size(LogicalSize::new(
+                    width.round() as u32,
+                    height.round() as u32,
+                ));
+                self.recreate_swapchain(win);
+                win.window.request_redraw();
+            }
+            // Only available in some states (see the guards above).
+            Action::CropToFrame
+            | Action::ConfirmSelection
+            | Action::AdjustSelection
+            | Action::PreviousFrame
+            | Action::NextFrame => {}
+        }
+    }
+
+    /// Replaces the image-specific state with that of `loaded`.
+    fn set_image(&mut self, source: &Source, loaded: Rc<LoadedImage>) {
+        self.source = source.clone();
+        self.title = source.name();
+        self.image_width = loaded.width();
+        self.image_height = loaded.height();
+        self.image_aspect_ratio = self.image_width as f32 / self.image_height as f32;
+        self.premultiplied = self.is_premultiplied(&loaded);
+        self.file_info = FileInfo {
+            format: loaded.format_name(),
+            color_type: loaded.color_type,
+            kb: loaded.kb,
+        };
+        self.frame_index = 0;
+        self.filter_override = None;
+        self.frame_count = loaded.images.len();
+        self.animated = loaded.animated;
+        self.entry_sizes = loaded.entry_sizes.clone();
+        self.frame_delays = loaded.delays.iter().map(|&d| Duration::from(d)).collect();
+        self.play_count = match self.play_count_override {
+            Some(0) => None,
+            Some(count) => Some(count),
+            None => loaded.play_count,
+        };
+        if self.animated {
+            match self.play_count {
+                Some(count) => log::debug!("playing animation {count} time(s)"),
+                None => log::debug!("looping animation forever"),
+            }
+        }
+        self.palettes.clear();
+        self.loaded = loaded;
+        self.update_palette();
+    }
+
+    fn is_premultiplied(&self, loaded: &LoadedImage) -> bool {
+        // SVGs are rasterized to straight alpha, regardless of how the image was authored.
+        self.assume_premultiplied && loaded.format.is_some()
+    }
+
+    /// Replaces the displayed image with the one from `source` and resets the view.
+    ///
+    /// If the file can't be loaded, the current image stays on screen. The same goes for URLs
+    /// until they have been downloaded.
+    fn open(&mut self, source: &Source) {
+        if let Some(loaded) = self.load(source, LoadPurpose::Open) {
+            self.show_opened(source, loaded);
+        }
+    }
+
+    /// Makes `loaded` the displayed image and resets the view.
+    fn show_opened(&mut self, source: &Source, loaded: LoadedImage) {
+        if !self.replace_image(source, loaded) {
+            return;
+        }
+        self.watch_file();
+
+        self.zoom_animation = None;
+        self.measure_start = None;
+        self.measure_end = None;
+        self.cursor_mode = CursorMode::Move;
+        self.update_cursor();
+        self.reset_region();
+        // The regions of the previous image don't mean anything for this one.
+        self.zoom_history.clear();
+    }
+
+    /// Opens `source`, adding it to the playlist right after the displayed image unless it's
+    /// already in there.
+    fn open_in_playlist(&mut self, source: Source) {
+        let index = match self.playlist.iter().position(|s| *s == source) {
+            Some(index) => index,
+            None => {
+                self.playlist.insert(self.playlist_index + 1, source);
+                self.playlist_index + 1
+            }
+        };
+        self.open_playlist_entry(index);
+    }
+
+    /// Opens the image at `index` in the playlist.
+    fn open_playlist_entry(&mut self, index: usize) {
+        // Move on even if the file can't be opened, so that it can be skipped.
+        self.playlist_index = index;
+        let source = self.playlist[index].clone();
+        self.open(&source);
+        if let Some(win) = &self.window {
+            win.window.request_redraw();
+        }
+        self.request_thumbnails();
+    }
+
+    /// Moves the displayed file to the trash after asking for confirmation, and opens the next
+    /// image of the playlist.
+    ///
+    /// Exits if there are no images left.
+    fn trash_current(&mut self) {
+        let Some(path) = self.source.path() else {
+            log::warn!("images opened from a URL or file descriptor can't be deleted");
+            return;
+        };
+        let confirmed = rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_title(concat!(env!("CARGO_PKG_NAME"), " – delete"))
+            .set_description(format!("Move '{}' to the trash?", path.display()))
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show();
+        if confirmed != rfd::MessageDialogResult::Yes {
+            return;
+        }
+        if let Err(e) = trash::delete(path) {
+            log::error!("failed to move '{}' to the trash: {e}", path.display());
+            return;
+        }
+
+        self.thumbnails.remove(&self.source);
+        self.playlist.remove(self.playlist_index);
+        if self.playlist.is_empty() {
+            log::info!("deleted the last image -> closing window");
+            self.closed = true;
+            return;
+        }
+        // Fall back to the previous image if the deleted one was at the end.
+        self.open_playlist_entry(self.playlist_index.min(self.playlist.len() - 1));
+    }
+
+    /// Sends the playlist entries that are visible in the thumbnail strip, but have no thumbnail
+    /// yet, to the thumbnail generator.
+    fn request_thumbnails(&mut self) {
+        let Some(win) = &self.window else { return };
+        let cells = self.thumbnail_cells(win);
+        let window_id = win.window.id();
+        let Some(proxy) = &self.proxy else { return };
+        if cells.is_empty() {
+            return;
+        }
+        let requests = self
+            .thumbnail_requests
+            .get_or_insert_with(|| thumbnails::spawn_generator(proxy.clone(), window_id));
+        for (index, _) in cells {
+            let source = &self.playlist[index];
+            if self.thumbnails.contains_key(source) {
+                continue;
+            }
+            // Once the atlas is full, the remaining entries keep showing a placeholder. Every
+            // pending thumbnail may still take a slot, and the slots of deleted files aren't reused.
+            let pending = self
+                .thumbnails
+                .values()
+                .filter(|thumbnail| matches!(thumbnail, Thumbnail::Pending))
+                .count();
+            if self.thumbnail_slots as usize + pending >= THUMBNAIL_SLOTS as usize {
+                break;
+            }
+            self.thumbnails.insert(source.clone(), Thumbnail::Pending);
+            requests.send(source.clone()).ok();
+        }
+    }
+
+    /// Loads the displayed file again, keeping the current zoom region.
+    fn reload(&mut self) {
+        let source = self.source.clone();
+        if let Some(loaded) = self.load(&source, LoadPurpose::Reload) {
+            self.show_reloaded(&source, loaded);
+        }
+    }
+
+    /// Makes `loaded` the displayed image, keeping the current zoom region.
+    fn show_reloaded(&mut self, source: &Source, loaded: LoadedImage) {
+        if self.replace_image(source, loaded) {
+            log::debug!("reloaded '{source}'");
+            // Recomputes the window aspect ratio in case the image size has changed.
+            self.set_region(self.min_uv, self.max_uv);
+            if let Some(win) = &self.window {
+                win.window.request_redraw();
+            }
+        }
+    }
+
+    /// Loads the image from `source`, or starts downloading it on another thread if it's a URL,
+    /// in which case `purpose` says what to do with it once it's there.
+    ///
+    /// Returns `None` if there is nothing to show yet, logging the error if the file can't be
+    /// loaded.
+    fn load(&mut self, source: &Source, purpose: LoadPurpose) -> Option<LoadedImage> {
+        if let Some(win) = &self.window {
+            if win.textures.readback.is_some() {
+                log::debug!("first image is still being preprocessed, not opening '{source}'");
+                return None;
+            }
+        }
+        if let Source::Url(_) = source {
+            self.download(source.clone(), purpose);
+            return None;
+        }
+        // The image that was being downloaded would otherwise replace this one once it's there.
+        self.download = None;
+        match load::load(source) {
+            Ok(loaded) => Some(loaded),
+            Err(e) => {
+                log::warn!("failed to load '{source}', keeping the current image: {e:#}");
+                None
+            }
+        }
+    }
+
+    /// Downloads the image from `source` in the background, replacing any other download.
+    fn download(&mut self, source: Source, purpose: LoadPurpose) {
+        self.download = Some(Download {
+            source,
+            purpose,
+            start: Instant::now(),
+        });
+        self.start_download();
+    }
+
+    /// Starts the thread for `download`, unless there is no window to send the result to yet.
+    fn start_download(&self) {
+        let (Some(download), Some(win), Some(proxy)) = (&self.download, &self.window, &self.proxy)
+        else {
+            return;
+        };
+        let source = download.source.clone();
+        let window_id = win.window.id();
+        let proxy = proxy.clone();
+        thread::spawn(move || {
+            let result = load::load(&source);
+            proxy
+                .send_event(UserEvent::Downloaded(window_id, source, result))
+                .ok();
+        });
+        win.window.request_redraw();
+    }
+
+    /// Shows the image downloaded from `source`, unless something else has been opened since.
+    fn downloaded(&mut self, source: Source, result: anyhow::Result<LoadedImage>) {
+        let Some(download) = self.download.take_if(|d| d.source == source) else {
+            log::debug!("discarding '{source}', which is no longer needed");
+            return;
+        };
+        // Removes the indicator, if nothing else does.
+        if let Some(win) = &self.window {
+            win.window.request_redraw();
+        }
+        let loaded = match result {
+            Ok(loaded) => loaded,
+            // The window has nothing to show without it.
+            Err(e) if download.purpose == LoadPurpose::Startup => {
+                exit_with_error(format!("{e:#}"), exit_code(&e))
+            }
+            Err(e) => {
+                log::warn!("failed to load '{source}', keeping the current image: {e:#}");
+                return;
+            }
+        };
+        match download.purpose {
+            LoadPurpose::Startup => self.show_first_image(&source, loaded),
+            LoadPurpose::Open => self.show_opened(&source, loaded),
+            LoadPurpose::Reload => self.show_reloaded(&source, loaded),
+        }
+    }
+
+    /// Sets the image the window opens with. Without `loaded`, the window opens with a
+    /// transparent placeholder, and downloads the image from `source` once it's there.
+    fn set_first_image(&mut self, source: &Source, loaded: Option<LoadedImage>) {
+        let loaded = loaded.unwrap_or_else(|| {
+            self.download(source.clone(), LoadPurpose::Startup);
+            let (width, height) = DOWNLOAD_PLACEHOLDER_SIZE;
+            LoadedImage {
+                images: vec![DynamicImage::ImageRgba8(RgbaImage::new(width, height))],
+                ..LoadedImage::default()
+            }
+        });
+        self.set_image(source, Rc::new(loaded));
+    }
+
+    /// Replaces the placeholder with the downloaded first image, and resizes the window as if the
+    /// image had been there from the start.
+    fn show_first_image(&mut self, source: &Source, loaded: LoadedImage) {
+        if let Some(region) = self.pending_region.take() {
+            match region_uv(region, loaded.width(), loaded.height()) {
+                Ok(region) => self.initial_region = Some(region),
+                Err(e) => exit_with_error(format!("{e:#}"), EXIT_ERROR),
+            }
+        }
+        if !self.replace_image(source, loaded) {
+            exit_with_error(format!("Failed to display '{source}'"), EXIT_ERROR);
+        }
+        self.filter_override = self.tiny_filter_override();
+        self.show_initial_region();
+        if let Some(win) = &self.window {
+            let size = self.window_size(win.window.current_monitor().as_ref());
+            let _ = win.window.request_inner_size(size);
+        }
+    }
+
+    /// Makes `loaded` the displayed image, without touching the view.
+    ///
+    /// Returns `false` and logs the error if it can't be displayed.
+    fn replace_image(&mut self, source: &Source, loaded: LoadedImage) -> bool {
+        let Some(win) = &self.window else {
+            return false;
+        };
+        if win.textures.readback.is_some() {
+            log::debug!("first image is still being preprocessed, not opening '{source}'");
+            return false;
+        }
+        let textures = match upload_images(
+            &win.device,
+            &win.queue,
+            &win.uploader,
+            &win.display_settings,
+            &loaded.images,
+            self.is_premultiplied(&loaded),
+        ) {
+            Ok(mut textures) => {
+                textures.wait_for_preprocessing(&win.device);
+                textures
             }
-            WindowEvent::RedrawRequested => {
-                self.redraw(win);
+            Err(e) => {
+                log::warn!("failed to load '{source}', keeping the current image: {e:#}");
+                return false;
             }
-            WindowEvent::MouseInput {
-                state: ElementState::Pressed,
-                button: MouseButton::Left,
-                ..
-            } => match self.cursor_mode {
-                CursorMode::Move => {
-                    if let Err(e) = win.window.drag_window() {
-                        log::error!("failed to initiate window move: {e}");
-                    }
+        };
+
+        self.set_image(source, Rc::new(loaded));
+        if let Some(win) = &mut self.window {
+            win.textures = textures;
+        }
+        self.update_present_mode();
+        if let Some(win) = &self.window {
+            win.set_title(self.window_title());
+        }
+        self.start_animation();
+        true
+    }
+
+    /// Starts a thread that closes the window once the `--auto-close` timeout expires.
+    fn start_auto_close(&self) {
+        let (Some(timeout), Some(proxy), Some(win)) = (self.auto_close, &self.proxy, &self.window)
+        else {
+            return;
+        };
+        let proxy = proxy.clone();
+        let window_id = win.window.id();
+        thread::spawn(move || {
+            thread::sleep(timeout);
+            proxy.send_event(UserEvent::AutoClose(window_id)).ok();
+        });
+    }
+
+    /// Starts watching the displayed file for changes if `--watch` was passed, replacing the
+    /// watcher of the previous file.
+    fn watch_file(&mut self) {
+        let (true, Some(proxy), Some(win)) = (self.watch, &self.proxy, &self.window) else {
+            return;
+        };
+        let Some(path) = self.source.path().map(Path::to_path_buf) else {
+            log::warn!("`--watch` has no effect on images opened from a URL or file descriptor");
+            self.watcher = None;
+            return;
+        };
+        let proxy = proxy.clone();
+        let window_id = win.window.id();
+        let pending = self.reload_pending.clone();
+        let on_change = move || {
+            if !pending.swap(true, Ordering::Relaxed) {
+                proxy.send_event(UserEvent::FileChanged(window_id)).ok();
+            }
+        };
+        self.watcher = match watch::watch(&path, on_change) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                log::error!("failed to watch '{}': {e}", path.display());
+                None
+            }
+        };
+    }
+
+    /// Tells the animation thread to exit, if there is one.
+    fn stop_animation(&mut self) {
+        if let Some(stop) = self.animation_stop.take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Starts the thread that plays back the animation, replacing that of the previous image.
+    fn start_animation(&mut self) {
+        self.stop_animation();
+        let (Some(win), Some(proxy)) = (&self.window, &self.proxy) else {
+            return;
+        };
+        if !self.animated {
+            return;
+        }
+        let window = win.window.clone();
+        let window_id = window.id();
+        let proxy = proxy.clone();
+        let delays = self.frame_delays.clone();
+        let playback_speed = self.playback_speed.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        self.animation_stop = Some(stop.clone());
+
+        // Number of frames to advance by until the last frame of the last loop is reached.
+        let mut remaining = self
+            .play_count
+            .map(|count| count as usize * delays.len() - 1);
+        // Never advance the animation more often than the display refreshes.
+        let refresh_interval = window
+            .current_monitor()
+            .and_then(|m| m.refresh_rate_millihertz())
+            .map_or(DEFAULT_REFRESH_INTERVAL, |mhz| {
+                Duration::from_secs_f64(1000.0 / f64::from(mhz))
+            });
+
+        thread::spawn(move || {
+            log::debug!("starting animation thread (refresh interval: {refresh_interval:?})");
+            let mut durations = delays.iter().copied().cycle().map(|delay| {
+                let steps = playback_speed.load(Ordering::Relaxed);
+                delay.div_f32(PLAYBACK_SPEED_STEP.powi(steps))
+            });
+            // Time at which the current frame's delay expires.
+            let mut frame_end = Instant::now() + durations.next().unwrap();
+            loop {
+                thread::sleep(frame_end.saturating_duration_since(Instant::now()));
+                if stop.load(Ordering::Relaxed) {
+                    log::debug!("image was replaced, stopping animation thread");
+                    break;
                 }
-                CursorMode::Resize(dir) => {
-                    if let Err(e) = win.window.drag_resize_window(dir) {
-                        log::error!("failed to initiate window resize: {e}");
+
+                // Skip every frame that would end before the display refreshes again
+                // (because its delay is too short, or because we're lagging behind).
+                let horizon = Instant::now() + refresh_interval;
+                let mut frames = 0;
+                while frame_end <= horizon {
+                    frame_end += durations.next().unwrap();
+                    frames += 1;
+                    if frames == delays.len() {
+                        // Looped through the whole animation; this happens when all
+                        // delays are 0, or when waking up from system sleep.
+                        frame_end = frame_end.max(horizon);
+                        break;
                     }
                 }
-                CursorMode::Select(_) => {}
-            },
-            WindowEvent::MouseInput {
-                button: MouseButton::Middle,
-                state,
-                ..
-            } => match state {
-                ElementState::Pressed => {
-                    if let Some(pos) = self.cursor_pos {
-                        self.cursor_mode = CursorMode::Select(pos);
-                        self.update_cursor();
-                        win.window.request_redraw();
-                    }
+
+                if let Some(remaining) = &mut remaining {
+                    frames = frames.min(*remaining);
+                    *remaining -= frames;
                 }
-                ElementState::Released => {
-                    // Commit area selection, compute new aspect ratio, and enforce it.
-                    let (min, max) = self.selection_region(win);
-                    let range = [max[0] - min[0], max[1] - min[1]];
-                    if range[0] > 0.0 && range[1] > 0.0 {
-                        // Valid (ish?) range
-                        self.min_uv = min;
-                        self.max_uv = max;
-                        self.aspect_ratio = self.image_aspect_ratio * (range[0] / range[1]);
 
-                        // Also downsize the window, since this is largely intended to be a cropping tool.
-                        if let (CursorMode::Select(start), Some(end)) =
-                            (self.cursor_mode, self.cursor_pos)
-                        {
-                            // sort corners
-                            let min = [f64::min(start.x, end.x), f64::min(start.y, end.y)];
-                            let max = [f64::max(start.x, end.x), f64::max(start.y, end.y)];
-                            let size = [max[0] - min[0], max[1] - min[1]];
-                            let _ = win.window.request_inner_size(PhysicalSize::new(
-                                size[0] as u32,
-                                size[1] as u32,
-                            ));
-                        }
-                    }
+                let event = if remaining == Some(0) {
+                    UserEvent::AnimationFinished(window_id)
+                } else {
+                    UserEvent::Advance(window_id, frames)
+                };
+                let Ok(()) = proxy.send_event(event) else {
+                    break;
+                };
+                window.request_redraw();
 
-                    self.cursor_mode = CursorMode::Move;
-                    self.update_cursor();
-                    self.enforce_aspect_ratio(win, win.window.inner_size());
-                    win.window.request_redraw();
-                }
-            },
-            WindowEvent::MouseInput {
-                state: ElementState::Released,
-                button: MouseButton::Right,
-                ..
-            } => {
-                if let Some(pos) = self.cursor_pos {
-                    win.window.show_window_menu(pos);
+                if remaining == Some(0) {
+                    log::debug!("animation finished, stopping animation thread");
+                    break;
                 }
             }
-            WindowEvent::CursorLeft { .. } => {
-                self.cursor_pos = None;
-                win.window.request_redraw();
+        });
+    }
+
+    fn update_cursor(&self) {
+        let Some(win) = &self.window else { return };
+        let cursor = match self.cursor_mode {
+            CursorMode::Move if self.measuring => CursorIcon::Crosshair,
+            CursorMode::Move | CursorMode::Press(_) => CursorIcon::Grab,
+            CursorMode::Resize(dir) => CursorIcon::from(dir),
+            CursorMode::Select(_) => CursorIcon::Crosshair,
+            CursorMode::Timeline
+            | CursorMode::Thumbnail(_)
+            | CursorMode::MenuItem(_)
+            | CursorMode::ToolbarButton(_) => CursorIcon::Pointer,
+            CursorMode::Scrub => CursorIcon::ColResize,
+            CursorMode::AdjustSelection(dir) | CursorMode::DragSelection(dir) => {
+                CursorIcon::from(dir)
             }
-            WindowEvent::CursorMoved { position, .. } => {
-                self.cursor_pos = Some(position);
-                win.window.request_redraw();
+        };
+        win.window.set_cursor(cursor);
+    }
 
-                if let CursorMode::Select(_) = self.cursor_mode {
-                    // We're already doing something, don't change to move/resize mode.
-                    return;
-                }
+    fn enforce_aspect_ratio(&self, win: &Win, size: PhysicalSize<u32>) {
+        if self.free_resize {
+            // The image is letterboxed into whatever size the window has.
+            self.recreate_swapchain(win);
+            win.window.request_redraw();
+            return;
+        }
 
-                let inner_size = win.window.inner_size().cast::<f64>();
-                let (n, e, s, w) = (
-                    position.y <= RESIZE_BORDER_WIDTH,
-                    position.x >= inner_size.width - RESIZE_BORDER_WIDTH,
-                    position.y >= inner_size.height - RESIZE_BORDER_WIDTH,
-                    position.x <= RESIZE_BORDER_WIDTH,
-                );
+        // We use the `CursorMode` as a hint – if we're resizing vertically, respect the requested
+        // height, if we're resizing horizontally, respect the requested width.
+        let is_vertical = matches!(
+            self.cursor_mode,
+            CursorMode::Resize(ResizeDirection::North | ResizeDirection::South)
+        );
+        let min = (f64::from(sizing::MIN_WINDOW_SIZE) * win.window.scale_factor()).round() as u32;
+        let max =
+            win.window
+                .current_monitor()
+                .map_or(PhysicalSize::new(u32::MAX, u32::MAX), |monitor| {
+                    let margin =
+                        (f64::from(MONITOR_MARGIN) * monitor.scale_factor()).round() as u32;
+                    PhysicalSize::new(
+                        monitor.size().width.saturating_sub(margin).max(min),
+                        monitor.size().height.saturating_sub(margin).max(min),
+                    )
+                });
+        let fitted_size =
+            sizing::snap_to_aspect_ratio(size, self.aspect_ratio, is_vertical, min, max);
+        log::trace!(
+            "enforce_aspect_ratio: requested {}x{}, fitted size {}x{} (vertical={is_vertical})",
+            size.width,
+            size.height,
+            fitted_size.width,
+            fitted_size.height,
+        );
 
-                self.cursor_mode = match (n, e, s, w) {
-                    (false, false, false, false) => CursorMode::Move,
-                    (true, false, false, false) => CursorMode::Resize(ResizeDirection::North),
-                    (true, true, false, false) => CursorMode::Resize(ResizeDirection::NorthEast),
-                    (true, false, false, true) => CursorMode::Resize(ResizeDirection::NorthWest),
-                    (false, false, true, false) => CursorMode::Resize(ResizeDirection::South),
-                    (false, true, true, false) => CursorMode::Resize(ResizeDirection::SouthEast),
-                    (false, false, true, true) => CursorMode::Resize(ResizeDirection::SouthWest),
-                    (false, true, false, false) => CursorMode::Resize(ResizeDirection::East),
-                    (false, false, false, true) => CursorMode::Resize(ResizeDirection::West),
-                    // Ambiguous cases. These can happen when the window is so small that the resize
-                    // borders overlap. Result is mostly arbitrary.
-                    (false, true, true, true) => CursorMode::Resize(ResizeDirection::South),
-                    (false, true, false, true) => CursorMode::Resize(ResizeDirection::West),
-                    (true, false, true, _) => CursorMode::Resize(ResizeDirection::South),
-                    (true, true, true, _) => CursorMode::Resize(ResizeDirection::SouthEast),
-                    (true, true, false, true) => CursorMode::Resize(ResizeDirection::NorthEast),
-                };
+        if fitted_size.width == max.width || fitted_size.height == max.height {
+            // The window system would undo the letterboxing otherwise.
+            ratio::release(&win.window);
+        } else {
+            ratio::enforce(&win.window, self.aspect_ratio, size);
+        }
 
-                self.update_cursor();
+        if fitted_size != size {
+            let _ = win.window.request_inner_size(fitted_size);
+        }
+        self.recreate_swapchain(win);
+        win.window.request_redraw();
+    }
+
+    fn reset_region(&mut self) {
+        let Some(win) = &self.window else { return };
+        self.committed_selection = None;
+        self.adjusting_selection = None;
+        let (min, max) = self.fitted_region(win);
+        self.set_region(min, max);
+    }
+
+    /// Returns every display mode to the value this window started with, hides the optional
+    /// overlays, and resets the zoom region and its history.
+    fn reset_all(&mut self) {
+        let Some(win) = &self.window else { return };
+        // Only this window is affected; the configuration file keeps whatever was toggled.
+        let startup = self.startup;
+        self.transparency = startup.transparency;
+        if !win.supports_alpha && self.transparency == TransparencyMode::TrueTransparency {
+            self.transparency = TransparencyMode::LightCheckerboard;
+        }
+        self.filter = startup.filter;
+        self.filter_override = self.tiny_filter_override();
+        self.outline = startup.outline;
+        self.dither = startup.dither;
+        self.lanczos = startup.lanczos;
+        self.checkerboard_zoom = startup.checkerboard_zoom;
+        self.crop_ratio = startup.crop_ratio;
+        if self.playback_speed.swap(0, Ordering::Relaxed) != 0 {
+            log::info!("playback speed: 1.00x");
+        }
+
+        self.grid = GridMode::Off;
+        self.pixel_grid = false;
+        self.alpha_view = false;
+        self.loupe = false;
+        self.loupe_zoom = LOUPE_DEFAULT_ZOOM;
+        self.measuring = false;
+        self.measure_start = None;
+        self.measure_end = None;
+        self.show_content_bounds = false;
+        self.show_frame_counter = false;
+        self.show_alpha_info = false;
+        self.show_file_info = false;
+        self.show_palette = false;
+        self.show_background_image = self.background_image.is_some();
+
+        self.reset_region();
+        self.zoom_history.clear();
+        self.update_cursor();
+        if let Some(win) = &self.window {
+            win.window.request_redraw();
+        }
+    }
+
+    /// Tiny images open enlarged (see `sizing::initial_size`), and should stay crisp, so they use
+    /// nearest-neighbor filtering until the filter mode is changed.
+    fn tiny_filter_override(&self) -> Option<FilterMode> {
+        let size = LogicalSize::new(self.image_width, self.image_height);
+        sizing::is_tiny(size).then(|| {
+            log::debug!("tiny image; using nearest-neighbor filtering for it");
+            FilterMode::Nearest
+        })
+    }
+
+    /// Resets the zoom region, or, if it's already reset, switches between showing the content
+    /// bounds and the whole image.
+    fn reset_or_toggle_region(&mut self) {
+        let Some(win) = &self.window else { return };
+        let full = (vec2(0.0, 0.0), vec2(1.0, 1.0));
+        let content = self.content_region(&win.textures.image_info);
+        let fitted = self.fitted_region(win);
+        if (self.min_uv, self.max_uv) != fitted || content == full {
+            self.reset_region();
+            return;
+        }
+
+        let (min, max) = if fitted == full { content } else { full };
+        log::debug!("already showing the default view -> switching to {min:?}-{max:?}");
+        self.committed_selection = None;
+        self.adjusting_selection = None;
+        self.set_region(min, max);
+    }
+
+    /// Shows the whole image along `axis` (0 = width, 1 = height), keeping the window's aspect ratio
+    /// and the center of the view along the other axis.
+    ///
+    /// If the image is too long to fit along the other axis, the rest of it can be panned to.
+    fn fit_axis(&mut self, axis: usize) {
+        let Some(win) = &self.window else { return };
+        let size = win.window.inner_size();
+        let window_aspect_ratio = size.width as f32 / size.height as f32;
+        let other = 1 - axis;
+        let mut range = vec2(1.0, 1.0);
+        range[other] = if axis == 0 {
+            self.image_aspect_ratio / window_aspect_ratio
+        } else {
+            window_aspect_ratio / self.image_aspect_ratio
+        }
+        .min(1.0);
+
+        let center = (self.min_uv + self.max_uv) * 0.5;
+        let mut min = vec2(0.0, 0.0);
+        min[other] = (center[other] - range[other] * 0.5).clamp(0.0, 1.0 - range[other]);
+        self.committed_selection = None;
+        self.adjusting_selection = None;
+        self.set_region(min, min + range);
+    }
+
+    /// Zooms to the content of the current animation frame only.
+    fn crop_to_frame(&mut self) {
+        let Some(win) = &self.window else { return };
+        self.committed_selection = None;
+        self.adjusting_selection = None;
+        let (min, max) = self.content_region(&win.textures.frame_infos[self.frame_index]);
+        self.set_region(min, max);
+    }
+
+    /// Returns the UV region that [`Viewer::reset_region`] displays: the bounds of the image content
+    /// across all frames, or the whole image if autocropping is disabled.
+    fn fitted_region(&self, win: &Win) -> (Vec2f, Vec2f) {
+        if self.autocrop {
+            self.content_region(&win.textures.image_info)
+        } else {
+            (vec2(0.0, 0.0), vec2(1.0, 1.0))
+        }
+    }
+
+    /// Returns the content bounds described by `info` as a UV region.
+    fn content_region(&self, info: &ImageInfo) -> (Vec2f, Vec2f) {
+        if info.top == u32::MAX {
+            // Somehow not a single non-transparent pixel in the image? good luck finding the window, fucker
+            (vec2(0.0, 0.0), vec2(1.0, 1.0))
+        } else {
+            let min = vec2(
+                info.left as f32 / self.image_width as f32,
+                info.top as f32 / self.image_height as f32,
+            );
+            let max = vec2(
+                (info.right + 1) as f32 / self.image_width as f32,
+                (info.bottom + 1) as f32 / self.image_height as f32,
+            );
+            (min, max)
+        }
+    }
+
+    /// Speeds up animations by `steps` [`PLAYBACK_SPEED_STEP`]s (or slows them down, if negative).
+    fn change_playback_speed(&mut self, steps: i32) {
+        let old = self.playback_speed.load(Ordering::Relaxed);
+        let new = (old + steps).clamp(-PLAYBACK_SPEED_MAX_STEPS, PLAYBACK_SPEED_MAX_STEPS);
+        self.playback_speed.store(new, Ordering::Relaxed);
+        log::info!("playback speed: {:.2}x", PLAYBACK_SPEED_STEP.powi(new));
+        if let Some(win) = &self.window {
+            // The frame counter shows the speed.
+            win.window.request_redraw();
+        }
+    }
+
+    /// Zooms in by `factor` (or out, if it's less than 1), keeping the center of the view in place.
+    fn zoom_by(&mut self, factor: f32) {
+        let center = (self.min_uv + self.max_uv) * 0.5;
+        if let Some((min, max)) = self.zoomed_region(factor, center) {
+            self.set_region(min, max);
+        }
+    }
+
+    /// Returns the region that zooming in by `factor` results in, keeping the UV coordinate
+    /// `anchor` at the same place in the window, or `None` if the region doesn't change.
+    fn zoomed_region(&self, factor: f32, anchor: Vec2f) -> Option<(Vec2f, Vec2f)> {
+        let win = self.window.as_ref()?;
+        let image_size = vec2(self.image_width as f32, self.image_height as f32);
+        let range = self.max_uv - self.min_uv;
+        // Don't zoom out past the full image, or in past a single image pixel.
+        let min_range = vec2(1.0, 1.0) / image_size;
+        let factor = factor
+            .min(range[0] / min_range[0])
+            .min(range[1] / min_range[1]);
+        let mut new_range = (range / factor).map(|r| r.min(1.0));
+
+        let width = win
+            .window
+            .inner_size()
+            .to_logical::<f32>(win.window.scale_factor())
+            .width;
+        if self.snap_zoom {
+            // At whole multiples of 100%, every image pixel covers the same number of screen
+            // pixels, which matters for pixel art.
+            let scale = width / (new_range[0] * image_size[0]);
+            let snapped = scale.round();
+            if snapped >= 1.0 && (scale / snapped - 1.0).abs() < ZOOM_SNAP_TOLERANCE {
+                new_range = (new_range * (scale / snapped)).map(|r| r.min(1.0));
             }
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        state: ElementState::Pressed,
-                        physical_key: PhysicalKey::Code(code),
-                        ..
-                    },
-                ..
-            } => match code {
-                KeyCode::Escape => {
-                    log::info!("escape pressed -> exiting");
-                    event_loop.exit();
-                }
-                KeyCode::Backspace => {
-                    log::info!("backspace pressed -> resetting zoom region");
-                    self.reset_region();
-                }
-                KeyCode::KeyT => {
-                    self.transparency = match self.transparency {
-                        TransparencyMode::TrueTransparency => TransparencyMode::LightCheckerboard,
-                        TransparencyMode::LightCheckerboard => TransparencyMode::DarkCheckerboard,
-                        TransparencyMode::DarkCheckerboard => {
-                            if win.supports_alpha {
-                                TransparencyMode::TrueTransparency
-                            } else {
-                                TransparencyMode::LightCheckerboard
-                            }
-                        }
-                    };
-                    log::debug!("T -> cycling transparency mode to {:?}", self.transparency);
-                    win.window.request_redraw();
-                }
-                KeyCode::KeyL => {
-                    self.filter = match self.filter {
-                        FilterMode::Smart => FilterMode::Linear,
-                        FilterMode::Linear => FilterMode::Smart,
-                    };
-                    log::debug!("L -> cycling filter mode to {:?}", self.filter);
-                    win.window.request_redraw();
-                }
-                KeyCode::Digit1 => {
-                    // Set the window size to the exact size of the view.
-                    let width = (self.max_uv[0] - self.min_uv[0]) * self.image_width as f32;
-                    let height = width / self.aspect_ratio;
-                    let _ = win.window.request_inner_size(PhysicalSize::new(
-                        width.round() as u32,
-                        height.round() as u32,
-                    ));
-                    self.recreate_swapchain(win);
-                    win.window.request_redraw();
+        }
+
+        // Position of the anchor relative to the region, which stays the same.
+        let rel = (anchor - self.min_uv) / range;
+        let mut min = anchor - rel * new_range;
+        for axis in 0..2 {
+            min[axis] = min[axis].clamp(0.0, 1.0 - new_range[axis]);
+        }
+        let max = min + new_range;
+        if (min, max) == (self.min_uv, self.max_uv) {
+            return None;
+        }
+
+        let zoom = width / (new_range[0] * image_size[0]) * 100.0;
+        log::info!("zooming by {factor} -> {zoom:.0}%");
+        Some((min, max))
+    }
+
+    /// Moves the image along with a touchpad gesture that moved by `delta` window pixels.
+    fn pan_by_pixels(&mut self, delta: Vec2f) {
+        let Some(win) = &self.window else { return };
+        let (fb_min, fb_max) = self.fb_coord_range(win);
+        let range = self.max_uv - self.min_uv;
+        // Moving the fingers down moves the image down, revealing more of its top.
+        let delta = delta / (fb_max - fb_min) * range * -1.0;
+        let (min, max) = pan_region(self.min_uv, self.max_uv, delta);
+        if (min, max) != (self.min_uv, self.max_uv) {
+            self.show_region(min, max);
+        }
+    }
+
+    /// Toggles between the fitted view and a 100% view centered on the window position `pos`.
+    fn toggle_zoom(&mut self, pos: PhysicalPosition<f64>) {
+        let Some(win) = &self.window else { return };
+        if (self.min_uv, self.max_uv) != self.fitted_region(win) {
+            log::debug!("double click -> fitting view");
+            self.reset_region();
+            return;
+        }
+
+        // Show as much of the image at 100% as fits in the window (in logical pixels, like `1`).
+        let size = win
+            .window
+            .inner_size()
+            .to_logical::<f32>(win.window.scale_factor());
+        let image_size = vec2(self.image_width as f32, self.image_height as f32);
+        let range = (vec2(size.width, size.height) / image_size).map(|r| r.min(1.0));
+        let center = self.window_to_uv(win, pos);
+        let mut min = center - range * 0.5;
+        for axis in 0..2 {
+            min[axis] = min[axis].clamp(0.0, 1.0 - range[axis]);
+        }
+        let max = min + range;
+        log::debug!("double click -> zooming to 100% at {center:?}");
+
+        let window = win.window.clone();
+        self.committed_selection = None;
+        self.adjusting_selection = None;
+        self.set_region(min, max);
+        let size = range * image_size;
+        let _ = window.request_inner_size(LogicalSize::new(
+            size[0].round() as u32,
+            size[1].round() as u32,
+        ));
+    }
+
+    /// Returns the last committed selection as `(x, y, width, height)` in image pixels, or the full
+    /// image bounds if nothing was selected.
+    fn selection_pixels(&self) -> (u32, u32, u32, u32) {
+        match self.committed_selection {
+            Some((min, max)) => self.region_pixels(min, max),
+            None => (0, 0, self.image_width, self.image_height),
+        }
+    }
+
+    /// Converts the UV region `min`-`max` to `(x, y, width, height)` in image pixels.
+    fn region_pixels(&self, min: Vec2f, max: Vec2f) -> (u32, u32, u32, u32) {
+        let size = vec2(self.image_width as f32, self.image_height as f32);
+        let min = (min * size).map(|v| v.round() as u32);
+        let max = (max * size).map(|v| v.round() as u32);
+        (min[0], min[1], max[0] - min[0], max[1] - min[1])
+    }
+
+    /// Puts `text` on the clipboard.
+    fn copy_to_clipboard(&mut self, text: String) {
+        let clipboard = match &mut self.clipboard {
+            Some(clipboard) => clipboard,
+            None => match arboard::Clipboard::new() {
+                Ok(clipboard) => self.clipboard.insert(clipboard),
+                Err(e) => {
+                    log::error!("failed to access the clipboard: {e}");
+                    return;
                 }
-                _ => {}
             },
-            WindowEvent::CloseRequested => {
-                log::info!("external close request");
-                event_loop.exit();
+        };
+        match clipboard.set_text(&text) {
+            Ok(()) => log::info!("copied '{text}' to the clipboard"),
+            Err(e) => log::error!("failed to copy '{text}' to the clipboard: {e}"),
+        }
+    }
+
+    /// Returns the coordinates of the image pixel at the window position `pos`, or `None` if
+    /// `pos` is outside of the image.
+    fn pixel_at(&self, win: &Win, pos: PhysicalPosition<f64>) -> Option<(u32, u32)> {
+        let uv = self.window_to_uv(win, pos);
+        if !(0.0..1.0).contains(&uv[0]) || !(0.0..1.0).contains(&uv[1]) {
+            return None;
+        }
+        let x = (uv[0] * self.image_width as f32) as u32;
+        let y = (uv[1] * self.image_height as f32) as u32;
+        Some((x.min(self.image_width - 1), y.min(self.image_height - 1)))
+    }
+
+    /// Asks for a file name and saves the current frame there, in the format that matches the
+    /// file extension.
+    fn save_as(&self) {
+        let stem = Path::new(&self.title)
+            .file_stem()
+            .map_or("image".into(), |stem| stem.to_string_lossy());
+        let mut dialog = rfd::FileDialog::new()
+            .set_title(concat!(env!("CARGO_PKG_NAME"), " – save as"))
+            .set_file_name(format!("{stem}.png"));
+        if let Some(dir) = self.source.path().and_then(Path::parent) {
+            dialog = dialog.set_directory(dir);
+        }
+        let Some(target) = dialog.save_file() else {
+            return;
+        };
+
+        let frame = self.frame_index.min(self.loaded.images.len() - 1);
+        match save_image(&self.loaded.images[frame], &target) {
+            Ok(()) => log::info!("saved '{}' as '{}'", self.source, target.display()),
+            Err(e) => {
+                log::error!("{e:#}");
+                show_error(&format!("{e:#}"));
             }
-            _ => {}
         }
     }
-}
 
-impl App {
-    fn update_cursor(&self) {
-        let Some(win) = &self.window else { return };
-        let cursor = match self.cursor_mode {
-            CursorMode::Move => CursorIcon::Grab,
-            CursorMode::Resize(dir) => CursorIcon::from(dir),
-            CursorMode::Select(_) => CursorIcon::Crosshair,
-        };
-        win.window.set_cursor(cursor);
+    /// Computes the colors shown by [`Action::TogglePalette`] for the current frame, unless they
+    /// are already known or not shown.
+    fn update_palette(&mut self) {
+        if !self.show_palette || self.palettes.contains_key(&self.frame_index) {
+            return;
+        }
+        let frame = self.frame_index.min(self.loaded.images.len() - 1);
+        let palette = palette::analyze(&self.loaded.images[frame], self.premultiplied);
+        self.palettes.insert(self.frame_index, palette);
     }
 
-    fn enforce_aspect_ratio(&self, win: &Win, size: PhysicalSize<u32>) {
-        // We use the `CursorMode` as a hint – if we're resizing vertically, respect the requested
-        // height, if we're resizing horizontally, respect the requested width.
-        let is_vertical = matches!(
-            self.cursor_mode,
-            CursorMode::Resize(ResizeDirection::North | ResizeDirection::South)
+    /// Returns the size the current frame has to be downscaled to for [`Action::ToggleLanczos`], or
+    /// `None` if it isn't displayed smaller than it is.
+    fn downscaled_size(&self, win: &Win) -> Option<(u32, u32)> {
+        let (min_uv, max_uv) = self.displayed_region();
+        let range = max_uv - min_uv;
+        let aspect_ratio = self.image_aspect_ratio * (range[0] / range[1]);
+        let (min_fb, max_fb) = sizing::fit_aspect_ratio(win.window.inner_size(), aspect_ratio);
+        // Like `tex_per_px` in the shader.
+        let texture = &win.textures.texture;
+        let tex_per_px = f32::max(
+            range[0] * texture.width() as f32 / (max_fb[0] - min_fb[0]),
+            range[1] * texture.height() as f32 / (max_fb[1] - min_fb[1]),
         );
-        let fitted_size = if is_vertical {
-            PhysicalSize::new(
-                (size.height as f32 * self.aspect_ratio).round() as u32,
-                size.height,
-            )
-        } else {
-            PhysicalSize::new(
-                size.width,
-                (size.width as f32 / self.aspect_ratio).round() as u32,
-            )
+        // Infinite for a window without any room for the image.
+        if !tex_per_px.is_finite() || tex_per_px <= 1.0 {
+            return None;
+        }
+        Some((
+            (texture.width() as f32 / tex_per_px).ceil() as u32,
+            (texture.height() as f32 / tex_per_px).ceil() as u32,
+        ))
+    }
+
+    /// Returns whether the downscaled frame can be displayed as it is.
+    fn downscaled_is_current(&self, win: &Win) -> bool {
+        let Some(downscaled) = &win.textures.downscaled else {
+            return false;
         };
-        log::trace!(
-            "enforce_aspect_ratio: requested {}x{}, fitted size {}x{} (vertical={is_vertical})",
-            size.width,
-            size.height,
-            fitted_size.width,
-            fitted_size.height,
+        self.lanczos
+            && downscaled.frame == self.frame_index as u32
+            && self.downscaled_size(win) == Some((downscaled.width, downscaled.height))
+    }
+
+    /// Downscales the current frame for [`Action::ToggleLanczos`], unless that has already been
+    /// done for the current size and frame.
+    fn update_downscaled(&mut self) {
+        let Some(win) = &self.window else { return };
+        if !self.lanczos || win.textures.readback.is_some() || self.downscaled_is_current(win) {
+            return;
+        }
+        let Some((width, height)) = self.downscaled_size(win) else {
+            return;
+        };
+        let frame = self.frame_index as u32;
+        let Some(win) = &mut self.window else { return };
+        let texture = win.uploader.downscaler.downscale(
+            &win.device,
+            &win.queue,
+            &win.textures.texture,
+            frame,
+            width,
+            height,
         );
+        win.textures.display_bind_group = display_bind_group(
+            &win.device,
+            &win.uploader,
+            &win.textures.texture,
+            Some(&texture),
+            &win.display_settings,
+        );
+        win.textures.downscaled = Some(DownscaledFrame {
+            frame,
+            width,
+            height,
+        });
+    }
 
-        ratio::enforce(&win.window, self.aspect_ratio, size);
+    /// Renders what the window currently shows (without the overlay) to a PNG file in the
+    /// temporary directory, and returns its path.
+    fn export_view(&self, win: &Win) -> anyhow::Result<PathBuf> {
+        if win.textures.readback.is_some() {
+            bail!("the image is still being preprocessed");
+        }
+        let state = self.frame_state(win);
+        let image = render_view(win, &state.display_settings)?;
 
-        if fitted_size != size {
-            let _ = win.window.request_inner_size(fitted_size);
+        let stem = Path::new(&self.title)
+            .file_stem()
+            .map_or("image".into(), |stem| stem.to_string_lossy());
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = env::temp_dir().join(format!("{stem}-view-{timestamp}.png"));
+        image
+            .save_with_format(&path, ImageFormat::Png)
+            .with_context(|| format!("Failed to save image to '{}'", path.display()))?;
+        log::info!("exported the view to '{}'", path.display());
+        Ok(path)
+    }
+
+    /// Opens the displayed region of the image in a new window, leaving this one as it is.
+    fn open_view_in_new_window(&mut self) {
+        let mut viewer = self.sibling();
+        viewer.set_image(&self.source, self.loaded.clone());
+        viewer.playlist_index = self.playlist_index;
+        viewer.initial_region = Some((self.min_uv, self.max_uv));
+        log::info!("opening the displayed region in a new window");
+        self.spawned.push(viewer);
+    }
+
+    /// Creates a viewer for a new window that has the same settings and playlist as this one, but
+    /// no image yet.
+    fn sibling(&self) -> Viewer {
+        Viewer {
+            startup: self.startup,
+            transparency: self.transparency,
+            filter: self.filter,
+            outline: self.outline,
+            dither: self.dither,
+            lanczos: self.lanczos,
+            checkerboard_zoom: self.checkerboard_zoom,
+            selection_color: self.selection_color,
+            selection_outline: self.selection_outline,
+            snap_zoom: self.snap_zoom,
+            grid: self.grid,
+            pixel_grid: self.pixel_grid,
+            always_on_top: self.always_on_top,
+            autocrop: self.autocrop,
+            vsync: self.vsync,
+            crop_ratio: self.crop_ratio,
+            assume_premultiplied: self.assume_premultiplied,
+            play_count_override: self.play_count_override,
+            keymap: self.keymap.clone(),
+            config: self.config.clone(),
+            title_template: self.title_template.clone(),
+            toolbar: self.toolbar,
+            free_resize: self.free_resize,
+            loupe_zoom: LOUPE_DEFAULT_ZOOM,
+            proxy: self.proxy.clone(),
+            backends: self.backends,
+            instance: self.instance.clone(),
+            watch: self.watch,
+            show_fps: self.show_fps,
+            monitor: self.monitor,
+            auto_close: self.auto_close,
+            corner_radius: self.corner_radius,
+            background_image: self.background_image.clone(),
+            show_background_image: self.show_background_image,
+            playlist: self.playlist.clone(),
+            ..Viewer::default()
         }
-        self.recreate_swapchain(win);
-        win.window.request_redraw();
     }
 
-    fn reset_region(&mut self) {
+    /// Displays the given UV region of the image and adjusts the window's aspect ratio to match.
+    ///
+    /// The previously displayed region is remembered for [`Action::UndoZoom`].
+    fn set_region(&mut self, min: Vec2f, max: Vec2f) {
+        if (min, max) != (self.min_uv, self.max_uv) {
+            push_zoom_history(&mut self.zoom_history, self.min_uv, self.max_uv);
+        }
+        self.show_region(min, max);
+    }
+
+    /// Like [`Viewer::set_region`], but without adding to the zoom history.
+    fn show_region(&mut self, min: Vec2f, max: Vec2f) {
         let Some(win) = &self.window else { return };
-        if win.image_info.top == u32::MAX {
-            // Somehow not a single non-transparent pixel in the image? good luck finding the window, fucker
-            self.min_uv = vec2(0.0, 0.0);
-            self.max_uv = vec2(1.0, 1.0);
-            self.aspect_ratio = self.image_aspect_ratio;
-        } else {
-            self.min_uv = vec2(
-                win.image_info.left as f32 / self.image_width as f32,
-                win.image_info.top as f32 / self.image_height as f32,
-            );
-            self.max_uv = vec2(
-                (win.image_info.right + 1) as f32 / self.image_width as f32,
-                (win.image_info.bottom + 1) as f32 / self.image_height as f32,
-            );
-            let range = self.max_uv - self.min_uv;
+        self.zoom_animation = self.zoom_animation_to(min, max);
+        self.min_uv = min;
+        self.max_uv = max;
+        let range = max - min;
 
-            // UVs always go from 0-1, so their "native" aspect ratio is 1.0.
-            self.aspect_ratio = self.image_aspect_ratio * (range[0] / range[1]);
-        }
+        // UVs always go from 0-1, so their "native" aspect ratio is 1.0.
+        self.aspect_ratio = self.image_aspect_ratio * (range[0] / range[1]);
 
         self.enforce_aspect_ratio(win, win.window.inner_size());
     }
 
+    /// Returns the transition from the currently displayed region to the UV region `min`-`max`, or
+    /// `None` if there is nothing to animate.
+    fn zoom_animation_to(&self, min: Vec2f, max: Vec2f) -> Option<ZoomAnimation> {
+        let (from_min, from_max) = self.displayed_region();
+        let from_range = from_max - from_min;
+        // Nothing is displayed yet during startup, so there's nothing to animate from.
+        if from_range[0] <= 0.0 || from_range[1] <= 0.0 || (from_min, from_max) == (min, max) {
+            return None;
+        }
+        Some(ZoomAnimation {
+            from_min,
+            from_max,
+            start: Instant::now(),
+        })
+    }
+
+    /// Returns the UV region that is currently being displayed, which lags behind `min_uv` and
+    /// `max_uv` while a zoom transition is in progress.
+    fn displayed_region(&self) -> (Vec2f, Vec2f) {
+        let Some(anim) = &self.zoom_animation else {
+            return (self.min_uv, self.max_uv);
+        };
+        let t = anim.start.elapsed().as_secs_f32() / ZOOM_ANIMATION_DURATION.as_secs_f32();
+        if t >= 1.0 {
+            return (self.min_uv, self.max_uv);
+        }
+        // Cubic ease-out.
+        let t = 1.0 - (1.0 - t).powi(3);
+        (
+            anim.from_min + (self.min_uv - anim.from_min) * t,
+            anim.from_max + (self.max_uv - anim.from_max) * t,
+        )
+    }
+
     fn window_to_uv(&self, win: &Win, coords: PhysicalPosition<f64>) -> Vec2f {
-        let (min, max) = self.fb_coord_range(win);
-        let mut u = (coords.x as f32 - min[0]) / (max[0] - min[0]);
-        let mut v = (coords.y as f32 - min[1]) / (max[1] - min[1]);
+        sizing::window_to_uv(
+            win.window.inner_size(),
+            self.aspect_ratio,
+            (self.min_uv, self.max_uv),
+            to_vec2f(coords),
+        )
+    }
 
-        // Adjust the raw UVs to take `min_uv` and `max_uv` into account.
-        let u_range = self.max_uv[0] - self.min_uv[0];
-        let v_range = self.max_uv[1] - self.min_uv[1];
-        u = (u * u_range) + self.min_uv[0];
-        v = (v * v_range) + self.min_uv[1];
+    /// Inverse of [`Viewer::window_to_uv`].
+    fn uv_to_window(&self, win: &Win, uv: Vec2f) -> Vec2f {
+        sizing::uv_to_window(
+            win.window.inner_size(),
+            self.aspect_ratio,
+            (self.min_uv, self.max_uv),
+            uv,
+        )
+    }
 
-        vec2(u, v)
+    /// Returns the distance (in image pixels) and angle (in degrees, counterclockwise from the
+    /// positive X axis) between two points given in UV coordinates.
+    fn measurement(&self, start: Vec2f, end: Vec2f) -> (f32, f32) {
+        let delta = (end - start) * vec2(self.image_width as f32, self.image_height as f32);
+        let angle = f32::atan2(-delta[1], delta[0]).to_degrees();
+        (delta.length(), angle)
     }
 
     fn selection_region(&self, win: &Win) -> (Vec2f, Vec2f) {
         if let (CursorMode::Select(start), Some(end)) = (self.cursor_mode, self.cursor_pos) {
             let start = self.window_to_uv(win, start);
-            let end = self.window_to_uv(win, end);
+            let mut end = self.window_to_uv(win, end);
+            if let (true, Some(ratio)) = (self.modifiers.shift_key(), self.crop_ratio) {
+                end = self.constrain_selection(start, end, ratio);
+            }
 
-            // sort corners
-            let min = [f32::min(start[0], end[0]), f32::min(start[1], end[1])];
-            let max = [f32::max(start[0], end[0]), f32::max(start[1], end[1])];
+            // sort corners, and clamp to visible area
+            let min = start.min(end).max(self.min_uv);
+            let max = start.max(end).min(self.max_uv);
 
-            // clamp to visible area
-            let min = vec2(
-                f32::max(min[0], self.min_uv[0]),
-                f32::max(min[1], self.min_uv[1]),
+            (min, max)
+        } else {
+            // Not selecting: show the selection being adjusted, if any.
+            self.adjusting_selection.unwrap_or_default()
+        }
+    }
+
+    /// Starts adjusting the committed selection, by zooming back out to the view it was made in.
+    fn begin_adjusting_selection(&mut self) {
+        let Some(selection) = self.committed_selection else {
+            return;
+        };
+        self.adjusting_selection = Some(selection);
+        let (min, max) = self.selection_view;
+        self.set_region(min, max);
+    }
+
+    /// Leaves adjust mode, zooming into the adjusted selection if `commit` is `true`, or back into
+    /// the previous one otherwise.
+    fn end_adjusting_selection(&mut self, commit: bool) {
+        let Some(adjusted) = self.adjusting_selection.take() else {
+            return;
+        };
+        if commit {
+            self.committed_selection = Some(adjusted);
+        }
+        if let Some((min, max)) = self.committed_selection {
+            self.set_region(min, max);
+        }
+        self.cursor_mode = CursorMode::Move;
+        self.update_cursor();
+    }
+
+    /// Returns the edge or corner of the selection being adjusted that is under `pos`.
+    fn selection_handle_at(
+        &self,
+        win: &Win,
+        pos: PhysicalPosition<f64>,
+    ) -> Option<ResizeDirection> {
+        let (min, max) = self.adjusting_selection?;
+        let (min, max) = (self.uv_to_window(win, min), self.uv_to_window(win, max));
+        let pos = to_vec2f(pos);
+        let border = RESIZE_BORDER_WIDTH as f32;
+        if pos[0] < min[0] - border
+            || pos[0] > max[0] + border
+            || pos[1] < min[1] - border
+            || pos[1] > max[1] + border
+        {
+            return None;
+        }
+        resize_direction(
+            (pos[1] - min[1]).abs() <= border,
+            (pos[0] - max[0]).abs() <= border,
+            (pos[1] - max[1]).abs() <= border,
+            (pos[0] - min[0]).abs() <= border,
+        )
+    }
+
+    /// Returns the selection being adjusted with the edges that `dir` refers to moved to `pos`.
+    fn dragged_selection(
+        &self,
+        win: &Win,
+        dir: ResizeDirection,
+        pos: PhysicalPosition<f64>,
+    ) -> Option<(Vec2f, Vec2f)> {
+        let uv = self
+            .window_to_uv(win, pos)
+            .max(self.min_uv)
+            .min(self.max_uv);
+        let (mut min, mut max) = self.adjusting_selection?;
+        // Keep the selection at least one image pixel in size.
+        let pixel = vec2(
+            1.0 / self.image_width as f32,
+            1.0 / self.image_height as f32,
+        );
+        let (n, e, s, w) = match dir {
+            ResizeDirection::North => (true, false, false, false),
+            ResizeDirection::NorthEast => (true, true, false, false),
+            ResizeDirection::East => (false, true, false, false),
+            ResizeDirection::SouthEast => (false, true, true, false),
+            ResizeDirection::South => (false, false, true, false),
+            ResizeDirection::SouthWest => (false, false, true, true),
+            ResizeDirection::West => (false, false, false, true),
+            ResizeDirection::NorthWest => (true, false, false, true),
+        };
+        if n {
+            min[1] = uv[1].min(max[1] - pixel[1]);
+        }
+        if s {
+            max[1] = uv[1].max(min[1] + pixel[1]);
+        }
+        if w {
+            min[0] = uv[0].min(max[0] - pixel[0]);
+        }
+        if e {
+            max[0] = uv[0].max(min[0] + pixel[0]);
+        }
+        Some((min, max))
+    }
+
+    /// Moves the `end` corner of a selection so that the selection has the aspect ratio `ratio`
+    /// (in image pixels) and stays within the visible area.
+    fn constrain_selection(&self, start: Vec2f, end: Vec2f, ratio: f32) -> Vec2f {
+        let image_size = vec2(self.image_width as f32, self.image_height as f32);
+        let delta = (end - start) * image_size;
+        let sign = delta.map(|d| if d < 0.0 { -1.0 } else { 1.0 });
+
+        // Grow the smaller side to match the larger one.
+        let width = f32::max(delta[0].abs(), delta[1].abs() * ratio);
+        let mut size = vec2(width, width / ratio);
+
+        // Shrink it again if that would extend past the visible area.
+        let available = vec2(
+            if sign[0] < 0.0 {
+                start[0] - self.min_uv[0]
+            } else {
+                self.max_uv[0] - start[0]
+            },
+            if sign[1] < 0.0 {
+                start[1] - self.min_uv[1]
+            } else {
+                self.max_uv[1] - start[1]
+            },
+        ) * image_size;
+        let scale = f32::min(available[0] / size[0], available[1] / size[1]);
+        if scale < 1.0 {
+            size = size * scale.max(0.0);
+        }
+
+        start + vec2(size[0] * sign[0], size[1] * sign[1]) / image_size
+    }
+
+    fn fb_coord_range(&self, win: &Win) -> (Vec2f, Vec2f) {
+        sizing::fit_aspect_ratio(win.window.inner_size(), self.aspect_ratio)
+    }
+
+    fn frame_state(&self, win: &Win) -> FrameState {
+        FrameState {
+            display_settings: self.display_settings(win),
+            overlay: self.overlay(win),
+        }
+    }
+
+    /// Requests a redraw, unless the window already shows what would be drawn.
+    fn request_redraw_if_changed(&self, win: &Win) {
+        let state = self.frame_state(win);
+        if win.last_frame.borrow().as_ref() != Some(&state) {
+            win.window.request_redraw();
+        }
+    }
+
+    /// Returns the radius of the window corners in physical pixels.
+    ///
+    /// Without compositor support for transparency, the corners would just be black, so they're
+    /// only rounded when it's available.
+    fn corner_radius_px(&self, win: &Win) -> f32 {
+        if !win.supports_alpha {
+            return 0.0;
+        }
+        let size = win.window.inner_size();
+        let max = size.width.min(size.height) as f32 / 2.0;
+        (self.corner_radius * win.window.scale_factor() as f32).min(max)
+    }
+
+    fn display_settings(&self, win: &Win) -> DisplaySettings {
+        let (min_uv, max_uv) = self.displayed_region();
+        let mut display_settings = DisplaySettings {
+            min_fb: vec2(0.0, 0.0),
+            max_fb: vec2(0.0, 0.0),
+            min_uv,
+            max_uv,
+            min_selection: vec2(0.0, 0.0),
+            max_selection: vec2(0.0, 0.0),
+            selection_color: self.selection_color,
+            checkerboard_a: vec4(0.0, 0.0, 0.0, 0.0),
+            checkerboard_b: vec4(0.0, 0.0, 0.0, 0.0),
+            checkerboard_res: CHECKERBOARD_CELL_SIZE,
+            filter_mode: 0,
+            loupe_center_fb: vec2(0.0, 0.0),
+            loupe_center_uv: vec2(0.0, 0.0),
+            loupe_radius: 0.0,
+            loupe_zoom: 1.0,
+            outline: self.outline.into(),
+            frame: self.frame_index as u32,
+            grid: self.grid as u32,
+            // Surfaces with more bits per channel don't show banding.
+            dither: (self.dither && !HIGH_BIT_DEPTH_SURFACE_FORMATS.contains(&win.surface_format))
+                .into(),
+            min_grid: vec2(0.0, 0.0),
+            max_grid: vec2(1.0, 1.0),
+            alpha_view: self.alpha_view.into(),
+            lanczos: self.downscaled_is_current(win).into(),
+            checkerboard_zoom: self.checkerboard_zoom.into(),
+            pixel_grid: self.pixel_grid.into(),
+            corner_radius: self.corner_radius_px(win),
+            window_size: vec2(
+                win.window.inner_size().width as f32,
+                win.window.inner_size().height as f32,
+            ),
+            background_image: (self.show_background_image && self.background_image.is_some())
+                .into(),
+            _padding: [0; 3],
+            selection_outline: self.selection_outline.into(),
+        };
+
+        // While zooming, the displayed region's aspect ratio doesn't match the window's yet.
+        let range = max_uv - min_uv;
+        let aspect_ratio = self.image_aspect_ratio * (range[0] / range[1]);
+        let (min, max) = sizing::fit_aspect_ratio(win.window.inner_size(), aspect_ratio);
+
+        display_settings.min_fb = min;
+        display_settings.max_fb = max;
+
+        let (min, max) = self.selection_region(win);
+        display_settings.min_selection = min;
+        display_settings.max_selection = max;
+        if min != max {
+            // Divide the selection, to help composing a crop.
+            display_settings.min_grid = min;
+            display_settings.max_grid = max;
+        }
+
+        (
+            display_settings.checkerboard_a,
+            display_settings.checkerboard_b,
+        ) = checkerboard_colors(self.transparency, self.cursor_pos.is_some());
+
+        match self.filter_override.unwrap_or(self.filter) {
+            FilterMode::Smart => display_settings.filter_mode = 0,
+            FilterMode::Linear => display_settings.filter_mode = 1,
+            FilterMode::Nearest => display_settings.filter_mode = 2,
+        }
+
+        if let (true, Some(pos)) = (self.loupe, self.cursor_pos) {
+            display_settings.loupe_center_fb = to_vec2f(pos);
+            display_settings.loupe_center_uv = self.window_to_uv(win, pos);
+            display_settings.loupe_radius = LOUPE_RADIUS;
+            display_settings.loupe_zoom = self.loupe_zoom;
+        }
+
+        display_settings
+    }
+
+    /// Returns the window area occupied by the animation timeline, or `None` if the image is not
+    /// animated.
+    ///
+    /// The timeline sits right above the bottom resize border, so that it doesn't interfere with
+    /// resizing the window.
+    fn timeline_rect(&self, win: &Win) -> Option<(Vec2f, Vec2f)> {
+        if !self.animated {
+            return None;
+        }
+        let size = win.window.inner_size();
+        let border = RESIZE_BORDER_WIDTH as f32;
+        let max = vec2(size.width as f32 - border, size.height as f32 - border);
+        let min = vec2(border, max[1] - TIMELINE_HEIGHT);
+        Some((min, max))
+    }
+
+    /// Returns the index of the animation frame under `pos` on the timeline.
+    fn timeline_frame_at(&self, win: &Win, pos: PhysicalPosition<f64>) -> Option<usize> {
+        let (min, max) = self.timeline_rect(win)?;
+        let x = (pos.x as f32 - min[0]) / (max[0] - min[0]);
+        let index = (x * self.frame_count as f32).floor() as isize;
+        Some(index.clamp(0, self.frame_count as isize - 1) as usize)
+    }
+
+    /// Returns the window area occupied by the thumbnail strip, or `None` if it is hidden.
+    ///
+    /// The strip sits above the animation timeline (or the bottom resize border).
+    fn thumbnail_strip_rect(&self, win: &Win) -> Option<(Vec2f, Vec2f)> {
+        if !self.show_thumbnails {
+            return None;
+        }
+        let size = win.window.inner_size();
+        let border = RESIZE_BORDER_WIDTH as f32;
+        let bottom = match self.timeline_rect(win) {
+            Some((min, _)) => min[1],
+            None => size.height as f32 - border,
+        };
+        let height = THUMBNAIL_SIZE as f32 + THUMBNAIL_PADDING * 2.0;
+        Some((
+            vec2(border, bottom - height),
+            vec2(size.width as f32 - border, bottom),
+        ))
+    }
+
+    /// Returns the playlist entries shown in the thumbnail strip, along with the top left corner
+    /// of their thumbnail.
+    fn thumbnail_cells(&self, win: &Win) -> Vec<(usize, Vec2f)> {
+        let Some((min, max)) = self.thumbnail_strip_rect(win) else {
+            return Vec::new();
+        };
+        let pitch = THUMBNAIL_SIZE as f32 + THUMBNAIL_PADDING;
+        let fitting = ((max[0] - min[0] - THUMBNAIL_PADDING) / pitch)
+            .floor()
+            .max(1.0) as usize;
+        let count = fitting.min(self.playlist.len());
+        // Keep the displayed image in the middle of the strip, unless that would leave space.
+        let first = self
+            .playlist_index
+            .saturating_sub(count / 2)
+            .min(self.playlist.len() - count);
+        (first..first + count)
+            .enumerate()
+            .map(|(i, index)| {
+                let pos = min + vec2(THUMBNAIL_PADDING + i as f32 * pitch, THUMBNAIL_PADDING);
+                (index, pos)
+            })
+            .collect()
+    }
+
+    /// Returns the index of the playlist entry whose thumbnail is under `pos`.
+    fn thumbnail_at(&self, win: &Win, pos: PhysicalPosition<f64>) -> Option<usize> {
+        let pos = to_vec2f(pos);
+        let size = THUMBNAIL_SIZE as f32;
+        self.thumbnail_cells(win)
+            .into_iter()
+            .find(|(_, min)| {
+                pos[0] >= min[0]
+                    && pos[0] < min[0] + size
+                    && pos[1] >= min[1]
+                    && pos[1] < min[1] + size
+            })
+            .map(|(index, _)| index)
+    }
+
+    /// Returns the toolbar buttons with their labels and window areas.
+    ///
+    /// The toolbar is centered at the top of the window, right below the resize border.
+    fn toolbar_buttons(&self, win: &Win) -> Vec<(Action, &'static str, Vec2f, Vec2f)> {
+        let widths = TOOLBAR
+            .iter()
+            .map(|(_, label)| overlay::text_size(label)[0] + LABEL_PADDING * 2.0)
+            .collect::<Vec<_>>();
+        let total = widths.iter().sum::<f32>();
+        let window_width = win.window.inner_size().width as f32;
+        let mut x = ((window_width - total) * 0.5).max(0.0);
+        let y = RESIZE_BORDER_WIDTH as f32;
+        TOOLBAR
+            .iter()
+            .zip(widths)
+            .map(|(&(action, label), width)| {
+                let min = vec2(x, y);
+                x += width;
+                (action, label, min, min + vec2(width, TOOLBAR_BUTTON_HEIGHT))
+            })
+            .collect()
+    }
+
+    /// Returns the toolbar button under `pos`, if the toolbar is shown.
+    fn toolbar_button_at(&self, win: &Win, pos: PhysicalPosition<f64>) -> Option<Action> {
+        if !self.toolbar || !self.toolbar_hover.hovered {
+            return None;
+        }
+        let pos = to_vec2f(pos);
+        self.toolbar_buttons(win)
+            .into_iter()
+            .find(|(_, _, min, max)| {
+                pos[0] >= min[0] && pos[0] < max[0] && pos[1] >= min[1] && pos[1] < max[1]
+            })
+            .map(|(action, ..)| action)
+    }
+
+    /// Returns the text of each context menu entry: its description and the first key bound to it.
+    fn context_menu_texts(&self) -> Vec<(Action, String)> {
+        let width = CONTEXT_MENU
+            .iter()
+            .map(|a| a.description().len())
+            .max()
+            .unwrap_or(0);
+        CONTEXT_MENU
+            .iter()
+            .map(|&action| {
+                let key = self
+                    .keymap
+                    .iter()
+                    .filter(|(_, a)| **a == action)
+                    .map(|(key, _)| *key)
+                    .min()
+                    .map_or(String::new(), keymap::key_name);
+                (action, format!("{:width$}  {key}", action.description()))
+            })
+            .collect()
+    }
+
+    fn context_menu_size(&self) -> Vec2f {
+        let width = self
+            .context_menu_texts()
+            .iter()
+            .map(|(_, text)| overlay::text_size(text)[0])
+            .fold(0.0, f32::max);
+        vec2(width, CONTEXT_MENU.len() as f32 * CONTEXT_MENU_ROW_HEIGHT)
+            + vec2(LABEL_PADDING, LABEL_PADDING) * 2.0
+    }
+
+    /// Returns the entries of the open context menu, along with the window area of each.
+    fn context_menu_rows(&self) -> Vec<(Action, String, Vec2f, Vec2f)> {
+        let Some(pos) = self.context_menu else {
+            return Vec::new();
+        };
+        let width = self.context_menu_size()[0];
+        self.context_menu_texts()
+            .into_iter()
+            .enumerate()
+            .map(|(i, (action, text))| {
+                let min = pos + vec2(0.0, LABEL_PADDING + i as f32 * CONTEXT_MENU_ROW_HEIGHT);
+                let max = min + vec2(width, CONTEXT_MENU_ROW_HEIGHT);
+                (action, text, min, max)
+            })
+            .collect()
+    }
+
+    /// Returns the context menu entry under `pos`.
+    fn context_menu_item_at(&self, pos: PhysicalPosition<f64>) -> Option<Action> {
+        let pos = to_vec2f(pos);
+        self.context_menu_rows()
+            .into_iter()
+            .find(|(_, _, min, max)| {
+                pos[0] >= min[0] && pos[0] < max[0] && pos[1] >= min[1] && pos[1] < max[1]
+            })
+            .map(|(action, ..)| action)
+    }
+
+    fn overlay(&self, win: &Win) -> Overlay {
+        let mut overlay = Overlay::default();
+
+        if let Some(readback) = &win.textures.readback {
+            scanning_indicator(&mut overlay, win.window.inner_size(), readback.start);
+            return overlay;
+        }
+
+        if let Some((min, max)) = self.thumbnail_strip_rect(win) {
+            overlay.rect(min, max, THUMBNAIL_STRIP_COLOR);
+            let cell = vec2(THUMBNAIL_SIZE as f32, THUMBNAIL_SIZE as f32);
+            for (index, pos) in self.thumbnail_cells(win) {
+                match self.thumbnails.get(&self.playlist[index]) {
+                    Some(Thumbnail::Ready { uv, size }) => {
+                        let min = pos + (cell - *size) * 0.5;
+                        overlay.thumbnail(min, min + *size, *uv);
+                    }
+                    _ => overlay.rect(pos, pos + cell, THUMBNAIL_PLACEHOLDER_COLOR),
+                }
+                if index == self.playlist_index {
+                    overlay.frame(
+                        pos,
+                        pos + cell,
+                        THUMBNAIL_HIGHLIGHT_WIDTH,
+                        THUMBNAIL_HIGHLIGHT_COLOR,
+                    );
+                }
+            }
+        }
+
+        if let Some((min, max)) = self.timeline_rect(win) {
+            let scrubbing = matches!(self.cursor_mode, CursorMode::Scrub);
+            if self.cursor_pos.is_some() || scrubbing {
+                let played = (self.frame_index + 1) as f32 / self.frame_count as f32;
+                let played_x = min[0] + (max[0] - min[0]) * played;
+                overlay.rect(min, max, TIMELINE_COLOR);
+                overlay.rect(min, vec2(played_x, max[1]), TIMELINE_PLAYED_COLOR);
+            }
+        }
+
+        if self.show_content_bounds {
+            let mut bounds = vec![(&win.textures.image_info, CONTENT_BOUNDS_COLOR)];
+            if self.frame_count > 1 {
+                bounds.push((
+                    &win.textures.frame_infos[self.frame_index],
+                    FRAME_BOUNDS_COLOR,
+                ));
+            }
+            for (info, color) in bounds {
+                if info.top == u32::MAX {
+                    continue;
+                }
+                let (min, max) = self.content_region(info);
+                overlay.frame(
+                    self.uv_to_window(win, min),
+                    self.uv_to_window(win, max),
+                    CONTENT_BOUNDS_WIDTH,
+                    color,
+                );
+            }
+        }
+
+        if let Some(start) = self.measure_start {
+            let end = self
+                .measure_end
+                .or_else(|| self.cursor_pos.map(|pos| self.window_to_uv(win, pos)));
+            if let Some(end) = end {
+                let from = self.uv_to_window(win, start);
+                let to = self.uv_to_window(win, end);
+                overlay.line(from, to, MEASURE_LINE_WIDTH, MEASURE_COLOR);
+                let marker = vec2(MEASURE_MARKER_SIZE, MEASURE_MARKER_SIZE) * 0.5;
+                overlay.rect(from - marker, from + marker, MEASURE_COLOR);
+                overlay.rect(to - marker, to + marker, MEASURE_COLOR);
+
+                let (distance, angle) = self.measurement(start, end);
+                let text = format!("{distance:.1} px\n{angle:.1} deg");
+                overlay.label(to + vec2(OVERLAY_MARGIN, OVERLAY_MARGIN), &text);
+            }
+        }
+
+        let mut label_pos = vec2(OVERLAY_MARGIN, OVERLAY_MARGIN);
+        if self.show_frame_counter && self.frame_count > 1 {
+            let mut text = format!("{} / {}", self.frame_index + 1, self.frame_count);
+            let speed = self.playback_speed.load(Ordering::Relaxed);
+            if speed != 0 {
+                write!(text, " ({:.2}x)", PLAYBACK_SPEED_STEP.powi(speed)).unwrap();
+            }
+            let max = overlay.label(label_pos, &text);
+            label_pos[1] = max[1] + OVERLAY_MARGIN;
+
+            // Fraction of the loop that has been shown once the current frame's delay expires.
+            let total = self.frame_delays.iter().sum::<Duration>();
+            let elapsed = self.frame_delays[..=self.frame_index]
+                .iter()
+                .sum::<Duration>();
+            let progress = if total.is_zero() {
+                (self.frame_index + 1) as f32 / self.frame_count as f32
+            } else {
+                elapsed.as_secs_f32() / total.as_secs_f32()
+            };
+
+            let (min, max) = self.fb_coord_range(win);
+            let width = (max[0] - min[0]) * progress;
+            overlay.rect(
+                vec2(min[0], max[1] - PROGRESS_BAR_HEIGHT),
+                vec2(min[0] + width, max[1]),
+                PROGRESS_BAR_COLOR,
             );
-            let max = vec2(
-                f32::min(max[0], self.max_uv[0]),
-                f32::min(max[1], self.max_uv[1]),
+        }
+
+        if self.show_file_info {
+            let max = overlay.label(label_pos, &self.file_info_text(win));
+            label_pos[1] = max[1] + OVERLAY_MARGIN;
+        }
+
+        if self.show_alpha_info {
+            let text = self.alpha_info(&win.textures.frame_infos[self.frame_index]);
+            let max = overlay.label(label_pos, &text);
+            label_pos[1] = max[1] + OVERLAY_MARGIN;
+        }
+
+        if self.show_palette {
+            self.draw_palette(&mut overlay, label_pos);
+        }
+
+        if self.show_fps {
+            // Only frames that were actually drawn are counted, so idle periods without redraws
+            // lower the rate.
+            let frame_times = win.frame_times.borrow();
+            let text = match (frame_times.front(), frame_times.back()) {
+                (Some(first), Some(last)) if frame_times.len() > 1 => {
+                    let frame_time = (*last - *first) / (frame_times.len() - 1) as u32;
+                    format!(
+                        "{:.1} fps\n{:.2} ms",
+                        1.0 / frame_time.as_secs_f32(),
+                        frame_time.as_secs_f32() * 1000.0,
+                    )
+                }
+                _ => "- fps\n- ms".to_string(),
+            };
+            let size = overlay::text_size(&text) + vec2(LABEL_PADDING, LABEL_PADDING) * 2.0;
+            let pos = vec2(
+                win.window.inner_size().width as f32 - size[0] - OVERLAY_MARGIN,
+                OVERLAY_MARGIN,
             );
+            overlay.label(pos, &text);
+        }
 
-            (min, max)
-        } else {
-            Default::default()
+        if self.show_help {
+            // Drawn over the other labels, since it would mostly hide them anyway.
+            let pos = vec2(OVERLAY_MARGIN, OVERLAY_MARGIN);
+            overlay.label(pos, &keymap::help_text(&self.keymap));
+        }
+
+        let opacity = self.toolbar_hover.opacity();
+        if self.toolbar && opacity > 0.0 {
+            let hovered = match self.cursor_mode {
+                CursorMode::ToolbarButton(action) => Some(action),
+                _ => None,
+            };
+            let fade = vec4(1.0, 1.0, 1.0, opacity);
+            for (action, label, min, max) in self.toolbar_buttons(win) {
+                let color = if hovered == Some(action) {
+                    CONTEXT_MENU_HOVER_COLOR
+                } else {
+                    PANEL_COLOR
+                };
+                overlay.rect(min, max, color * fade);
+                let offset = (max - min - overlay::text_size(label)) * 0.5;
+                overlay.text(min + offset, label, TEXT_COLOR * fade);
+            }
+        }
+
+        if let Some(pos) = self.context_menu {
+            overlay.rect(pos, pos + self.context_menu_size(), PANEL_COLOR);
+            let hovered = match self.cursor_mode {
+                CursorMode::MenuItem(action) => Some(action),
+                _ => None,
+            };
+            for (action, text, min, max) in self.context_menu_rows() {
+                if hovered == Some(action) {
+                    overlay.rect(min, max, CONTEXT_MENU_HOVER_COLOR);
+                }
+                let text_offset = (CONTEXT_MENU_ROW_HEIGHT - overlay::text_size(&text)[1]) * 0.5;
+                overlay.text(min + vec2(LABEL_PADDING, text_offset), &text, TEXT_COLOR);
+            }
+        }
+
+        if self.drop_hover {
+            let size = win.window.inner_size();
+            let size = vec2(size.width as f32, size.height as f32);
+            overlay.rect(vec2(0.0, 0.0), size, DROP_HINT_COLOR);
+            let text = "Drop to open";
+            let pos = (size - overlay::text_size(text)) * 0.5;
+            overlay.label(pos, text);
+        }
+
+        if let Some(download) = &self.download {
+            scanning_indicator(&mut overlay, win.window.inner_size(), download.start);
+        }
+
+        overlay
+    }
+
+    /// Draws the average and dominant colors as swatches with their hex codes, in a label whose
+    /// top left corner is at `pos`.
+    fn draw_palette(&self, overlay: &mut Overlay, pos: Vec2f) {
+        let Some(Some(palette)) = self.palettes.get(&self.frame_index) else {
+            overlay.label(pos, "no colors");
+            return;
+        };
+        let hex = |Color([r, g, b, _]): Color| format!("#{r:02x}{g:02x}{b:02x}");
+        // The first two columns of every line are left blank for the swatch.
+        let mut rows = vec![(palette.average, "average".to_string())];
+        rows.extend(
+            palette
+                .dominant
+                .iter()
+                .map(|&(color, share)| (color, format!("{:.0}%", share * 100.0))),
+        );
+        let text = rows
+            .iter()
+            .map(|(color, caption)| format!("   {} {caption}", hex(*color)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        overlay.label(pos, &text);
+
+        let swatch = overlay::text_size("  ");
+        for (i, (color, _)) in rows.iter().enumerate() {
+            let min = pos + vec2(LABEL_PADDING, LABEL_PADDING + i as f32 * LINE_HEIGHT);
+            overlay.rect(min, min + swatch, color.to_linear_premultiplied());
         }
     }
 
-    fn fb_coord_range(&self, win: &Win) -> (Vec2f, Vec2f) {
-        let size = win.window.inner_size();
-        let to_aspect = size.width as f32 / size.height as f32;
-        let (y_min, x_min, w, h);
-        if self.aspect_ratio > to_aspect {
-            w = size.width as f32;
-            h = size.width as f32 / self.aspect_ratio;
-
-            x_min = 0.0;
-            y_min = (size.height as f32 - h) / 2.0;
+    fn file_info_text(&self, win: &Win) -> String {
+        let format = &self.file_info.format;
+        let color = match self.file_info.color_type {
+            Some(ty) => format!(
+                "{:?} ({}-bit)",
+                ty,
+                ty.bits_per_pixel() / u16::from(ty.channel_count())
+            ),
+            None => "unknown".to_string(),
+        };
+        format!(
+            "{}\n{}x{} {format}, {} KiB\nframes: {}\ncolor: {color}\nalpha: {}",
+            self.title,
+            self.image_width,
+            self.image_height,
+            self.file_info.kb,
+            self.frame_count,
+            if win.textures.image_info.uses_alpha() {
+                "yes"
+            } else {
+                "no"
+            },
+        )
+    }
+
+    /// Describes how the image uses its alpha channel, as detected during preprocessing.
+    fn alpha_info(&self, info: &ImageInfo) -> String {
+        let usage = if info.uses_partial_alpha() {
+            "partial"
+        } else if info.uses_alpha() {
+            "binary"
+        } else {
+            "none"
+        };
+        let straight = if !info.uses_partial_alpha() {
+            "n/a"
+        } else if info.known_straight() {
+            "straight"
+        } else {
+            "unknown"
+        };
+        let treated_as = if self.premultiplied {
+            "premultiplied"
         } else {
-            w = size.height as f32 * self.aspect_ratio;
-            h = size.height as f32;
+            "straight"
+        };
+        let view = if self.alpha_view {
+            "alpha channel"
+        } else {
+            "image"
+        };
+        format!(
+            "alpha: {usage}
+detected: {straight}
+treated as: {treated_as}
+showing: {view}"
+        )
+    }
 
-            x_min = (size.width as f32 - w) / 2.0;
-            y_min = 0.0;
+    fn window_title(&self) -> String {
+        if let Some(template) = &self.title_template {
+            // The zoom level isn't known until the window exists.
+            let zoom = match &self.window {
+                Some(win) => format!("{:.0}", self.zoom_percent(win)),
+                None => "?".to_string(),
+            };
+            return template
+                .replace("{name}", &self.title)
+                .replace("{w}", &self.image_width.to_string())
+                .replace("{h}", &self.image_height.to_string())
+                .replace("{zoom}", &zoom);
         }
 
-        let min = vec2(x_min, y_min);
-        let max = min + vec2(w, h);
-        (min, max)
+        let app_name = env!("CARGO_PKG_NAME");
+        match self.entry_sizes.get(self.frame_index) {
+            Some((w, h)) => format!("{} ({w}x{h}) – {app_name}", self.title),
+            None => format!("{} – {app_name}", self.title),
+        }
     }
 
-    fn display_settings(&self, win: &Win) -> DisplaySettings {
-        let mut display_settings = DisplaySettings {
-            min_fb: vec2(0.0, 0.0),
-            max_fb: vec2(0.0, 0.0),
-            min_uv: self.min_uv,
-            max_uv: self.max_uv,
-            min_selection: vec2(0.0, 0.0),
-            max_selection: vec2(0.0, 0.0),
-            selection_color: SELECTION_COLOR,
-            checkerboard_a: vec4(0.0, 0.0, 0.0, 0.0),
-            checkerboard_b: vec4(0.0, 0.0, 0.0, 0.0),
-            checkerboard_res: CHECKERBOARD_CELL_SIZE,
-            force_linear: 0,
-            padding: Default::default(),
-        };
-
-        let (min, max) = self.fb_coord_range(win);
+    /// Returns the current zoom level in percent (100 = one image pixel per logical screen pixel,
+    /// like `--zoom`).
+    fn zoom_percent(&self, win: &Win) -> f32 {
+        let width = win.window.inner_size().width as f32 / win.window.scale_factor() as f32;
+        let view_width = (self.max_uv[0] - self.min_uv[0]) * self.image_width as f32;
+        width / view_width * 100.0
+    }
 
-        display_settings.min_fb = min;
-        display_settings.max_fb = max;
+    /// Creates a surface for `window` and finds a graphics adapter that can present to it.
+    ///
+    /// If none of the backends in `self.instance` has a usable adapter, this retries with a software
+    /// fallback adapter, and then (unless a backend was chosen with `--backend`) with the OpenGL
+    /// backend, before giving up.
+    fn open_adapter(&self, window: &Arc<Window>) -> (wgpu::Surface<'static>, wgpu::Adapter) {
+        let create_surface =
+            |instance: &wgpu::Instance| match instance.create_surface(window.clone()) {
+                Ok(surface) => surface,
+                Err(e) => {
+                    eprintln!("failed to create surface: {e}");
+                    process::exit(1);
+                }
+            };
+        let request_adapter =
+            |instance: &wgpu::Instance, surface: &wgpu::Surface<'_>, force_fallback_adapter| {
+                pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                    compatible_surface: Some(surface),
+                    power_preference: wgpu::PowerPreference::LowPower, // no need to spin up a dGPU for this workload
+                    force_fallback_adapter,
+                }))
+            };
 
-        let (min, max) = self.selection_region(win);
-        display_settings.min_selection = min;
-        display_settings.max_selection = max;
+        let surface = create_surface(&self.instance);
+        if let Some(adapter) = request_adapter(&self.instance, &surface, false) {
+            return (surface, adapter);
+        }
+        log::warn!(
+            "no adapter found for backends {:?}, trying a fallback adapter",
+            self.backends,
+        );
+        if let Some(adapter) = request_adapter(&self.instance, &surface, true) {
+            return (surface, adapter);
+        }
 
-        match self.transparency {
-            TransparencyMode::TrueTransparency => {
-                if self.cursor_pos.is_some() {
-                    // Partially transparent checkerboard while hovered.
-                    let a = CHECKERBOARD_LIGHT_A * CHECKERBOARD_HOVER_ALPHA;
-                    let b = CHECKERBOARD_LIGHT_B * CHECKERBOARD_HOVER_ALPHA;
-                    display_settings.checkerboard_a = vec4(a, a, a, CHECKERBOARD_HOVER_ALPHA);
-                    display_settings.checkerboard_b = vec4(b, b, b, CHECKERBOARD_HOVER_ALPHA);
-                } else {
-                    // Fully transparent.
-                    display_settings.checkerboard_a = vec4(0.0, 0.0, 0.0, 0.0);
-                    display_settings.checkerboard_b = vec4(0.0, 0.0, 0.0, 0.0);
+        if self.backends == DEFAULT_BACKENDS {
+            log::warn!("no fallback adapter found, trying the OpenGL backend");
+            let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+                backends: wgpu::Backends::GL,
+                ..Default::default()
+            });
+            let surface = create_surface(&instance);
+            for force_fallback_adapter in [false, true] {
+                if let Some(adapter) = request_adapter(&instance, &surface, force_fallback_adapter)
+                {
+                    return (surface, adapter);
                 }
             }
-            TransparencyMode::LightCheckerboard => {
-                let a = CHECKERBOARD_LIGHT_A;
-                let b = CHECKERBOARD_LIGHT_B;
-                display_settings.checkerboard_a = vec4(a, a, a, 1.0);
-                display_settings.checkerboard_b = vec4(b, b, b, 1.0);
-            }
-            TransparencyMode::DarkCheckerboard => {
-                let a = CHECKERBOARD_DARK_A;
-                let b = CHECKERBOARD_DARK_B;
-                display_settings.checkerboard_a = vec4(a, a, a, 1.0);
-                display_settings.checkerboard_b = vec4(b, b, b, 1.0);
-            }
         }
 
-        match self.filter {
-            FilterMode::Smart => display_settings.force_linear = 0,
-            FilterMode::Linear => display_settings.force_linear = 1,
+        eprintln!("could not open any compatible graphics device");
+        process::exit(1);
+    }
+
+    /// Returns the logical size the window opens with, which fits on `monitor`.
+    fn window_size(&self, monitor: Option<&MonitorHandle>) -> LogicalSize<u32> {
+        // Size of the part of the image that is initially visible, in image pixels.
+        let (view_width, view_height) = match self.initial_region {
+            Some((min, max)) => {
+                let range = max - min;
+                (
+                    (range[0] * self.image_width as f32).round() as u32,
+                    (range[1] * self.image_height as f32).round() as u32,
+                )
+            }
+            None => (self.image_width, self.image_height),
+        };
+        let view = LogicalSize::new(view_width, view_height);
+
+        // Compute initial window size; fit aspect ratio. This is done in logical pixels: image pixels
+        // are treated as logical pixels, so that 100% zoom looks the same regardless of the display's
+        // scale factor.
+        let mut size = sizing::initial_size(view, LogicalSize::new(WIN_WIDTH, WIN_HEIGHT));
+        log::debug!("logical window size: {}x{}", size.width, size.height);
+
+        if let Some(zoom) = self.initial_zoom {
+            // An explicit zoom level overrides the fitting logic.
+            size = sizing::zoomed_size(view, zoom);
+            log::debug!(
+                "logical window size: {}x{} at {zoom}% zoom",
+                size.width,
+                size.height
+            );
         }
 
-        display_settings
+        // There are no decorations, so an oversized window could not be dragged back into view.
+        if let Some(monitor) = monitor {
+            let available = monitor.size().to_logical::<f32>(monitor.scale_factor());
+            let clamped = sizing::shrink_to_fit(
+                size,
+                LogicalSize::new(
+                    available.width - MONITOR_MARGIN,
+                    available.height - MONITOR_MARGIN,
+                ),
+            );
+            if clamped != size {
+                size = clamped;
+                log::debug!(
+                    "logical window size: {}x{} after clamping to monitor ({}x{})",
+                    size.width,
+                    size.height,
+                    available.width,
+                    available.height,
+                );
+            }
+        }
+        size
     }
 
-    fn create_window(&self, event_loop: &ActiveEventLoop, images: Vec<image::RgbaImage>) -> Win {
-        // Compute initial window size; fit aspect ratio.
-        let s1 = PhysicalSize::new(
-            (WIN_HEIGHT as f32 * self.image_aspect_ratio).round() as u32,
-            WIN_HEIGHT,
-        );
-        let s2 = PhysicalSize::new(
-            WIN_WIDTH,
-            (WIN_WIDTH as f32 / self.image_aspect_ratio).round() as u32,
-        );
-        let fit_size = if s1.width > WIN_WIDTH || s1.height > WIN_HEIGHT {
-            s2
-        } else {
-            s1
+    fn create_window(&self, event_loop: &ActiveEventLoop, images: &[DynamicImage]) -> Win {
+        // Open the window on the monitor the user is looking at (well, pointing at), unless told
+        // otherwise, and make sure it fits on there.
+        let monitor = match self.monitor {
+            Some(index) => placement::nth_monitor(event_loop, index),
+            None => placement::cursor_monitor(event_loop),
         };
-
-        let mut size = fit_size;
-        size.width = cmp::min(size.width, self.image_width);
-        size.height = cmp::min(size.height, self.image_height);
-        log::debug!(
-            "window size: fit={}x{}, clamped={}x{}",
-            fit_size.width,
-            fit_size.height,
-            size.width,
-            size.height,
-        );
+        let size = self.window_size(monitor.as_ref());
 
         // Create Window.
-        let app_name = env!("CARGO_PKG_NAME");
+        // NB: the window level doesn't do anything on Wayland.
+        let level = if self.always_on_top {
+            WindowLevel::AlwaysOnTop
+        } else {
+            WindowLevel::Normal
+        };
+        let title = self.window_title();
         let res = event_loop.create_window(
             Window::default_attributes()
                 .with_inner_size(size)
-                .with_title(format!("{} – {app_name}", self.title))
+                .with_min_inner_size(LogicalSize::new(
+                    sizing::MIN_WINDOW_SIZE,
+                    sizing::MIN_WINDOW_SIZE,
+                ))
+                .with_title(&title)
                 .with_transparent(true)
                 .with_decorations(false)
-                .with_window_level(WindowLevel::AlwaysOnTop), // NB: doesn't work on Wayland
+                .with_window_level(level),
         );
         let window = match res {
             Ok(win) => Arc::new(win),
@@ -728,6 +4882,11 @@ impl App {
             }
         };
 
+        // NB: doesn't work on Wayland, where windows can't position themselves.
+        if let Some(monitor) = &monitor {
+            placement::center_on(&window, monitor);
+        }
+
         // Log backend info.
         match window.window_handle() {
             Ok(h) => {
@@ -754,26 +4913,8 @@ impl App {
             Err(e) => log::warn!("couldn't obtain window handle: {e}"),
         }
 
-        let surface = match self.instance.create_surface(window.clone()) {
-            Ok(surface) => surface,
-            Err(e) => {
-                eprintln!("failed to create surface: {e}");
-                process::exit(1);
-            }
-        };
-
         // Open GPU.
-        let adapter =
-            pollster::block_on(self.instance.request_adapter(&wgpu::RequestAdapterOptions {
-                compatible_surface: Some(&surface),
-                power_preference: wgpu::PowerPreference::LowPower, // no need to spin up a dGPU for this workload
-                ..Default::default()
-            }));
-
-        let Some(adapter) = adapter else {
-            eprintln!("could not open any compatible graphics device");
-            process::exit(1);
-        };
+        let (surface, adapter) = self.open_adapter(&window);
         let info = adapter.get_info();
         log::info!(
             "using {} via {} ({}) [api={}]",
@@ -781,369 +4922,181 @@ impl App {
             info.driver,
             info.driver_info,
             info.backend,
-        );
-        let surface_caps = surface.get_capabilities(&adapter);
-        log::debug!("supported surface formats: {:?}", surface_caps.formats);
-        log::debug!("supported present modes: {:?}", surface_caps.present_modes);
-        log::debug!("supported alpha modes: {:?}", surface_caps.alpha_modes);
-        let supports_alpha = surface_caps
-            .alpha_modes
-            .iter()
-            .any(|m| SUPPORTED_ALPHA_MODES.contains(m));
-        let surface_format = *surface_caps
-            .formats
-            .first()
-            .expect("adapter cannot render to surface");
-
-        let limits = adapter.limits();
-        log::debug!(
-            "limits: maxTextureDimension2D={}, maxBindGroups={}, maxBindingsPerBindGroup={}",
-            limits.max_texture_dimension_2d,
-            limits.max_bind_groups,
-            limits.max_bindings_per_bind_group,
-        );
-        log::debug!(
-            "compute limits: maxComputeWorkgroupStorageSize={}, \
-            maxComputeInvocationsPerWorkgroup={}, maxComputeWorkgroupsPerDimension={}, \
-            maxComputeWorkgroupSize=[{}, {}, {}]",
-            limits.max_compute_workgroup_storage_size,
-            limits.max_compute_invocations_per_workgroup,
-            limits.max_compute_workgroups_per_dimension,
-            limits.max_compute_workgroup_size_x,
-            limits.max_compute_workgroup_size_y,
-            limits.max_compute_workgroup_size_z,
-        );
-
-        if limits.max_texture_dimension_2d < self.image_height
-            || limits.max_texture_dimension_2d < self.image_width
-        {
-            exit_with_error(format!(
-                "Image size {}x{} exceeds maximum supported texture size {}x{}",
-                self.image_width,
-                self.image_height,
-                limits.max_texture_dimension_2d,
-                limits.max_texture_dimension_2d
-            ));
-        }
-
-        let res = pollster::block_on(adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                required_limits: wgpu::Limits::default().using_resolution(limits),
-                memory_hints: wgpu::MemoryHints::MemoryUsage,
-                ..Default::default()
-            },
-            None,
-        ));
-        let (device, queue) = match res {
-            Ok((dev, q)) => (dev, q),
-            Err(e) => {
-                eprintln!("failed to request graphics device: {e}");
-                process::exit(1);
-            }
-        };
-
-        // Create GPU resources.
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        });
-
-        // A single `ImageInfo` is bound to the shader for every frame; this computes a conservative
-        // result that takes all frames into account.
-        let image_info = device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: bytemuck::bytes_of(&ImageInfo::default()),
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
-        });
-        let preprocess_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: None,
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::StorageTexture {
-                        access: wgpu::StorageTextureAccess::WriteOnly,
-                        format: TEXTURE_FORMAT,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: false },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-            ],
-        });
-
-        const PREPROCESS_WORKGROUP_SIZE: u32 = 16;
-        let preprocess_pipeline =
-            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: None,
-                layout: Some(
-                    &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                        label: None,
-                        bind_group_layouts: &[&preprocess_bgl],
-                        push_constant_ranges: &[],
-                    }),
-                ),
-                module: &device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                    label: Some("preprocess.wgsl"),
-                    source: wgpu::ShaderSource::Wgsl(include_str!("preprocess.wgsl").into()),
-                }),
-                entry_point: Some("preprocess"),
-                compilation_options: wgpu::PipelineCompilationOptions {
-                    constants: &[(
-                        "WORKGROUP_SIZE".to_string(),
-                        PREPROCESS_WORKGROUP_SIZE as f64,
-                    )]
-                    .into(),
-                    zero_initialize_workgroup_memory: false,
-                },
-                cache: None,
-            });
-
-        let display_settings = device.create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size: mem::size_of::<DisplaySettings>() as _,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-        let display_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: None,
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-            ],
-        });
-
-        // Upload and preprocess frames.
-        let mut display_bind_groups = Vec::new();
-        let mut preprocess = Vec::new();
-        for image in &images {
-            let size = wgpu::Extent3d {
-                width: image.width(),
-                height: image.height(),
-                depth_or_array_layers: 1,
-            };
-            let input_format = wgpu::TextureFormat::Rgba8UnormSrgb;
-            let input_texture = device.create_texture(&wgpu::TextureDescriptor {
-                label: None,
-                size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: input_format,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
-            });
-            queue.write_texture(
-                input_texture.as_image_copy(),
-                image,
-                wgpu::TexelCopyBufferLayout {
-                    offset: 0,
-                    bytes_per_row: Some(4 * self.image_width),
-                    rows_per_image: None,
-                },
-                size,
-            );
-
-            let output_texture = device.create_texture(&wgpu::TextureDescriptor {
-                label: None,
-                size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: TEXTURE_FORMAT,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
-                view_formats: &[],
-            });
-            let preprocess_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: None,
-                layout: &preprocess_bgl,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(
-                            &input_texture.create_view(&Default::default()),
-                        ),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::TextureView(
-                            &output_texture.create_view(&Default::default()),
-                        ),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::Buffer(
-                            image_info.as_entire_buffer_binding(),
-                        ),
-                    },
-                ],
-            });
-            preprocess.push(preprocess_bind_group);
-
-            let display_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: None,
-                layout: &display_bgl,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::Sampler(&sampler),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::TextureView(
-                            &output_texture.create_view(&Default::default()),
-                        ),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::Buffer(
-                            display_settings.as_entire_buffer_binding(),
-                        ),
-                    },
-                ],
-            });
-
-            display_bind_groups.push(display_bind_group);
-        }
-
-        let mut enc = device.create_command_encoder(&Default::default());
-        let mut pass = enc.begin_compute_pass(&Default::default());
-        for (image, preprocess_bind_group) in images.iter().zip(&preprocess) {
-            let workgroups_x =
-                (image.width() + PREPROCESS_WORKGROUP_SIZE - 1) / PREPROCESS_WORKGROUP_SIZE;
-            let workgroups_y =
-                (image.height() + PREPROCESS_WORKGROUP_SIZE - 1) / PREPROCESS_WORKGROUP_SIZE;
-            pass.set_pipeline(&preprocess_pipeline);
-            pass.set_bind_group(0, preprocess_bind_group, &[]);
-            pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
-        }
-        drop(pass);
-
-        // Copy the computed image information to a staging buffer.
-        let image_info_dl = device.create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size: image_info.size(),
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-            mapped_at_creation: false,
-        });
-        enc.copy_buffer_to_buffer(&image_info, 0, &image_info_dl, 0, image_info.size());
-
-        let idx = queue.submit([enc.finish()]);
-
-        image_info_dl
-            .slice(..)
-            .map_async(wgpu::MapMode::Read, Result::unwrap);
-        device
-            .poll(wgpu::Maintain::wait_for(idx))
-            .panic_on_timeout();
+        );
+        let surface_caps = surface.get_capabilities(&adapter);
+        log::debug!("supported surface formats: {:?}", surface_caps.formats);
+        log::debug!("supported present modes: {:?}", surface_caps.present_modes);
+        log::debug!("supported alpha modes: {:?}", surface_caps.alpha_modes);
+        let supports_alpha = surface_caps
+            .alpha_modes
+            .iter()
+            .any(|m| SUPPORTED_ALPHA_MODES.contains(m));
+        let surface_format = choose_surface_format(&surface_caps.formats, supports_alpha)
+            .expect("adapter cannot render to surface");
+        log::debug!("using surface format {surface_format:?}");
 
-        let image_info: ImageInfo =
-            *bytemuck::from_bytes(&image_info_dl.slice(..).get_mapped_range());
+        let present_mode = self.choose_present_mode(&surface_caps.present_modes);
+        log::debug!("using present mode {present_mode:?}");
 
+        let limits = adapter.limits();
         log::debug!(
-            "left={} top={} right={} bottom={}",
-            image_info.left,
-            image_info.top,
-            image_info.right,
-            image_info.bottom,
+            "limits: maxTextureDimension2D={}, maxBindGroups={}, maxBindingsPerBindGroup={}",
+            limits.max_texture_dimension_2d,
+            limits.max_bind_groups,
+            limits.max_bindings_per_bind_group,
         );
         log::debug!(
-            "uses_alpha={} known_straight={}",
-            image_info.uses_alpha(),
-            image_info.known_straight(),
+            "compute limits: maxComputeWorkgroupStorageSize={}, \
+            maxComputeInvocationsPerWorkgroup={}, maxComputeWorkgroupsPerDimension={}, \
+            maxComputeWorkgroupSize=[{}, {}, {}]",
+            limits.max_compute_workgroup_storage_size,
+            limits.max_compute_invocations_per_workgroup,
+            limits.max_compute_workgroups_per_dimension,
+            limits.max_compute_workgroup_size_x,
+            limits.max_compute_workgroup_size_y,
+            limits.max_compute_workgroup_size_z,
         );
-        if image_info.uses_alpha() && !supports_alpha {
-            log::warn!(
-                "compositor does not support premultiplied alpha; using checkerboard background"
+
+        // Preprocessing writes to a storage texture, which the display pipeline then samples with
+        // filtering, so the format has to support both.
+        if !adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            exit_with_error(
+                format!(
+                    "The graphics device ({}, {}) does not support compute shaders",
+                    info.name, info.backend,
+                ),
+                EXIT_ERROR,
             );
         }
-        if image_info.uses_partial_alpha() && !image_info.known_straight() {
-            log::warn!("image uses alpha channel, but may already be premultiplied; artifacts are possible");
-        }
-
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("display.wgsl"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("display.wgsl").into()),
+        let adapter_specific = adapter
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+        let usable = |features: wgpu::TextureFormatFeatures| {
+            features.allowed_usages.contains(
+                wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            ) && features
+                .flags
+                .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE)
+        };
+        // Formats outside of the guaranteed feature set require opting into adapter-specific
+        // format features.
+        let texture_format = TEXTURE_FORMATS.iter().copied().find_map(|format| {
+            if usable(format.guaranteed_format_features(adapter.features())) {
+                Some((format, false))
+            } else if adapter_specific && usable(adapter.get_texture_format_features(format)) {
+                Some((format, true))
+            } else {
+                None
+            }
         });
-        let display_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(
-                &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                    label: None,
-                    bind_group_layouts: &[&display_bgl],
-                    push_constant_ranges: &[],
-                }),
+        let Some((texture_format, needs_adapter_specific)) = texture_format else {
+            exit_with_error(
+                format!(
+                "The graphics device ({}, {}) does not support any of the texture formats {:?} \
+                as storage textures",
+                info.name, info.backend, TEXTURE_FORMATS,
             ),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vertex"),
-                compilation_options: Default::default(),
-                buffers: &[],
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                EXIT_ERROR,
+            );
+        };
+        if texture_format == TEXTURE_FORMATS[0] {
+            log::debug!("using {texture_format:?} for preprocessed images");
+        } else {
+            log::warn!(
+                "{:?} storage textures are not supported, falling back to {texture_format:?}",
+                TEXTURE_FORMATS[0],
+            );
+        }
+        let required_features = if needs_adapter_specific {
+            wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+        } else {
+            wgpu::Features::empty()
+        };
+
+        let res = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                required_features,
+                required_limits: wgpu::Limits {
+                    max_texture_array_layers: limits.max_texture_array_layers,
+                    ..wgpu::Limits::default().using_resolution(limits)
+                },
+                memory_hints: wgpu::MemoryHints::MemoryUsage,
                 ..Default::default()
             },
-            depth_stencil: None,
-            multisample: Default::default(),
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fragment"),
-                compilation_options: Default::default(),
-                targets: &[Some(wgpu::ColorTargetState::from(surface_format))],
-            }),
-            multiview: None,
-            cache: None,
+            None,
+        ));
+        let (device, queue) = match res {
+            Ok((dev, q)) => (dev, q),
+            Err(e) => {
+                eprintln!("failed to request graphics device: {e}");
+                process::exit(1);
+            }
+        };
+
+        // Use the highest supported MSAA sample count that doesn't exceed the configured one.
+        let format_features = if device
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+        {
+            adapter.get_texture_format_features(surface_format)
+        } else {
+            surface_format.guaranteed_format_features(device.features())
+        };
+        let sample_count = [4, 2, 1]
+            .into_iter()
+            .find(|&count| {
+                count <= self.config.borrow().msaa_samples
+                    && format_features.flags.sample_count_supported(count)
+            })
+            .unwrap_or(1);
+        if sample_count != self.config.borrow().msaa_samples {
+            log::warn!(
+                "{}x MSAA is not supported, using {sample_count}x instead",
+                self.config.borrow().msaa_samples,
+            );
+        }
+        log::debug!("using {sample_count}x MSAA");
+
+        // Create GPU resources.
+        let display_settings = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: mem::size_of::<DisplaySettings>() as _,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         });
+        let uploader = Uploader::new(
+            &device,
+            &queue,
+            texture_format,
+            self.background_image.as_deref(),
+        );
+        let textures = upload_images(
+            &device,
+            &queue,
+            &uploader,
+            &display_settings,
+            images,
+            self.premultiplied,
+        )
+        .unwrap_or_else(|e| exit_with_error(format!("{e:#}"), EXIT_ERROR));
+
+        let display_pipeline =
+            create_display_pipeline(&device, &uploader, surface_format, sample_count);
+
+        let overlay = OverlayRenderer::new(
+            &device,
+            &queue,
+            surface_format,
+            &output_constants(surface_format),
+            sample_count,
+        );
 
         let win = Win {
             supports_alpha,
-            image_info,
             window,
             surface,
             adapter,
@@ -1151,12 +5104,59 @@ impl App {
             queue,
             display_pipeline,
             display_settings,
-            display_bind_groups,
+            uploader,
+            textures,
+            overlay,
+            present_mode,
+            surface_format,
+            sample_count,
+            msaa_target: RefCell::new(None),
+            last_frame: RefCell::new(None),
+            frame_times: RefCell::new(VecDeque::with_capacity(FPS_SAMPLES)),
+            title: RefCell::new(title),
         };
         self.recreate_swapchain(&win);
         win
     }
 
+    /// Picks the present mode for the displayed image out of the `supported` ones.
+    fn choose_present_mode(&self, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        // Animations are presented with vsync to avoid tearing. Static images are only redrawn in
+        // response to user input, so we prefer the lower latency of `Mailbox` there.
+        let preferred_modes: &[wgpu::PresentMode] = if !self.vsync {
+            &[wgpu::PresentMode::Immediate, wgpu::PresentMode::Mailbox]
+        } else if self.animated {
+            &[wgpu::PresentMode::Fifo]
+        } else {
+            &[wgpu::PresentMode::Mailbox]
+        };
+        preferred_modes
+            .iter()
+            .copied()
+            .find(|mode| supported.contains(mode))
+            .unwrap_or(wgpu::PresentMode::Fifo)
+    }
+
+    /// Switches to the present mode for the displayed image, if that changed because the new image
+    /// is animated and the old one wasn't, or vice versa.
+    fn update_present_mode(&mut self) {
+        let Some(win) = &self.window else { return };
+        let supported = win.surface.get_capabilities(&win.adapter).present_modes;
+        let present_mode = self.choose_present_mode(&supported);
+        let Some(win) = self
+            .window
+            .as_mut()
+            .filter(|win| win.present_mode != present_mode)
+        else {
+            return;
+        };
+        log::debug!("switching to present mode {present_mode:?}");
+        win.present_mode = present_mode;
+        if let Some(win) = &self.window {
+            self.recreate_swapchain(win);
+        }
+    }
+
     fn recreate_swapchain(&self, win: &Win) {
         let res = win.window.inner_size();
 
@@ -1165,6 +5165,8 @@ impl App {
             .surface
             .get_default_config(&win.adapter, res.width, res.height)
             .expect("adapter does not support surface");
+        config.present_mode = win.present_mode;
+        config.format = win.surface_format;
 
         for mode in SUPPORTED_ALPHA_MODES {
             if caps.alpha_modes.contains(mode) {
@@ -1183,6 +5185,25 @@ impl App {
         );
 
         win.surface.configure(&win.device, &config);
+
+        *win.msaa_target.borrow_mut() = (win.sample_count > 1).then(|| {
+            win.device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("msaa_target"),
+                    size: wgpu::Extent3d {
+                        width: config.width,
+                        height: config.height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: win.sample_count,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: config.format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                })
+                .create_view(&Default::default())
+        });
     }
 
     fn redraw(&self, win: &Win) {
@@ -1201,18 +5222,26 @@ impl App {
         };
         let view = st.texture.create_view(&Default::default());
 
-        let display_settings = self.display_settings(win);
+        let state = self.frame_state(win);
+        let display_settings = state.display_settings;
         win.queue.write_buffer(
             &win.display_settings,
             0,
             bytemuck::bytes_of(&display_settings),
         );
 
+        // When multisampling, render into the MSAA target and resolve into the surface texture.
+        let msaa_target = win.msaa_target.borrow();
+        let (target, resolve_target) = match &*msaa_target {
+            Some(msaa) => (msaa, Some(&view)),
+            None => (&view, None),
+        };
+
         let mut enc = win.device.create_command_encoder(&Default::default());
         let mut pass = enc.begin_render_pass(&wgpu::RenderPassDescriptor {
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
-                resolve_target: None,
+                view: target,
+                resolve_target,
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
                     store: wgpu::StoreOp::Store,
@@ -1220,18 +5249,145 @@ impl App {
             })],
             ..Default::default()
         });
-        pass.set_pipeline(&win.display_pipeline);
-        pass.set_bind_group(0, &win.display_bind_groups[self.frame_index], &[]);
-        pass.draw(0..4, 0..1);
+        // Nothing sensible can be displayed until the initial region is known.
+        let preprocessing = win.textures.readback.is_some();
+        if !preprocessing {
+            pass.set_pipeline(&win.display_pipeline);
+            pass.set_bind_group(0, &win.textures.display_bind_group, &[]);
+            pass.draw(0..4, 0..1);
+        }
+        let size = win.window.inner_size();
+        win.overlay
+            .draw(&win.device, &mut pass, &state.overlay, size);
         drop(pass);
+        *win.last_frame.borrow_mut() = Some(state);
 
         win.queue.submit([enc.finish()]);
         win.window.pre_present_notify();
         st.present();
+
+        if self.show_fps {
+            let mut frame_times = win.frame_times.borrow_mut();
+            if frame_times.len() == FPS_SAMPLES {
+                frame_times.pop_front();
+            }
+            frame_times.push_back(Instant::now());
+        }
+    }
+}
+
+/// Adds a short bar sweeping back and forth across the middle of the window to `overlay`, for
+/// something that started at `start`.
+fn scanning_indicator(overlay: &mut Overlay, size: PhysicalSize<u32>, start: Instant) {
+    let width = size.width as f32 * SCANNING_INDICATOR_WIDTH;
+    let t = start.elapsed().as_secs_f32() / SCANNING_INDICATOR_PERIOD;
+    let t = 1.0 - (1.0 - (t.fract() * 2.0)).abs();
+    let min = vec2(
+        (size.width as f32 - width) * t,
+        (size.height as f32 - PROGRESS_BAR_HEIGHT) * 0.5,
+    );
+    overlay.rect(
+        min,
+        min + vec2(width, PROGRESS_BAR_HEIGHT),
+        PROGRESS_BAR_COLOR,
+    );
+}
+
+/// Returns the two (linear, premultiplied) colors of the checkerboard shown behind transparent
+/// areas in `mode`, while the window is `hovered` or not.
+fn checkerboard_colors(mode: TransparencyMode, hovered: bool) -> (Vec4f, Vec4f) {
+    match mode {
+        // Partially transparent checkerboard while hovered.
+        TransparencyMode::TrueTransparency if hovered => (
+            premultiplied_gray(CHECKERBOARD_LIGHT_A, CHECKERBOARD_HOVER_ALPHA),
+            premultiplied_gray(CHECKERBOARD_LIGHT_B, CHECKERBOARD_HOVER_ALPHA),
+        ),
+        // Fully transparent.
+        TransparencyMode::TrueTransparency => (vec4(0.0, 0.0, 0.0, 0.0), vec4(0.0, 0.0, 0.0, 0.0)),
+        TransparencyMode::LightCheckerboard => (
+            premultiplied_gray(CHECKERBOARD_LIGHT_A, 1.0),
+            premultiplied_gray(CHECKERBOARD_LIGHT_B, 1.0),
+        ),
+        TransparencyMode::DarkCheckerboard => (
+            premultiplied_gray(CHECKERBOARD_DARK_A, 1.0),
+            premultiplied_gray(CHECKERBOARD_DARK_B, 1.0),
+        ),
+    }
+}
+
+/// Returns the linear gray level `level` with `alpha`, premultiplied.
+///
+/// Like everything the display shader blends, this is premultiplied in linear space; the shader
+/// converts its output for the compositor.
+fn premultiplied_gray(level: f32, alpha: f32) -> Vec4f {
+    let c = level * alpha;
+    vec4(c, c, c, alpha)
+}
+
+/// Remembers the displayed region `min`-`max`, so that [`Action::UndoZoom`] can return to it.
+fn push_zoom_history(history: &mut VecDeque<(Vec2f, Vec2f)>, min: Vec2f, max: Vec2f) {
+    let range = max - min;
+    // Nothing is displayed yet during startup.
+    if range[0] <= 0.0 || range[1] <= 0.0 {
+        return;
+    }
+    if history.len() == ZOOM_HISTORY_MAX {
+        history.pop_front();
+    }
+    history.push_back((min, max));
+}
+
+/// Maps the set of edges the cursor is close to (north, east, south, west) to a resize direction.
+fn resize_direction(n: bool, e: bool, s: bool, w: bool) -> Option<ResizeDirection> {
+    Some(match (n, e, s, w) {
+        (false, false, false, false) => return None,
+        (true, false, false, false) => ResizeDirection::North,
+        (true, true, false, false) => ResizeDirection::NorthEast,
+        (true, false, false, true) => ResizeDirection::NorthWest,
+        (false, false, true, false) => ResizeDirection::South,
+        (false, true, true, false) => ResizeDirection::SouthEast,
+        (false, false, true, true) => ResizeDirection::SouthWest,
+        (false, true, false, false) => ResizeDirection::East,
+        (false, false, false, true) => ResizeDirection::West,
+        // Ambiguous cases. These can happen when the area is so small that the resize borders
+        // overlap. Result is mostly arbitrary.
+        (false, true, true, true) => ResizeDirection::South,
+        (false, true, false, true) => ResizeDirection::West,
+        (true, false, true, _) => ResizeDirection::South,
+        (true, true, true, _) => ResizeDirection::SouthEast,
+        (true, true, false, true) => ResizeDirection::NorthEast,
+    })
+}
+
+fn to_vec2f(pos: PhysicalPosition<f64>) -> Vec2f {
+    vec2(pos.x as f32, pos.y as f32)
+}
+
+fn distance(a: PhysicalPosition<f64>, b: PhysicalPosition<f64>) -> f64 {
+    f64::hypot(a.x - b.x, a.y - b.y)
+}
+
+/// Moves the UV region `min`-`max` by `delta`, without moving it past the edges of the image.
+fn pan_region(min: Vec2f, max: Vec2f, delta: Vec2f) -> (Vec2f, Vec2f) {
+    let mut delta = delta;
+    for axis in 0..2 {
+        delta[axis] = delta[axis].clamp(-min[axis], 1.0 - max[axis]);
+        if max[axis] - min[axis] >= 1.0 {
+            // Not zoomed in along this axis, nothing to pan.
+            delta[axis] = 0.0;
+        }
     }
+    (min + delta, max + delta)
+}
+
+/// Everything that determines the contents of a rendered frame, apart from the window size.
+#[derive(PartialEq)]
+struct FrameState {
+    display_settings: DisplaySettings,
+    overlay: Overlay,
 }
 
-#[derive(Debug, Clone, Copy, bytemuck::NoUninit)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::NoUninit)]
 #[repr(C)]
 struct DisplaySettings {
     min_fb: Vec2f,
@@ -1244,8 +5400,26 @@ struct DisplaySettings {
     checkerboard_a: Vec4f,
     checkerboard_b: Vec4f,
     checkerboard_res: u32,
-    force_linear: u32,
-    padding: [u32; 2],
+    filter_mode: u32,
+    loupe_center_fb: Vec2f,
+    loupe_center_uv: Vec2f,
+    loupe_radius: f32,
+    loupe_zoom: f32,
+    outline: u32,
+    frame: u32,
+    grid: u32,
+    dither: u32,
+    min_grid: Vec2f,
+    max_grid: Vec2f,
+    alpha_view: u32,
+    lanczos: u32,
+    selection_outline: u32,
+    checkerboard_zoom: u32,
+    pixel_grid: u32,
+    corner_radius: f32,
+    window_size: Vec2f,
+    background_image: u32,
+    _padding: [u32; 3],
 }
 
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
@@ -1276,6 +5450,38 @@ impl Default for ImageInfo {
 }
 
 impl ImageInfo {
+    /// Converts the content bounds from pixels of a texture of size `from` to pixels of an image of
+    /// size `to`, rounding outwards.
+    fn rescaled(&self, from: (u32, u32), to: (u32, u32)) -> Self {
+        if from == to || self.top == u32::MAX {
+            return *self;
+        }
+        let scale = |c: u32, from: u32, to: u32| u64::from(c) * u64::from(to) / u64::from(from);
+        let scale_end = |c: u32, from: u32, to: u32| {
+            ((u64::from(c) + 1) * u64::from(to)).div_ceil(u64::from(from)) - 1
+        };
+        Self {
+            top: scale(self.top, from.1, to.1) as u32,
+            right: scale_end(self.right, from.0, to.0) as u32,
+            bottom: scale_end(self.bottom, from.1, to.1) as u32,
+            left: scale(self.left, from.0, to.0) as u32,
+            ..*self
+        }
+    }
+
+    /// Combines the information about two frames into a conservative result for both.
+    fn union(&self, other: &Self) -> Self {
+        Self {
+            uses_alpha: self.uses_alpha | other.uses_alpha,
+            uses_partial_alpha: self.uses_partial_alpha | other.uses_partial_alpha,
+            known_straight: self.known_straight | other.known_straight,
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+            left: self.left.min(other.left),
+        }
+    }
+
     fn uses_alpha(&self) -> bool {
         self.uses_alpha != 0
     }
@@ -1288,3 +5494,340 @@ impl ImageInfo {
         self.known_straight != 0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Opens a device on whatever adapter is available (including software renderers), or returns
+    /// `None` if there is none.
+    fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+        let desc = wgpu::DeviceDescriptor {
+            required_limits: adapter.limits(),
+            ..Default::default()
+        };
+        pollster::block_on(adapter.request_device(&desc, None)).ok()
+    }
+
+    /// Renders a single row of linear, premultiplied `pixels` at 100% zoom into a `format` target,
+    /// and returns the texels.
+    fn render(
+        (device, queue): &(wgpu::Device, wgpu::Queue),
+        format: wgpu::TextureFormat,
+        pixels: &RgbaImage,
+        hovered: bool,
+    ) -> Vec<[u8; 4]> {
+        let width = pixels.width();
+        let uploader = Uploader::new(device, queue, wgpu::TextureFormat::Rgba8Unorm, None);
+        let display_settings = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: mem::size_of::<DisplaySettings>() as _,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // Uploaded as-is, instead of going through `upload_images`: the pixels are premultiplied
+        // already, and 8-bit values are linear in the `Rgba8Unorm` texture. There are two layers,
+        // since the OpenGL backend can't sample single-layer textures as arrays.
+        let size = wgpu::Extent3d {
+            width,
+            height: 1,
+            depth_or_array_layers: 2,
+        };
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: None,
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: uploader.texture_format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &[pixels.as_raw().as_slice(), pixels.as_raw()].concat(),
+        );
+        let bind_group = display_bind_group(device, &uploader, &texture, None, &display_settings);
+        let pipeline = create_display_pipeline(device, &uploader, format, 1);
+
+        let (checkerboard_a, _) = checkerboard_colors(TransparencyMode::TrueTransparency, hovered);
+        let settings = DisplaySettings {
+            min_fb: vec2(0.0, 0.0),
+            max_fb: vec2(width as f32, 1.0),
+            min_uv: vec2(0.0, 0.0),
+            max_uv: vec2(1.0, 1.0),
+            min_selection: vec2(0.0, 0.0),
+            max_selection: vec2(0.0, 0.0),
+            selection_color: vec4(0.0, 0.0, 0.0, 0.0),
+            // Both colors are the same, so that the result doesn't depend on the cell size.
+            checkerboard_a,
+            checkerboard_b: checkerboard_a,
+            checkerboard_res: CHECKERBOARD_CELL_SIZE,
+            filter_mode: 2,
+            loupe_center_fb: vec2(0.0, 0.0),
+            loupe_center_uv: vec2(0.0, 0.0),
+            loupe_radius: 0.0,
+            loupe_zoom: 1.0,
+            outline: 0,
+            frame: 0,
+            grid: GridMode::Off as u32,
+            dither: 0,
+            min_grid: vec2(0.0, 0.0),
+            max_grid: vec2(1.0, 1.0),
+            alpha_view: 0,
+            lanczos: 0,
+            selection_outline: 0,
+            checkerboard_zoom: 0,
+            pixel_grid: 0,
+            corner_radius: 0.0,
+            window_size: vec2(width as f32, 1.0),
+            background_image: 0,
+            _padding: [0; 3],
+        };
+        queue.write_buffer(&display_settings, 0, bytemuck::bytes_of(&settings));
+
+        let extent = wgpu::Extent3d {
+            width,
+            height: 1,
+            depth_or_array_layers: 1,
+        };
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: u64::from(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut enc = device.create_command_encoder(&Default::default());
+        let mut pass = enc.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target.create_view(&Default::default()),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.draw(0..4, 0..1);
+        drop(pass);
+        enc.copy_texture_to_buffer(
+            target.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: None,
+                },
+            },
+            extent,
+        );
+        queue.submit([enc.finish()]);
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, |r| r.unwrap());
+        device.poll(wgpu::Maintain::Wait);
+
+        let data = buffer.slice(..).get_mapped_range();
+        data.chunks(4)
+            .take(width as usize)
+            .map(|texel| texel.try_into().unwrap())
+            .collect()
+    }
+
+    /// Converts a linear, premultiplied color to what the compositor should get: sRGB-encoded
+    /// values, premultiplied in sRGB space.
+    fn for_compositor([r, g, b, a]: [f32; 4]) -> [u8; 4] {
+        let encode = |c: f32| {
+            let straight = if a == 0.0 { 0.0 } else { c / a };
+            (math::linear_to_srgb(straight) * a * 255.0).round() as u8
+        };
+        [encode(r), encode(g), encode(b), (a * 255.0).round() as u8]
+    }
+
+    fn assert_close(actual: &[[u8; 4]], expected: &[[u8; 4]]) {
+        let close = actual.iter().zip(expected).all(|(a, e)| {
+            a.iter()
+                .zip(e)
+                .all(|(&a, &e)| (i16::from(a) - i16::from(e)).abs() <= 2)
+        });
+        assert!(close, "expected {expected:?}, got {actual:?}");
+    }
+
+    #[test]
+    fn hovered_checkerboard_is_premultiplied_for_the_compositor() {
+        let Some(device) = device() else {
+            eprintln!("no graphics adapter available, skipping");
+            return;
+        };
+
+        // Fully transparent, half-transparent red, and opaque green.
+        let pixels =
+            RgbaImage::from_raw(3, 1, vec![0, 0, 0, 0, 128, 0, 0, 128, 0, 255, 0, 255]).unwrap();
+        let red_alpha = 128.0 / 255.0;
+
+        let (level, alpha) = (CHECKERBOARD_LIGHT_A, CHECKERBOARD_HOVER_ALPHA);
+        let gray = [level * alpha, level * alpha, level * alpha, alpha];
+        let behind_red = 1.0 - red_alpha;
+        let hovered_expected = [
+            for_compositor(gray),
+            for_compositor([
+                red_alpha + gray[0] * behind_red,
+                gray[1] * behind_red,
+                gray[2] * behind_red,
+                red_alpha + gray[3] * behind_red,
+            ]),
+            [0, 255, 0, 255],
+        ];
+        let unhovered_expected = [
+            [0, 0, 0, 0],
+            for_compositor([red_alpha, 0.0, 0.0, red_alpha]),
+            [0, 255, 0, 255],
+        ];
+
+        // The surface encodes the output to sRGB itself with `-Srgb` formats, and the shader does
+        // it otherwise; the compositor has to see the same values either way.
+        for format in [
+            wgpu::TextureFormat::Rgba8Unorm,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        ] {
+            let hovered = render(&device, format, &pixels, true);
+            let unhovered = render(&device, format, &pixels, false);
+            assert_close(&hovered, &hovered_expected);
+            assert_close(&unhovered, &unhovered_expected);
+            assert_eq!(
+                hovered[2], unhovered[2],
+                "opaque pixels don't change on hover"
+            );
+        }
+    }
+
+    /// Downscales a square texture of `size` texels to `width`x`height`, and returns the texels.
+    /// Every mip level is filled with `texel(u)` (where `u` is the horizontal texture coordinate of
+    /// the texel center), instead of being computed by `MipmapGenerator`.
+    ///
+    /// The texture has a single layer, since the OpenGL backend can't view a layer of an array
+    /// texture on its own.
+    fn downscale(
+        (device, queue): &(wgpu::Device, wgpu::Queue),
+        size: u32,
+        texel: impl Fn(f32) -> [u8; 4],
+        (width, height): (u32, u32),
+    ) -> Vec<[u8; 4]> {
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let extent = wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        };
+        let mip_level_count = extent.max_mips(wgpu::TextureDimension::D2);
+        let mut data = Vec::new();
+        for level in 0..mip_level_count {
+            let level_size = (size >> level).max(1);
+            for _ in 0..level_size {
+                for x in 0..level_size {
+                    data.extend(texel((x as f32 + 0.5) / level_size as f32));
+                }
+            }
+        }
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: None,
+                size: extent,
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &data,
+        );
+        let downscaled =
+            Downscaler::new(device, format).downscale(device, queue, &texture, 0, width, height);
+        assert_eq!((downscaled.width(), downscaled.height()), (width, height));
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: u64::from(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT * height),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut enc = device.create_command_encoder(&Default::default());
+        enc.copy_texture_to_buffer(
+            downscaled.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: None,
+                },
+            },
+            downscaled.size(),
+        );
+        queue.submit([enc.finish()]);
+        buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+        let mapped = buffer.slice(..).get_mapped_range();
+        mapped
+            .chunks(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize)
+            .flat_map(|row| row[..width as usize * 4].chunks(4))
+            .map(|texel| texel.try_into().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn downscaling_keeps_flat_colors() {
+        let Some(device) = device() else {
+            eprintln!("no graphics adapter available, skipping");
+            return;
+        };
+        let color = [128, 64, 32, 255];
+        for size in [(3, 3), (5, 5), (8, 8)] {
+            let texels = downscale(&device, 16, |_| color, size);
+            assert_close(&texels, &vec![color; texels.len()]);
+        }
+    }
+
+    #[test]
+    fn downscaled_hard_edges_stay_premultiplied() {
+        let Some(device) = device() else {
+            eprintln!("no graphics adapter available, skipping");
+            return;
+        };
+        // The negative lobes of the kernel overshoot on both sides of the edge.
+        let texels = downscale(
+            &device,
+            16,
+            |u| if u < 0.5 { [255; 4] } else { [0; 4] },
+            (6, 6),
+        );
+        for [r, g, b, a] in &texels {
+            assert!(r.max(g).max(b) <= a, "{texels:?} is not premultiplied");
+        }
+        assert_close(&texels[..1], &[[255; 4]]);
+        assert_close(&texels[5..6], &[[0; 4]]);
+    }
+}
@@ -35,6 +35,16 @@ impl<const N: usize> Vec<f32, N> {
     pub fn normalize(self) -> Self {
         self / self.length()
     }
+
+    /// Component-wise minimum.
+    pub fn min(self, other: Self) -> Self {
+        Vec(array::from_fn(|i| self.0[i].min(other.0[i])))
+    }
+
+    /// Component-wise maximum.
+    pub fn max(self, other: Self) -> Self {
+        Vec(array::from_fn(|i| self.0[i].max(other.0[i])))
+    }
 }
 
 // Safety: `[T; N]` has no padding iff `T` has no padding.
@@ -116,6 +126,17 @@ where
     }
 }
 
+impl<T, const N: usize> Div<Vec<T, N>> for Vec<T, N>
+where
+    T: Div<Output = T> + Copy,
+{
+    type Output = Vec<T, N>;
+
+    fn div(self, rhs: Vec<T, N>) -> Self::Output {
+        Vec(array::from_fn(|i| self.0[i] / rhs.0[i]))
+    }
+}
+
 impl<T, const N: usize> Div<T> for Vec<T, N>
 where
     T: Div<Output = T> + Copy,
@@ -148,3 +169,21 @@ pub const fn vec2<T>(x: T, y: T) -> Vec2<T> {
 pub const fn vec4<T>(x: T, y: T, z: T, w: T) -> Vec4<T> {
     Vec([x, y, z, w])
 }
+
+/// Encodes a linear color channel value with the sRGB transfer function.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decodes an sRGB-encoded color channel value to a linear one.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
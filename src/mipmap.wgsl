@@ -0,0 +1,30 @@
+// Mipmap generation.
+// Computes one mip level from the previous one by averaging 2x2 texel blocks. The texels are
+// premultiplied at this point, so a plain average is correct.
+
+@group(0) @binding(0)
+var src: texture_2d<f32>;
+
+@group(0) @binding(1)
+var dst: texture_storage_2d<rgba16float, write>;
+
+override WORKGROUP_SIZE: u32 = 16;
+
+@compute
+@workgroup_size(WORKGROUP_SIZE, WORKGROUP_SIZE)
+fn downsample(@builtin(global_invocation_id) id: vec3u) {
+    if any(id.xy >= textureDimensions(dst)) {
+        return;
+    }
+
+    // Odd sizes make the last row/column of the source texture contribute twice.
+    let src_max = textureDimensions(src) - vec2(1u);
+    let base = id.xy * 2u;
+    var sum = vec4(0.0);
+    for (var y = 0u; y < 2u; y++) {
+        for (var x = 0u; x < 2u; x++) {
+            sum += textureLoad(src, min(base + vec2(x, y), src_max), 0);
+        }
+    }
+    textureStore(dst, id.xy, sum / 4.0);
+}
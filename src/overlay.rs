@@ -0,0 +1,445 @@
+//! Text and rectangle overlays drawn on top of the image.
+
+use std::collections::HashMap;
+
+use bytemuck::NoUninit;
+use font8x8::legacy::BASIC_LEGACY;
+use image::RgbaImage;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use winit::dpi::PhysicalSize;
+
+use crate::math::{vec2, vec4, Vec2f, Vec4f};
+
+/// Width and height of a glyph in the embedded font (in texels).
+const GLYPH_SIZE: u32 = 8;
+/// Number of glyph cells per row in the atlas texture.
+const ATLAS_COLUMNS: u32 = 16;
+const ATLAS_ROWS: u32 = BASIC_LEGACY.len() as u32 / ATLAS_COLUMNS;
+/// Atlas cell that is filled completely, used for drawing solid rectangles (this is DEL, which has
+/// no glyph).
+const SOLID_CELL: u32 = 0x7f;
+
+/// Maximum width and height of a thumbnail (in texels and screen pixels).
+pub const THUMBNAIL_SIZE: u32 = 64;
+/// Number of thumbnail slots per row and column of the thumbnail atlas.
+const THUMBNAIL_ATLAS_CELLS: u32 = 16;
+/// Number of thumbnails the thumbnail atlas can hold.
+pub const THUMBNAIL_SLOTS: u32 = THUMBNAIL_ATLAS_CELLS * THUMBNAIL_ATLAS_CELLS;
+
+/// Scale factor applied to the glyphs when drawing text.
+pub const TEXT_SCALE: f32 = 2.0;
+/// Vertical distance between lines of text (in screen pixels).
+pub const LINE_HEIGHT: f32 = 10.0 * TEXT_SCALE;
+/// Padding between the text of a label and the edge of its background panel.
+pub const LABEL_PADDING: f32 = 6.0;
+
+pub const TEXT_COLOR: Vec4f = vec4(1.0, 1.0, 1.0, 1.0);
+pub const PANEL_COLOR: Vec4f = vec4(0.0, 0.0, 0.0, 0.6);
+
+#[derive(Debug, Clone, Copy, PartialEq, NoUninit)]
+#[repr(C)]
+struct Quad {
+    /// Position of the top left corner.
+    origin: Vec2f,
+    /// Vectors from the top left corner to the top right and bottom left corners.
+    x_axis: Vec2f,
+    y_axis: Vec2f,
+    min_uv: Vec2f,
+    max_uv: Vec2f,
+    color: Vec4f,
+    /// Texture to sample: 0 = glyph atlas, 1 = thumbnail atlas.
+    source: u32,
+}
+
+/// A list of shapes to draw on top of the image, in window pixel coordinates.
+#[derive(Default, PartialEq)]
+pub struct Overlay {
+    quads: Vec<Quad>,
+}
+
+impl Overlay {
+    pub fn is_empty(&self) -> bool {
+        self.quads.is_empty()
+    }
+
+    /// Draws a solid rectangle.
+    pub fn rect(&mut self, min: Vec2f, max: Vec2f, color: Vec4f) {
+        let size = max - min;
+        self.solid_quad(min, vec2(size[0], 0.0), vec2(0.0, size[1]), color);
+    }
+
+    /// Draws a border of the given `width` around the rectangle `min`-`max`.
+    pub fn frame(&mut self, min: Vec2f, max: Vec2f, width: f32, color: Vec4f) {
+        let (outer_min, outer_max) = (min - vec2(width, width), max + vec2(width, width));
+        self.rect(outer_min, vec2(outer_max[0], min[1]), color);
+        self.rect(vec2(outer_min[0], max[1]), outer_max, color);
+        self.rect(vec2(outer_min[0], min[1]), vec2(min[0], max[1]), color);
+        self.rect(vec2(max[0], min[1]), vec2(outer_max[0], max[1]), color);
+    }
+
+    /// Draws a straight line of the given `width` from `from` to `to`.
+    pub fn line(&mut self, from: Vec2f, to: Vec2f, width: f32, color: Vec4f) {
+        let dir = to - from;
+        if dir.length() == 0.0 {
+            return;
+        }
+        let normal = vec2(-dir[1], dir[0]).normalize() * width;
+        self.solid_quad(from - normal * 0.5, dir, normal, color);
+    }
+
+    fn solid_quad(&mut self, origin: Vec2f, x_axis: Vec2f, y_axis: Vec2f, color: Vec4f) {
+        let uv = cell_uv(SOLID_CELL, vec2(0.5, 0.5));
+        self.quads.push(Quad {
+            origin,
+            x_axis,
+            y_axis,
+            min_uv: uv,
+            max_uv: uv,
+            color,
+            source: 0,
+        });
+    }
+
+    /// Draws a (possibly multi-line) string with its top left corner at `pos`.
+    pub fn text(&mut self, pos: Vec2f, text: &str, color: Vec4f) {
+        let glyph = GLYPH_SIZE as f32 * TEXT_SCALE;
+        for (line_index, line) in text.lines().enumerate() {
+            let y = pos[1] + line_index as f32 * LINE_HEIGHT;
+            for (i, ch) in line.chars().enumerate() {
+                let cell = if ch.is_ascii() { ch as u32 } else { '?' as u32 };
+                if ch == ' ' {
+                    continue;
+                }
+                self.quads.push(Quad {
+                    origin: vec2(pos[0] + i as f32 * glyph, y),
+                    x_axis: vec2(glyph, 0.0),
+                    y_axis: vec2(0.0, glyph),
+                    min_uv: cell_uv(cell, vec2(0.0, 0.0)),
+                    max_uv: cell_uv(cell, vec2(1.0, 1.0)),
+                    color,
+                    source: 0,
+                });
+            }
+        }
+    }
+
+    /// Draws the part `min_uv`-`max_uv` of the thumbnail atlas into the rectangle `min`-`max`.
+    pub fn thumbnail(&mut self, min: Vec2f, max: Vec2f, (min_uv, max_uv): (Vec2f, Vec2f)) {
+        let size = max - min;
+        self.quads.push(Quad {
+            origin: min,
+            x_axis: vec2(size[0], 0.0),
+            y_axis: vec2(0.0, size[1]),
+            min_uv,
+            max_uv,
+            color: vec4(1.0, 1.0, 1.0, 1.0),
+            source: 1,
+        });
+    }
+
+    /// Draws text on a semi-transparent background panel whose top left corner is at `pos`.
+    ///
+    /// Returns the bottom right corner of the panel.
+    pub fn label(&mut self, pos: Vec2f, text: &str) -> Vec2f {
+        let max = pos + text_size(text) + vec2(LABEL_PADDING, LABEL_PADDING) * 2.0;
+        self.rect(pos, max, PANEL_COLOR);
+        self.text(pos + vec2(LABEL_PADDING, LABEL_PADDING), text, TEXT_COLOR);
+        max
+    }
+}
+
+/// Computes the size of `text` when drawn with [`Overlay::text`].
+pub fn text_size(text: &str) -> Vec2f {
+    let columns = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+    let lines = text.lines().count();
+    if lines == 0 {
+        return vec2(0.0, 0.0);
+    }
+    vec2(
+        columns as f32 * GLYPH_SIZE as f32 * TEXT_SCALE,
+        (lines - 1) as f32 * LINE_HEIGHT + GLYPH_SIZE as f32 * TEXT_SCALE,
+    )
+}
+
+/// Maps coordinates within a glyph cell (0-1) to atlas UV coordinates.
+fn cell_uv(cell: u32, pos: Vec2f) -> Vec2f {
+    let col = (cell % ATLAS_COLUMNS) as f32;
+    let row = (cell / ATLAS_COLUMNS) as f32;
+    vec2(
+        (col + pos[0]) / ATLAS_COLUMNS as f32,
+        (row + pos[1]) / ATLAS_ROWS as f32,
+    )
+}
+
+pub struct OverlayRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    thumbnail_atlas: wgpu::Texture,
+}
+
+impl OverlayRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target_format: wgpu::TextureFormat,
+        constants: &HashMap<String, f64>,
+        sample_count: u32,
+    ) -> Self {
+        // Rasterize the font into a single-channel atlas.
+        let width = ATLAS_COLUMNS * GLYPH_SIZE;
+        let height = ATLAS_ROWS * GLYPH_SIZE;
+        let mut texels = vec![0u8; (width * height) as usize];
+        for (cell, glyph) in BASIC_LEGACY.iter().enumerate() {
+            let cell = cell as u32;
+            let x0 = (cell % ATLAS_COLUMNS) * GLYPH_SIZE;
+            let y0 = (cell / ATLAS_COLUMNS) * GLYPH_SIZE;
+            for (y, row) in glyph.iter().enumerate() {
+                for x in 0..GLYPH_SIZE {
+                    // Bit 0 is the leftmost pixel.
+                    let set = cell == SOLID_CELL || row & (1 << x) != 0;
+                    let index = (y0 + y as u32) * width + x0 + x;
+                    texels[index as usize] = if set { 0xff } else { 0 };
+                }
+            }
+        }
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let atlas = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("glyph atlas"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            atlas.as_image_copy(),
+            &texels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width),
+                rows_per_image: None,
+            },
+            size,
+        );
+        // Thumbnails are in sRGB, like the images the display pipeline shows.
+        let thumbnail_atlas = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("thumbnail atlas"),
+            size: wgpu::Extent3d {
+                width: THUMBNAIL_ATLAS_CELLS * THUMBNAIL_SIZE,
+                height: THUMBNAIL_ATLAS_CELLS * THUMBNAIL_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &bgl,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &atlas.create_view(&Default::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        &thumbnail_atlas.create_view(&Default::default()),
+                    ),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("overlay.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("overlay.wgsl").into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(
+                &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[&bgl],
+                    push_constant_ranges: &[],
+                }),
+            ),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex"),
+                compilation_options: Default::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: size_of::<Quad>() as _,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                        2 => Float32x2,
+                        3 => Float32x2,
+                        4 => Float32x2,
+                        5 => Float32x4,
+                        6 => Uint32,
+                    ],
+                }],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment"),
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants,
+                    ..Default::default()
+                },
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            thumbnail_atlas,
+        }
+    }
+
+    /// Stores `image` (at most [`THUMBNAIL_SIZE`] in each dimension) in thumbnail slot `slot`.
+    ///
+    /// Returns the UV range of the thumbnail atlas to pass to [`Overlay::thumbnail`].
+    pub fn write_thumbnail(
+        &self,
+        queue: &wgpu::Queue,
+        slot: u32,
+        image: &RgbaImage,
+    ) -> (Vec2f, Vec2f) {
+        assert!(slot < THUMBNAIL_SLOTS);
+        assert!(image.width() <= THUMBNAIL_SIZE && image.height() <= THUMBNAIL_SIZE);
+        let x = slot % THUMBNAIL_ATLAS_CELLS * THUMBNAIL_SIZE;
+        let y = slot / THUMBNAIL_ATLAS_CELLS * THUMBNAIL_SIZE;
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.thumbnail_atlas,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            image,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(image.width() * 4),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: image.width(),
+                height: image.height(),
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let atlas_size = (THUMBNAIL_ATLAS_CELLS * THUMBNAIL_SIZE) as f32;
+        let min = vec2(x as f32, y as f32);
+        let max = min + vec2(image.width() as f32, image.height() as f32);
+        (min / atlas_size, max / atlas_size)
+    }
+
+    /// Records the draw commands for `overlay` into `pass`, which targets a surface of `size`.
+    pub fn draw(
+        &self,
+        device: &wgpu::Device,
+        pass: &mut wgpu::RenderPass<'_>,
+        overlay: &Overlay,
+        size: PhysicalSize<u32>,
+    ) {
+        if overlay.is_empty() {
+            return;
+        }
+
+        // Convert window pixel coordinates to NDC.
+        let scale = vec2(2.0 / size.width as f32, -2.0 / size.height as f32);
+        let offset = vec2(-1.0, 1.0);
+        let quads = overlay
+            .quads
+            .iter()
+            .map(|q| Quad {
+                origin: q.origin * scale + offset,
+                x_axis: q.x_axis * scale,
+                y_axis: q.y_axis * scale,
+                ..*q
+            })
+            .collect::<Vec<_>>();
+        let instances = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&quads),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, instances.slice(..));
+        pass.draw(0..4, 0..quads.len() as u32);
+    }
+}
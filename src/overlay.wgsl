@@ -0,0 +1,74 @@
+// Overlay rendering.
+// Draws a list of screen-space quads on top of the image. Each quad samples either the glyph atlas,
+// which also contains a fully opaque cell for drawing solid rectangles, or the thumbnail atlas.
+
+@group(0) @binding(0)
+var atlas_sampler: sampler;
+@group(0) @binding(1)
+var atlas: texture_2d<f32>;
+@group(0) @binding(2)
+var thumbnail_atlas: texture_2d<f32>;
+
+// Whether the render target is a non-sRGB format, so the output has to be encoded manually.
+override SRGB_OUTPUT: bool = false;
+
+fn linear_to_srgb(c: vec3f) -> vec3f {
+    return select(1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3(0.0031308));
+}
+
+struct Quad {
+    // Top left corner and the edges leaving it, in normalized device coordinates.
+    @location(0) origin: vec2f,
+    @location(1) x_axis: vec2f,
+    @location(2) y_axis: vec2f,
+    // Corners in atlas UV coordinates.
+    @location(3) min_uv: vec2f,
+    @location(4) max_uv: vec2f,
+    // Straight-alpha color.
+    @location(5) color: vec4f,
+    // 0 = glyph atlas, 1 = thumbnail atlas.
+    @location(6) source: u32,
+}
+
+struct VertexOutput {
+    @builtin(position)
+    position: vec4f,
+    @location(0)
+    uv: vec2f,
+    @location(1)
+    color: vec4f,
+    @location(2) @interpolate(flat)
+    source: u32,
+};
+
+@vertex
+fn vertex(@builtin(vertex_index) vertex_index: u32, quad: Quad) -> VertexOutput {
+    // Triangle strip order: top left, top right, bottom left, bottom right.
+    let corner = vec2f(f32(vertex_index & 1), f32(vertex_index >> 1));
+
+    var out: VertexOutput;
+    out.position = vec4f(quad.origin + corner.x * quad.x_axis + corner.y * quad.y_axis, 0.0, 1.0);
+    out.uv = mix(quad.min_uv, quad.max_uv, corner);
+    out.color = quad.color;
+    out.source = quad.source;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4f {
+    // Both textures are sampled unconditionally, since sampling requires uniform control flow.
+    let coverage = textureSample(atlas, atlas_sampler, in.uv).r;
+    let thumbnail = textureSample(thumbnail_atlas, atlas_sampler, in.uv);
+    var color = in.color.rgb;
+    var alpha = in.color.a * coverage;
+    if in.source == 1 {
+        color = thumbnail.rgb;
+        alpha = thumbnail.a * in.color.a;
+    }
+    if SRGB_OUTPUT {
+        // Blending then happens in sRGB space, which is close enough for the overlay.
+        color = linear_to_srgb(color);
+    }
+    // premultiplied output, blended over the image
+    return vec4(color * alpha, alpha);
+}
@@ -0,0 +1,96 @@
+//! Support for TIFF files containing multiple pages.
+//!
+//! `image`'s TIFF decoder only ever decodes the first page of the file. The others are decoded with
+//! the `tiff` crate directly, which only handles the more common pixel layouts; pages in any other
+//! layout are skipped. If a later page can't be read at all, the pages decoded up to that point
+//! are kept.
+
+use std::io::Cursor;
+
+use anyhow::Context;
+use image::{DynamicImage, ImageBuffer, ImageFormat, RgbaImage};
+use tiff::{
+    decoder::{Decoder, DecodingResult},
+    ColorType,
+};
+
+/// Decodes every page of a TIFF file, in the order they're stored in.
+///
+/// The pages can all have different sizes.
+pub fn decode_pages(data: &[u8]) -> anyhow::Result<Vec<RgbaImage>> {
+    let first = image::load_from_memory_with_format(data, ImageFormat::Tiff)?.into_rgba8();
+    let mut pages = vec![first];
+
+    let mut decoder = match Decoder::new(Cursor::new(data)) {
+        Ok(decoder) => decoder,
+        Err(e) => {
+            log::warn!("failed to read TIFF pages after the first: {e}");
+            return Ok(pages);
+        }
+    };
+    let mut index = 0;
+    while decoder.more_images() {
+        index += 1;
+        if let Err(e) = decoder.next_image() {
+            log::warn!("failed to read TIFF page {index}, ignoring it and all later pages: {e}");
+            break;
+        }
+        match decode_page(&mut decoder) {
+            Ok(Some(page)) => pages.push(page),
+            Ok(None) => log::warn!("skipping TIFF page {index}: unsupported pixel layout"),
+            Err(e) => {
+                log::warn!(
+                    "failed to decode TIFF page {index}, ignoring it and all later pages: {e:#}"
+                );
+                break;
+            }
+        }
+    }
+    Ok(pages)
+}
+
+/// Decodes the page `decoder` is currently positioned at.
+///
+/// Returns `None` if the page uses a pixel layout that isn't supported.
+fn decode_page(decoder: &mut Decoder<Cursor<&[u8]>>) -> anyhow::Result<Option<RgbaImage>> {
+    let (width, height) = decoder.dimensions()?;
+    let color_type = decoder.colortype()?;
+    let image = match (color_type, decoder.read_image()?) {
+        (ColorType::Gray(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLuma8)
+        }
+        (ColorType::Gray(16), DecodingResult::U16(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLuma16)
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLumaA8)
+        }
+        (ColorType::GrayA(16), DecodingResult::U16(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageLumaA16)
+        }
+        (ColorType::RGB(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb8)
+        }
+        (ColorType::RGB(16), DecodingResult::U16(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb16)
+        }
+        (ColorType::RGB(32), DecodingResult::F32(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgb32F)
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba8)
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba16)
+        }
+        (ColorType::RGBA(32), DecodingResult::F32(buf)) => {
+            ImageBuffer::from_raw(width, height, buf).map(DynamicImage::ImageRgba32F)
+        }
+        (color_type, _) => {
+            log::debug!("unsupported TIFF color type {color_type:?}");
+            return Ok(None);
+        }
+    };
+    let image = image.context("page data is truncated")?;
+    Ok(Some(image.into_rgba8()))
+}
@@ -0,0 +1,187 @@
+//! The average and dominant colors of an image, for [`Action::TogglePalette`].
+//!
+//! [`Action::TogglePalette`]: crate::keymap::Action::TogglePalette
+
+use image::DynamicImage;
+
+use crate::{color::Color, math};
+
+/// Number of dominant colors to look for.
+const DOMINANT_COLORS: usize = 5;
+/// The dominant colors are computed from a grid of at most this many samples in each direction.
+const SAMPLE_GRID_SIZE: u32 = 96;
+/// Upper bound on the number of k-means iterations (it usually converges much earlier).
+const MAX_ITERATIONS: usize = 32;
+
+pub struct Palette {
+    /// Average color of all pixels, weighted by their alpha and averaged in linear light.
+    pub average: Color,
+    /// The dominant colors and the fraction of the visible pixels they stand for, most common
+    /// first.
+    pub dominant: Vec<(Color, f32)>,
+}
+
+/// Computes the palette of a frame of a `LoadedImage`.
+///
+/// Returns `None` if the frame is fully transparent.
+pub fn analyze(image: &DynamicImage, premultiplied: bool) -> Option<Palette> {
+    let (width, height) = (image.width(), image.height());
+    let pixels = linear_pixels(image, premultiplied);
+
+    let mut sum = [0.0f64; 3];
+    let mut weight = 0.0f64;
+    for &[r, g, b, a] in &pixels {
+        let a = f64::from(a);
+        sum[0] += f64::from(r) * a;
+        sum[1] += f64::from(g) * a;
+        sum[2] += f64::from(b) * a;
+        weight += a;
+    }
+    if weight == 0.0 {
+        return None;
+    }
+    let average = sum.map(|c| (c / weight) as f32);
+
+    // Clustering happens on sRGB-encoded values, where distances match perceived differences
+    // better than in linear light.
+    let step = (width.max(height) / SAMPLE_GRID_SIZE).max(1);
+    let samples = (0..height)
+        .step_by(step as usize)
+        .flat_map(|y| (0..width).step_by(step as usize).map(move |x| (x, y)))
+        .map(|(x, y)| pixels[(y * width + x) as usize])
+        .filter(|&[.., a]| a > 0.0)
+        .map(|[r, g, b, a]| ([r, g, b].map(math::linear_to_srgb), a))
+        .collect::<Vec<_>>();
+
+    Some(Palette {
+        average: to_color(average.map(math::linear_to_srgb)),
+        dominant: k_means(&samples, DOMINANT_COLORS)
+            .into_iter()
+            .map(|(center, share)| (to_color(center), share))
+            .collect(),
+    })
+}
+
+/// Returns the pixels of `image` as linear RGB with straight alpha.
+fn linear_pixels(image: &DynamicImage, premultiplied: bool) -> Vec<[f32; 4]> {
+    let pixels = match image {
+        // Frames are either 8-bit sRGB or floating-point linear (see `LoadedImage`).
+        DynamicImage::ImageRgba32F(image) => image
+            .pixels()
+            .map(|p| p.0.map(|c| c.clamp(0.0, 1.0)))
+            .collect::<Vec<_>>(),
+        image => image
+            .to_rgba8()
+            .pixels()
+            .map(|p| {
+                let [r, g, b, a] = p.0.map(|c| f32::from(c) / 255.0);
+                let [r, g, b] = [r, g, b].map(math::srgb_to_linear);
+                [r, g, b, a]
+            })
+            .collect(),
+    };
+    if !premultiplied {
+        return pixels;
+    }
+    pixels
+        .into_iter()
+        .map(|[r, g, b, a]| {
+            if a == 0.0 {
+                [0.0; 4]
+            } else {
+                [r / a, g / a, b / a, a].map(|c| c.min(1.0))
+            }
+        })
+        .collect()
+}
+
+/// Clusters the weighted `samples` into at most `k` colors.
+///
+/// Returns the cluster centers along with the fraction of the total weight they hold, largest
+/// first.
+fn k_means(samples: &[([f32; 3], f32)], k: usize) -> Vec<([f32; 3], f32)> {
+    let total = samples.iter().map(|(_, w)| w).sum::<f32>();
+    if samples.is_empty() || total == 0.0 {
+        return Vec::new();
+    }
+
+    // Farthest-point initialization: deterministic, and spreads the centers over the colors that
+    // actually occur. The first center is the sample with the highest weight.
+    let first = samples
+        .iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(color, _)| *color)
+        .unwrap();
+    let mut centers = vec![first];
+    while centers.len() < k {
+        let (farthest, dist) = samples
+            .iter()
+            .map(|(color, w)| (*color, nearest(&centers, *color).1 * w))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        if dist == 0.0 {
+            // There are fewer than `k` distinct colors.
+            break;
+        }
+        centers.push(farthest);
+    }
+
+    let mut assignment = vec![usize::MAX; samples.len()];
+    let mut weights = vec![0.0; centers.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (assigned, (color, _)) in assignment.iter_mut().zip(samples) {
+            let (index, _) = nearest(&centers, *color);
+            changed |= *assigned != index;
+            *assigned = index;
+        }
+
+        let mut sums = vec![[0.0f32; 3]; centers.len()];
+        weights.fill(0.0);
+        for (&index, (color, w)) in assignment.iter().zip(samples) {
+            for (sum, c) in sums[index].iter_mut().zip(color) {
+                *sum += c * w;
+            }
+            weights[index] += w;
+        }
+        for ((center, sum), &weight) in centers.iter_mut().zip(&sums).zip(&weights) {
+            if weight > 0.0 {
+                *center = sum.map(|c| c / weight);
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut clusters = centers
+        .into_iter()
+        .zip(weights)
+        .filter(|&(_, weight)| weight > 0.0)
+        .map(|(center, weight)| (center, weight / total))
+        .collect::<Vec<_>>();
+    clusters.sort_by(|a, b| b.1.total_cmp(&a.1));
+    clusters
+}
+
+/// Returns the index of the center closest to `color`, and the squared distance to it.
+fn nearest(centers: &[[f32; 3]], color: [f32; 3]) -> (usize, f32) {
+    centers
+        .iter()
+        .map(|center| {
+            center
+                .iter()
+                .zip(&color)
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum::<f32>()
+        })
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap()
+}
+
+/// Converts sRGB-encoded channel values to an opaque [`Color`].
+fn to_color(srgb: [f32; 3]) -> Color {
+    let [r, g, b] = srgb.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+    Color([r, g, b, u8::MAX])
+}
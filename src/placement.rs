@@ -0,0 +1,108 @@
+//! Initial window placement.
+
+use raw_window_handle::HasDisplayHandle;
+use winit::{
+    dpi::PhysicalPosition, event_loop::ActiveEventLoop, monitor::MonitorHandle, window::Window,
+};
+
+/// Returns the monitor that contains the mouse cursor.
+///
+/// Falls back to the primary monitor if the cursor position can't be determined (which is the case
+/// on every platform except X11).
+pub fn cursor_monitor(event_loop: &ActiveEventLoop) -> Option<MonitorHandle> {
+    if let Some(cursor) = cursor_position(event_loop) {
+        let monitor = event_loop.available_monitors().find(|m| {
+            let (pos, size) = (m.position(), m.size());
+            cursor.x >= pos.x
+                && cursor.y >= pos.y
+                && cursor.x < pos.x + size.width as i32
+                && cursor.y < pos.y + size.height as i32
+        });
+        if monitor.is_some() {
+            return monitor;
+        }
+    }
+    event_loop
+        .primary_monitor()
+        .or_else(|| event_loop.available_monitors().next())
+}
+
+/// Returns the monitor at `index` in the list of available monitors.
+///
+/// Falls back to the primary monitor (with a warning) if there is no such monitor.
+pub fn nth_monitor(event_loop: &ActiveEventLoop, index: usize) -> Option<MonitorHandle> {
+    let monitors = event_loop.available_monitors().collect::<Vec<_>>();
+    match monitors.get(index) {
+        Some(monitor) => Some(monitor.clone()),
+        None => {
+            log::warn!(
+                "there is no monitor {index} (found {}); using the primary monitor",
+                monitors.len(),
+            );
+            event_loop
+                .primary_monitor()
+                .or_else(|| monitors.into_iter().next())
+        }
+    }
+}
+
+/// Moves `window` to the center of `monitor`.
+///
+/// If the window is larger than the monitor, its top left corner is aligned with the monitor's
+/// instead, so that the window contents start on-screen.
+pub fn center_on(window: &Window, monitor: &MonitorHandle) {
+    let (pos, size) = (monitor.position(), monitor.size());
+    let outer = window.outer_size();
+    let x = pos.x + (size.width as i32 - outer.width as i32) / 2;
+    let y = pos.y + (size.height as i32 - outer.height as i32) / 2;
+    let target = PhysicalPosition::new(x.max(pos.x), y.max(pos.y));
+    log::debug!(
+        "placing window at {},{} on monitor {:?}",
+        target.x,
+        target.y,
+        monitor.name(),
+    );
+    window.set_outer_position(target);
+}
+
+/// Queries the global mouse cursor position in physical desktop coordinates.
+fn cursor_position(event_loop: &ActiveEventLoop) -> Option<PhysicalPosition<i32>> {
+    let dh = event_loop.display_handle().ok()?;
+    match dh.as_raw() {
+        // cfg predicate copied from winit, keep in sync with Cargo.toml
+        #[cfg(all(
+            unix,
+            not(any(
+                target_os = "redox",
+                target_family = "wasm",
+                target_os = "android",
+                target_os = "ios",
+                target_os = "macos"
+            ))
+        ))]
+        raw_window_handle::RawDisplayHandle::Xlib(dh) => {
+            let xlib = crate::xlib::get()?;
+            let display = dh.display?.as_ptr().cast();
+
+            let (mut root, mut child) = (0, 0);
+            let (mut root_x, mut root_y, mut win_x, mut win_y) = (0, 0, 0, 0);
+            let mut mask = 0;
+            let found = unsafe {
+                let root_window = (xlib.XDefaultRootWindow)(display);
+                (xlib.XQueryPointer)(
+                    display,
+                    root_window,
+                    &mut root,
+                    &mut child,
+                    &mut root_x,
+                    &mut root_y,
+                    &mut win_x,
+                    &mut win_y,
+                    &mut mask,
+                )
+            };
+            (found != 0).then(|| PhysicalPosition::new(root_x, root_y))
+        }
+        _ => None,
+    }
+}
@@ -25,6 +25,8 @@ struct ImageInfo {
 }
 
 override WORKGROUP_SIZE: u32 = 16;
+// whether the source image is already premultiplied, and should be stored as-is
+override PREMULTIPLIED: bool = false;
 
 @compute
 @workgroup_size(WORKGROUP_SIZE, WORKGROUP_SIZE)
@@ -58,6 +60,6 @@ fn preprocess(@builtin(global_invocation_id) gid: vec3u) {
         atomicMax(&info.bottom, gid.y);
     }
 
-    var out = vec4(pixel.rgb * pixel.a, pixel.a);
+    var out = select(vec4(pixel.rgb * pixel.a, pixel.a), pixel, PREMULTIPLIED);
     textureStore(output, gid.xy, out);
 }
@@ -1,9 +1,20 @@
-use std::sync::OnceLock;
-
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle};
 use winit::{dpi::PhysicalSize, window::Window};
 
+/// Tells the windowing system to keep `win` at `aspect_ratio` while it's being resized, where the
+/// platform supports it.
+///
+/// This is only a hint; the caller still has to correct the window size after each resize.
 pub fn enforce(win: &Window, aspect_ratio: f32, _size: PhysicalSize<u32>) {
+    set_hint(win, Some(aspect_ratio));
+}
+
+/// Removes the hint set by [`enforce`], so that `win` can be resized freely.
+pub fn release(win: &Window) {
+    set_hint(win, None);
+}
+
+fn set_hint(win: &Window, aspect_ratio: Option<f32>) {
     let Ok(wh) = win.window_handle() else { return };
     let Ok(dh) = win.display_handle() else { return };
     match (wh.as_raw(), dh.as_raw()) {
@@ -19,18 +30,16 @@ pub fn enforce(win: &Window, aspect_ratio: f32, _size: PhysicalSize<u32>) {
             ))
         ))]
         (RawWindowHandle::Xlib(wh), RawDisplayHandle::Xlib(dh)) => {
-            use x11_dl::error::OpenError;
-            use x11_dl::xlib::{PAspect, Xlib};
+            use x11_dl::xlib::PAspect;
 
-            static XLIB: OnceLock<Result<Xlib, OpenError>> = OnceLock::new();
-            let Ok(xlib) = XLIB.get_or_init(Xlib::open).as_ref() else {
+            let Some(xlib) = crate::xlib::get() else {
                 return;
             };
 
             let Some(display) = dh.display else { return };
 
             let num = 65536;
-            let denom = (aspect_ratio * num as f32).round() as _;
+            let denom = aspect_ratio.map_or(0, |r| (r * num as f32).round().max(1.0) as _);
             unsafe {
                 let size_hints = (xlib.XAllocSizeHints)();
                 if size_hints.is_null() {
@@ -51,19 +60,120 @@ pub fn enforce(win: &Window, aspect_ratio: f32, _size: PhysicalSize<u32>) {
 
                 // XWayland ignores these, because XWayland is very cool! Thanks, XWayland!
                 // So, this is mostly untested.
-                (*size_hints).min_aspect.x = num;
-                (*size_hints).min_aspect.y = denom;
-                (*size_hints).max_aspect.x = num;
-                (*size_hints).max_aspect.y = denom;
-                (*size_hints).flags |= PAspect;
+                if aspect_ratio.is_some() {
+                    (*size_hints).min_aspect.x = num;
+                    (*size_hints).min_aspect.y = denom;
+                    (*size_hints).max_aspect.x = num;
+                    (*size_hints).max_aspect.y = denom;
+                    (*size_hints).flags |= PAspect;
+                } else {
+                    (*size_hints).flags &= !PAspect;
+                }
 
                 (xlib.XSetWMNormalHints)(display.as_ptr().cast(), wh.window, size_hints);
 
                 (xlib.XFree)(size_hints.cast());
             }
 
-            log::debug!("set X11 aspect ratio to {num}/{denom}");
+            log::debug!("set X11 aspect ratio to {aspect_ratio:?} ({num}/{denom})");
+        }
+        #[cfg(windows)]
+        (RawWindowHandle::Win32(wh), _) => {
+            use windows_sys::Win32::UI::Shell::{RemoveWindowSubclass, SetWindowSubclass};
+
+            let Some(aspect_ratio) = aspect_ratio else {
+                unsafe { RemoveWindowSubclass(wh.hwnd.get() as _, Some(win32::sizing_proc), 0) };
+                log::debug!("removed Win32 aspect ratio");
+                return;
+            };
+
+            // Win32 has no aspect ratio hint, but we can adjust the rectangle in `WM_SIZING` while
+            // the user is dragging a window edge. Calling `SetWindowSubclass` again just updates
+            // the reference data, which we use to pass the aspect ratio to the subclass procedure.
+            let ok = unsafe {
+                SetWindowSubclass(
+                    wh.hwnd.get() as _,
+                    Some(win32::sizing_proc),
+                    0,
+                    aspect_ratio.to_bits() as usize,
+                )
+            };
+            if ok == 0 {
+                log::error!("`SetWindowSubclass` failed!");
+                return;
+            }
+
+            log::debug!("set Win32 aspect ratio to {aspect_ratio}");
+        }
+        #[cfg(target_os = "macos")]
+        (RawWindowHandle::AppKit(wh), _) => {
+            use objc2_app_kit::NSView;
+            use objc2_foundation::NSSize;
+
+            // We're always called on the main thread, so accessing the view is fine.
+            let view: &NSView = unsafe { wh.ns_view.cast().as_ref() };
+            let Some(window) = view.window() else { return };
+            match aspect_ratio {
+                Some(aspect_ratio) => unsafe {
+                    window.setContentAspectRatio(NSSize::new(aspect_ratio.into(), 1.0))
+                },
+                // Setting resize increments clears the aspect ratio.
+                None => window.setContentResizeIncrements(NSSize::new(1.0, 1.0)),
+            }
+
+            log::debug!("set AppKit aspect ratio to {aspect_ratio:?}");
+        }
+        (RawWindowHandle::Wayland(_), RawDisplayHandle::Wayland(_)) => {
+            // Neither xdg-shell nor any widely implemented extension protocol lets a client tell
+            // the compositor about its aspect ratio (min/max size could only pin the window to a
+            // single size). We rely entirely on `Viewer::enforce_aspect_ratio` snapping the size
+            // after each resize here.
+            log::trace!("no aspect ratio hint available on Wayland");
         }
         _ => {}
     }
 }
+
+#[cfg(windows)]
+mod win32 {
+    use windows_sys::Win32::{
+        Foundation::{HWND, LPARAM, LRESULT, RECT, TRUE, WPARAM},
+        UI::{
+            Shell::DefSubclassProc,
+            WindowsAndMessaging::{WMSZ_BOTTOM, WMSZ_TOP, WMSZ_TOPLEFT, WMSZ_TOPRIGHT, WM_SIZING},
+        },
+    };
+
+    /// Window subclass procedure that keeps the window rectangle at the aspect ratio stored in
+    /// `ref_data` while it's being resized.
+    pub unsafe extern "system" fn sizing_proc(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        _id: usize,
+        ref_data: usize,
+    ) -> LRESULT {
+        if msg != WM_SIZING {
+            return DefSubclassProc(hwnd, msg, wparam, lparam);
+        }
+
+        let aspect_ratio = f32::from_bits(ref_data as u32);
+        let rect = &mut *(lparam as *mut RECT);
+        let width = (rect.right - rect.left) as f32;
+        let height = (rect.bottom - rect.top) as f32;
+        // Keep the edge (or corner) the user is dragging where it is, and move the adjacent one.
+        match wparam as u32 {
+            WMSZ_TOP | WMSZ_BOTTOM => {
+                rect.right = rect.left + (height * aspect_ratio).round() as i32;
+            }
+            WMSZ_TOPLEFT | WMSZ_TOPRIGHT => {
+                rect.top = rect.bottom - (width / aspect_ratio).round() as i32;
+            }
+            _ => {
+                rect.bottom = rect.top + (width / aspect_ratio).round() as i32;
+            }
+        }
+        TRUE as LRESULT
+    }
+}
@@ -0,0 +1,51 @@
+//! Camera RAW files, decoded with `rawloader` and developed with `imagepipe`.
+//!
+//! Both are fairly heavy, so they're only included with the `raw` cargo feature.
+
+use std::path::Path;
+
+use image::RgbaImage;
+
+/// File extensions of the RAW formats that are recognized.
+const EXTENSIONS: &[&str] = &["dng", "cr2", "nef", "arw", "raf", "orf", "rw2"];
+
+pub fn is_raw(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| EXTENSIONS.iter().any(|raw| ext.eq_ignore_ascii_case(raw)))
+}
+
+/// Demosaics a RAW file and applies `imagepipe`'s default processing (white balance, color
+/// conversion and tone curve) to it.
+#[cfg(feature = "raw")]
+pub fn decode(data: &[u8]) -> anyhow::Result<RgbaImage> {
+    use std::io::Cursor;
+
+    use anyhow::{anyhow, Context};
+    use image::{DynamicImage, RgbImage};
+    use imagepipe::{ImageSource, Pipeline};
+
+    let raw = rawloader::decode(&mut Cursor::new(data)).context("failed to decode RAW file")?;
+    log::debug!(
+        "decoding {} {} RAW file ({}x{})",
+        raw.clean_make,
+        raw.clean_model,
+        raw.width,
+        raw.height,
+    );
+    let mut pipeline = Pipeline::new_from_source(ImageSource::Raw(raw))
+        .map_err(|e| anyhow!("failed to process RAW file: {e}"))?;
+    let image = pipeline
+        .output_8bit(None)
+        .map_err(|e| anyhow!("failed to process RAW file: {e}"))?;
+    let image = RgbImage::from_raw(image.width as u32, image.height as u32, image.data)
+        .context("RAW processing produced an image of the wrong size")?;
+    Ok(DynamicImage::ImageRgb8(image).into_rgba8())
+}
+
+#[cfg(not(feature = "raw"))]
+pub fn decode(_data: &[u8]) -> anyhow::Result<RgbaImage> {
+    anyhow::bail!(
+        "RAW files can only be opened if {} is built with the `raw` feature",
+        env!("CARGO_PKG_NAME"),
+    )
+}
@@ -0,0 +1,56 @@
+//! Finding the images in directories passed on the command line.
+
+use std::{fs, path::PathBuf};
+
+use anyhow::{bail, Context};
+
+use crate::{load, source::Source};
+
+/// Replaces every directory in `sources` with the supported images it contains, sorted by path.
+///
+/// If `recursive` is `true`, subdirectories are searched as well.
+pub fn expand_dirs(sources: Vec<Source>, recursive: bool) -> anyhow::Result<Vec<Source>> {
+    let mut expanded = Vec::new();
+    for source in sources {
+        let path = match source {
+            Source::Path(path) if path.is_dir() => path,
+            source => {
+                expanded.push(source);
+                continue;
+            }
+        };
+
+        let mut images = Vec::new();
+        collect_images(path.clone(), recursive, &mut images)?;
+        if images.is_empty() {
+            bail!("No supported images found in '{}'", path.display());
+        }
+        images.sort();
+        log::debug!("found {} images in '{}'", images.len(), path.display());
+        expanded.extend(images.into_iter().map(Source::Path));
+    }
+    Ok(expanded)
+}
+
+fn collect_images(dir: PathBuf, recursive: bool, images: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let entries = fs::read_dir(&dir)
+        .with_context(|| format!("Failed to read directory '{}'", dir.display()))?;
+    for entry in entries {
+        let entry =
+            entry.with_context(|| format!("Failed to read directory '{}'", dir.display()))?;
+        let path = entry.path();
+        // Unlike `Path::is_dir`, this doesn't follow symlinks, which could form a cycle.
+        let is_dir = entry.file_type().is_ok_and(|ty| ty.is_dir());
+        if is_dir {
+            if recursive {
+                // Don't let a single unreadable subdirectory prevent opening all the others.
+                if let Err(e) = collect_images(path, recursive, images) {
+                    log::warn!("skipping subdirectory: {e:#}");
+                }
+            }
+        } else if load::is_supported(&path) {
+            images.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,300 @@
+//! Window size computations.
+//!
+//! These are kept free of any window or event loop state, so they only deal with sizes and aspect
+//! ratios (width / height).
+
+use std::cmp;
+
+use winit::dpi::{LogicalSize, PhysicalSize};
+
+use crate::math::{vec2, Vec2f};
+
+/// Smallest window size along either axis, in logical pixels.
+///
+/// Images that are only a pixel or two wide (or tall) are letterboxed into a window of at least
+/// this size, so that the window can still be seen and grabbed.
+pub const MIN_WINDOW_SIZE: u32 = 32;
+
+/// Images smaller than this along either axis (favicons, sprites, ...) count as tiny.
+const TINY_IMAGE_THRESHOLD: u32 = 32;
+
+/// Tiny images are enlarged by a whole factor until their shorter side is at least this long.
+const TINY_IMAGE_TARGET_SIZE: u32 = 256;
+
+/// Returns whether an image of `size` pixels is tiny enough to be shown pixel-doubled.
+pub fn is_tiny(size: LogicalSize<u32>) -> bool {
+    size.width < TINY_IMAGE_THRESHOLD || size.height < TINY_IMAGE_THRESHOLD
+}
+
+/// Computes the initial window size for showing a view of `view` image pixels.
+///
+/// The view is scaled to fit within `max` while keeping its aspect ratio, but is never enlarged
+/// beyond its own size. Tiny views are the exception: they're enlarged by a whole factor until
+/// their shorter side reaches [`TINY_IMAGE_TARGET_SIZE`] (as far as `max` allows).
+pub fn initial_size(view: LogicalSize<u32>, max: LogicalSize<u32>) -> LogicalSize<u32> {
+    let view = LogicalSize::new(cmp::max(view.width, 1), cmp::max(view.height, 1));
+    let view = if is_tiny(view) {
+        let factor = TINY_IMAGE_TARGET_SIZE.div_ceil(cmp::min(view.width, view.height));
+        LogicalSize::new(
+            view.width.saturating_mul(factor),
+            view.height.saturating_mul(factor),
+        )
+    } else {
+        view
+    };
+
+    let aspect_ratio = view.width as f32 / view.height as f32;
+    let s1 = LogicalSize::new(
+        (max.height as f32 * aspect_ratio).round() as u32,
+        max.height,
+    );
+    let s2 = LogicalSize::new(max.width, (max.width as f32 / aspect_ratio).round() as u32);
+    let fit_size = if s1.width > max.width || s1.height > max.height {
+        s2
+    } else {
+        s1
+    };
+
+    LogicalSize::new(
+        fit_size.width.clamp(1, view.width).max(MIN_WINDOW_SIZE),
+        fit_size.height.clamp(1, view.height).max(MIN_WINDOW_SIZE),
+    )
+}
+
+/// Computes the window size that shows `view` image pixels at `zoom` percent.
+pub fn zoomed_size(view: LogicalSize<u32>, zoom: f32) -> LogicalSize<u32> {
+    let scale = zoom / 100.0;
+    LogicalSize::new(
+        cmp::max((view.width as f32 * scale).round() as u32, MIN_WINDOW_SIZE),
+        cmp::max((view.height as f32 * scale).round() as u32, MIN_WINDOW_SIZE),
+    )
+}
+
+/// Shrinks `size` (keeping its aspect ratio) so that it fits within `available`.
+///
+/// Returns `size` unchanged if it already fits.
+pub fn shrink_to_fit(size: LogicalSize<u32>, available: LogicalSize<f32>) -> LogicalSize<u32> {
+    let scale = f32::min(
+        available.width / size.width as f32,
+        available.height / size.height as f32,
+    );
+    if scale >= 1.0 {
+        return size;
+    }
+    LogicalSize::new(
+        cmp::max((size.width as f32 * scale).floor() as u32, 1),
+        cmp::max((size.height as f32 * scale).floor() as u32, 1),
+    )
+}
+
+/// Adjusts a window size requested by the user so that it matches `aspect_ratio`.
+///
+/// If `vertical` is `true`, the requested height is kept and the width is adjusted, otherwise the
+/// width is kept. Neither side is made smaller than `min` (in physical pixels), since the window
+/// system wouldn't allow that anyway: if the shorter side would be, it's set to `min` and the
+/// other side is computed from it, so that the aspect ratio is still kept.
+///
+/// Neither side is made larger than `max` (the space on the monitor) either. Images too long to
+/// fit at their aspect ratio get a window that's as long as possible, and are letterboxed in it.
+pub fn snap_to_aspect_ratio(
+    size: PhysicalSize<u32>,
+    aspect_ratio: f32,
+    vertical: bool,
+    min: u32,
+    max: PhysicalSize<u32>,
+) -> PhysicalSize<u32> {
+    let snapped = if vertical {
+        PhysicalSize::new(
+            (size.height as f32 * aspect_ratio).round() as u32,
+            size.height,
+        )
+    } else {
+        PhysicalSize::new(
+            size.width,
+            (size.width as f32 / aspect_ratio).round() as u32,
+        )
+    };
+    let snapped = if aspect_ratio >= 1.0 && snapped.height < min {
+        PhysicalSize::new((min as f32 * aspect_ratio).round() as u32, min)
+    } else if aspect_ratio < 1.0 && snapped.width < min {
+        PhysicalSize::new(min, (min as f32 / aspect_ratio).round() as u32)
+    } else {
+        snapped
+    };
+    PhysicalSize::new(
+        cmp::min(snapped.width, max.width),
+        cmp::min(snapped.height, max.height),
+    )
+}
+
+/// Computes the largest centered rectangle with the given aspect ratio that fits in a window of
+/// `size` pixels.
+pub fn fit_aspect_ratio(size: PhysicalSize<u32>, aspect_ratio: f32) -> (Vec2f, Vec2f) {
+    // Minimized windows can report a size of 0x0.
+    let size = PhysicalSize::new(cmp::max(size.width, 1), cmp::max(size.height, 1));
+    let to_aspect = size.width as f32 / size.height as f32;
+    let (y_min, x_min, w, h);
+    if aspect_ratio > to_aspect {
+        w = size.width as f32;
+        h = size.width as f32 / aspect_ratio;
+
+        x_min = 0.0;
+        y_min = (size.height as f32 - h) / 2.0;
+    } else {
+        w = size.height as f32 * aspect_ratio;
+        h = size.height as f32;
+
+        x_min = (size.width as f32 - w) / 2.0;
+        y_min = 0.0;
+    }
+
+    let min = vec2(x_min, y_min);
+    let max = min + vec2(w, h);
+    (min, max)
+}
+
+/// Maps a position in a window of `size` pixels to image UV coordinates, when the window shows the
+/// UV region `min_uv..max_uv` fitted into it with `aspect_ratio` (see [`fit_aspect_ratio`]).
+pub fn window_to_uv(
+    size: PhysicalSize<u32>,
+    aspect_ratio: f32,
+    (min_uv, max_uv): (Vec2f, Vec2f),
+    pos: Vec2f,
+) -> Vec2f {
+    let (min, max) = fit_aspect_ratio(size, aspect_ratio);
+    let uv = (pos - min) / (max - min);
+
+    // Adjust the raw UVs to take `min_uv` and `max_uv` into account.
+    uv * (max_uv - min_uv) + min_uv
+}
+
+/// Inverse of [`window_to_uv`].
+pub fn uv_to_window(
+    size: PhysicalSize<u32>,
+    aspect_ratio: f32,
+    (min_uv, max_uv): (Vec2f, Vec2f),
+    uv: Vec2f,
+) -> Vec2f {
+    let (min, max) = fit_aspect_ratio(size, aspect_ratio);
+    min + (uv - min_uv) / (max_uv - min_uv) * (max - min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: LogicalSize<u32> = LogicalSize::new(1920, 1080);
+
+    #[test]
+    fn initial_size_one_pixel_tall() {
+        // Enlarged as a tiny image, then shrunk to fit the screen width, but kept tall enough to
+        // be grabbed.
+        let size = initial_size(LogicalSize::new(100, 1), SCREEN);
+        assert_eq!(size, LogicalSize::new(1920, MIN_WINDOW_SIZE));
+    }
+
+    #[test]
+    fn initial_size_square() {
+        assert_eq!(
+            initial_size(LogicalSize::new(64, 64), SCREEN),
+            LogicalSize::new(64, 64)
+        );
+        assert_eq!(
+            initial_size(LogicalSize::new(16, 16), SCREEN),
+            LogicalSize::new(256, 256)
+        );
+        assert_eq!(
+            initial_size(LogicalSize::new(4000, 4000), SCREEN),
+            LogicalSize::new(1080, 1080)
+        );
+    }
+
+    #[test]
+    fn initial_size_panorama() {
+        let size = initial_size(LogicalSize::new(10000, 1000), SCREEN);
+        assert_eq!(size, LogicalSize::new(1920, 192));
+    }
+
+    const MONITOR: PhysicalSize<u32> = PhysicalSize::new(1920, 1080);
+
+    #[test]
+    fn snap_keeps_aspect_ratio_at_min_size() {
+        // 1x1 image, shrunk below the minimum.
+        let size = snap_to_aspect_ratio(PhysicalSize::new(10, 10), 1.0, false, 32, MONITOR);
+        assert_eq!(size, PhysicalSize::new(32, 32));
+
+        // 1x10 image: the width is the shorter side.
+        let size = snap_to_aspect_ratio(PhysicalSize::new(20, 200), 0.1, true, 32, MONITOR);
+        assert_eq!(size, PhysicalSize::new(32, 320));
+
+        // 10x1 image: the height is the shorter side.
+        let size = snap_to_aspect_ratio(PhysicalSize::new(200, 20), 10.0, false, 32, MONITOR);
+        assert_eq!(size, PhysicalSize::new(320, 32));
+    }
+
+    #[test]
+    fn snap_letterboxes_what_does_not_fit() {
+        // 1x1000 image: keeping the aspect ratio at the minimum width would be 32000 pixels tall.
+        let size =
+            snap_to_aspect_ratio(PhysicalSize::new(20, 500), 1.0 / 1000.0, true, 32, MONITOR);
+        assert_eq!(size, PhysicalSize::new(32, 1080));
+
+        // 1000x1 image.
+        let size = snap_to_aspect_ratio(PhysicalSize::new(500, 20), 1000.0, false, 32, MONITOR);
+        assert_eq!(size, PhysicalSize::new(1920, 32));
+
+        // Like what `initial_size_one_pixel_tall` opens, which must stay as it is.
+        let size = snap_to_aspect_ratio(PhysicalSize::new(1920, 32), 100.0, false, 32, MONITOR);
+        assert_eq!(size, PhysicalSize::new(1920, 32));
+    }
+
+    #[test]
+    fn snap_follows_the_dragged_side() {
+        let size = snap_to_aspect_ratio(PhysicalSize::new(400, 100), 2.0, false, 32, MONITOR);
+        assert_eq!(size, PhysicalSize::new(400, 200));
+        let size = snap_to_aspect_ratio(PhysicalSize::new(400, 100), 2.0, true, 32, MONITOR);
+        assert_eq!(size, PhysicalSize::new(200, 100));
+    }
+
+    #[test]
+    fn window_uv_round_trip() {
+        let regions = [
+            (vec2(0.0, 0.0), vec2(1.0, 1.0)),
+            (vec2(0.25, 0.4), vec2(0.5, 0.6)),
+            (vec2(0.9, 0.0), vec2(0.901, 0.002)),
+            // Zoomed out past the image edges.
+            (vec2(-0.5, -1.0), vec2(1.5, 2.0)),
+        ];
+        let sizes = [(800, 600), (600, 800), (1920, 1080), (33, 4000), (0, 0)];
+        for (min_uv, max_uv) in regions {
+            for (width, height) in sizes {
+                let size = PhysicalSize::new(width, height);
+                for aspect_ratio in [1.0, 4.0 / 3.0, 0.01, 100.0] {
+                    for pos in [vec2(0.0, 0.0), vec2(17.5, 3.25), vec2(400.0, 300.0)] {
+                        let uv = window_to_uv(size, aspect_ratio, (min_uv, max_uv), pos);
+                        let back = uv_to_window(size, aspect_ratio, (min_uv, max_uv), uv);
+                        // UVs are `f32`, so zooming in far costs some precision.
+                        assert!(
+                            back.dist(pos) < 0.05,
+                            "{pos:?} -> {uv:?} -> {back:?} in {width}x{height}, \
+                             region {min_uv:?}..{max_uv:?}, aspect ratio {aspect_ratio}",
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn window_corners_map_to_region_corners() {
+        let size = PhysicalSize::new(800, 400);
+        let region = (vec2(0.25, 0.5), vec2(0.75, 0.75));
+        let uv = window_to_uv(size, 2.0, region, vec2(0.0, 0.0));
+        assert!(uv.dist(region.0) < 1e-6);
+        let uv = window_to_uv(size, 2.0, region, vec2(800.0, 400.0));
+        assert!(uv.dist(region.1) < 1e-6);
+        // Letterboxed: the image only covers the middle half horizontally.
+        let pos = uv_to_window(size, 1.0, region, region.0);
+        assert!(pos.dist(vec2(200.0, 0.0)) < 1e-3);
+    }
+}
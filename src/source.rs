@@ -0,0 +1,90 @@
+//! Where images are read from.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::fetch;
+
+/// Where an image is read from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Source {
+    /// A file.
+    Path(PathBuf),
+    /// A file descriptor inherited from the parent process (`--fd`).
+    Fd(i32),
+    /// An `http://` or `https://` URL.
+    Url(String),
+}
+
+impl Source {
+    /// Interprets a path given by the user (on the command line, or from the recently opened
+    /// files), which may also be a URL.
+    pub fn from_path(path: PathBuf) -> Self {
+        match path.to_str() {
+            Some(s) if s.starts_with("http://") || s.starts_with("https://") => {
+                Self::Url(s.to_string())
+            }
+            _ => Self::Path(path),
+        }
+    }
+
+    /// Returns the path of the file, unless the image comes from somewhere else.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Path(path) => Some(path),
+            Self::Fd(_) | Self::Url(_) => None,
+        }
+    }
+
+    /// Returns the name of the image, as shown in the window title: the file name, or the last
+    /// segment of the URL.
+    pub fn name(&self) -> String {
+        match self {
+            Self::Path(path) => path.file_name().map_or_else(
+                || path.display().to_string(),
+                |name| name.to_string_lossy().into(),
+            ),
+            Self::Fd(fd) => format!("fd {fd}"),
+            Self::Url(url) => fetch::file_name(url).to_string(),
+        }
+    }
+}
+
+/// An empty path, until an image is opened.
+impl Default for Source {
+    fn default() -> Self {
+        Self::Path(PathBuf::new())
+    }
+}
+
+/// Formats the source like it was given on the command line (`--fd` becomes `fd N`).
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Path(path) => path.display().fmt(f),
+            Self::Fd(fd) => write!(f, "fd {fd}"),
+            Self::Url(url) => f.write_str(url),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_path() {
+        assert_eq!(
+            Source::from_path("https://example.com/a.png".into()),
+            Source::Url("https://example.com/a.png".into())
+        );
+        // Only `--fd` reads from file descriptors; this is just an oddly named file.
+        assert_eq!(
+            Source::from_path("fd:3".into()),
+            Source::Path("fd:3".into())
+        );
+        assert_eq!(Source::Fd(3).path(), None);
+    }
+}
@@ -0,0 +1,49 @@
+//! SVG rasterization.
+
+use std::path::Path;
+
+use anyhow::Context;
+use image::RgbaImage;
+use resvg::{tiny_skia, usvg};
+
+pub fn is_svg(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg") || ext.eq_ignore_ascii_case("svgz"))
+}
+
+/// Renders an SVG document so that it fits within `max_width`x`max_height` pixels.
+pub fn rasterize(data: &[u8], max_width: u32, max_height: u32) -> anyhow::Result<RgbaImage> {
+    let mut options = usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+    let tree = usvg::Tree::from_data(data, &options).context("failed to parse SVG")?;
+
+    let size = tree.size();
+    let scale = f32::min(
+        max_width as f32 / size.width(),
+        max_height as f32 / size.height(),
+    );
+    let width = (size.width() * scale).round().max(1.0) as u32;
+    let height = (size.height() * scale).round().max(1.0) as u32;
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).context("invalid SVG size")?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    log::debug!(
+        "rasterized {}x{} SVG at {width}x{height}",
+        size.width(),
+        size.height(),
+    );
+
+    // tiny-skia produces premultiplied pixels, but the preprocessing step expects straight alpha.
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        })
+        .collect();
+    Ok(RgbaImage::from_raw(width, height, pixels).unwrap())
+}
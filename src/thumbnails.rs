@@ -0,0 +1,54 @@
+//! Thumbnail generation for the thumbnail strip.
+
+use std::{
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+use image::{imageops, RgbaImage};
+use winit::{event_loop::EventLoopProxy, window::WindowId};
+
+use crate::{load, overlay::THUMBNAIL_SIZE, source::Source, UserEvent};
+
+/// Starts a thread that generates thumbnails for the images sent to the returned channel.
+///
+/// Each thumbnail is sent back as a [`UserEvent::Thumbnail`] for the window `window`. The thread
+/// exits when the channel is dropped.
+pub fn spawn_generator(proxy: EventLoopProxy<UserEvent>, window: WindowId) -> Sender<Source> {
+    let (sender, receiver) = mpsc::channel::<Source>();
+    thread::spawn(move || {
+        for source in receiver {
+            let thumbnail = match generate(&source) {
+                Ok(thumbnail) => Some(thumbnail),
+                Err(e) => {
+                    log::warn!("failed to create thumbnail of '{source}': {e:#}");
+                    None
+                }
+            };
+            if proxy
+                .send_event(UserEvent::Thumbnail(window, source, thumbnail))
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+    sender
+}
+
+/// Creates an sRGB thumbnail of the first frame of the image from `source` that fits within
+/// [`THUMBNAIL_SIZE`].
+fn generate(source: &Source) -> anyhow::Result<RgbaImage> {
+    let loaded = load::load(source)?;
+    // The atlas expects sRGB.
+    let image = load::to_srgb8(&loaded.images[0]);
+
+    let scale = f32::min(
+        THUMBNAIL_SIZE as f32 / image.width() as f32,
+        THUMBNAIL_SIZE as f32 / image.height() as f32,
+    )
+    .min(1.0);
+    let width = ((image.width() as f32 * scale).round() as u32).clamp(1, THUMBNAIL_SIZE);
+    let height = ((image.height() as f32 * scale).round() as u32).clamp(1, THUMBNAIL_SIZE);
+    Ok(imageops::thumbnail(&image, width, height))
+}
@@ -0,0 +1,39 @@
+//! Watching the displayed file for changes.
+
+use std::path::Path;
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Calls `on_change` whenever the file at `path` is written to or replaced.
+///
+/// Many programs save files by writing a temporary file and renaming it over the original, so
+/// this watches the containing directory rather than the file itself, whose watch would be lost
+/// with the original file. The file may briefly not exist while it is being replaced.
+pub fn watch(
+    path: &Path,
+    on_change: impl Fn() + Send + 'static,
+) -> notify::Result<RecommendedWatcher> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().map(ToOwned::to_owned);
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let event = match res {
+            Ok(event) => event,
+            Err(e) => {
+                log::warn!("file watcher error: {e}");
+                return;
+            }
+        };
+        if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            return;
+        }
+        if event.paths.iter().any(|p| p.file_name() == name.as_deref()) {
+            on_change();
+        }
+    })?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    Ok(watcher)
+}
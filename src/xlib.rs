@@ -0,0 +1,11 @@
+//! Lazily loaded Xlib bindings, shared by everything that talks to X11 directly.
+
+use std::sync::OnceLock;
+
+use x11_dl::{error::OpenError, xlib::Xlib};
+
+/// Returns the Xlib function table, or `None` if `libX11` couldn't be loaded.
+pub fn get() -> Option<&'static Xlib> {
+    static XLIB: OnceLock<Result<Xlib, OpenError>> = OnceLock::new();
+    XLIB.get_or_init(Xlib::open).as_ref().ok()
+}